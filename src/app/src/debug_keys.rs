@@ -0,0 +1,52 @@
+use emulator_core::{EmulatorHandle, Layer, Overlay};
+use minifb::{Key, KeyRepeat, Window};
+
+/// Hotkeys for switching PPU layers and debugging overlays on and off.
+///
+/// F1/F2/F3 toggle the background, window and sprite layers. F5/F6/F7
+/// toggle the sprite bounds, window bounds and tile grid overlays.
+pub struct DebugKeys {
+    layers: [(Key, Layer, bool); 3],
+    overlays: [(Key, Overlay, bool); 3],
+}
+
+impl DebugKeys {
+    pub fn new() -> Self {
+        Self {
+            layers: [
+                (Key::F1, Layer::Background, true),
+                (Key::F2, Layer::Window, true),
+                (Key::F3, Layer::Sprites, true),
+            ],
+            overlays: [
+                (Key::F5, Overlay::SpriteBounds, false),
+                (Key::F6, Overlay::WindowBounds, false),
+                (Key::F7, Overlay::TileGrid, false),
+            ],
+        }
+    }
+
+    /// Check for hotkeys pressed since the last window update and forward
+    /// any changes to the emulator.
+    pub fn update(&mut self, window: &Window, handle: &EmulatorHandle) {
+        for (key, layer, enabled) in self.layers.iter_mut() {
+            if window.is_key_pressed(*key, KeyRepeat::No) {
+                *enabled = !*enabled;
+                handle.set_layer_enabled(*layer, *enabled);
+            }
+        }
+
+        for (key, overlay, enabled) in self.overlays.iter_mut() {
+            if window.is_key_pressed(*key, KeyRepeat::No) {
+                *enabled = !*enabled;
+                handle.set_overlay_enabled(*overlay, *enabled);
+            }
+        }
+    }
+}
+
+impl Default for DebugKeys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
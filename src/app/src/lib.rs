@@ -1,10 +1,21 @@
+mod debug_keys;
 mod file_saver;
 mod joypad_manager;
+mod palettes;
+mod recordings;
+mod screenshots;
+mod vram_viewer;
 mod window_buffer;
 
 pub const WIDTH: usize = 160;
 pub const HEIGHT: usize = 144;
 
+pub use debug_keys::DebugKeys;
 pub use file_saver::FileSaver;
 pub use joypad_manager::JoypadManager;
+pub use palettes::PaletteCycle;
+pub use palettes::AUTUMN;
+pub use recordings::toggle_recording;
+pub use screenshots::save_screenshot;
+pub use vram_viewer::VramViewer;
 pub use window_buffer::WindowBuffer;
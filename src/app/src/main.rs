@@ -1,26 +1,180 @@
-use app::{FileSaver, JoypadManager, WindowBuffer, HEIGHT, WIDTH};
-use emulator_core::Cartridge;
-use std::{fs::File, io::Read, sync::Arc};
+use app::{
+    save_screenshot, toggle_recording, DebugKeys, FileSaver, JoypadManager, PaletteCycle,
+    VramViewer, WindowBuffer, HEIGHT, WIDTH,
+};
+use emulator_core::{
+    Filter, GdbServer, Ghosting, Header, PixelGrid, Recorder, RecordingFormat, Renderer, Scaler,
+    ToneCurve, TraceOptions, SGB_HEIGHT, SGB_WIDTH,
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Read},
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// ROM loaded from ./roms/<name>.gb, also used to name save files and
+/// screenshots.
+const ROM_NAME: &str = "pokemon-red";
+
+/// How many frames of the main window between refreshes of the VRAM viewer.
+const VRAM_VIEWER_REFRESH_FRAMES: u32 = 10;
+
+/// Overall integer scale aimed for with the dot-matrix effect of --lcd.
+const LCD_SCALE: usize = 4;
+/// How much of each frame lingers into the next with --lcd.
+const LCD_PERSISTENCE: f32 = 0.5;
 
 pub fn main() {
+    // Pass --vram-viewer to open extra windows showing the contents of VRAM.
+    let show_vram_viewer = std::env::args().any(|arg| arg == "--vram-viewer");
+    // Pass --lcd to simulate the look of the original screen.
+    let simulate_lcd = std::env::args().any(|arg| arg == "--lcd");
+    // Pass --filter=<name> to pick an upscaling filter (none, scale2x,
+    // scale3x, scale4x, eagle or xbr).
+    let filter = std::env::args()
+        .find_map(|arg| arg.strip_prefix("--filter=").map(str::to_string))
+        .map(|name| name.parse::<Filter>().expect("Should be a known filter"))
+        .unwrap_or(Filter::None);
+    // Pass --record-format=<format> to pick the format recorded with F9 (gif,
+    // y4m or raw).
+    let record_format = arg_value("--record-format")
+        .map(|name| name.parse().expect("Should be a known recording format"))
+        .unwrap_or(RecordingFormat::Gif);
+    // Pass --headless --frames=<n> --record=<path> to record the first n
+    // frames without opening a window, as fast as possible.
+    let headless = std::env::args().any(|arg| arg == "--headless");
+    // Pass --sgb to play as a Super Game Boy, with the game's colours and
+    // border.
+    let sgb = std::env::args().any(|arg| arg == "--sgb");
+    // Pass --illegal-opcode=<policy> to choose what happens when the game
+    // runs an opcode which doesn't exist (lockup, error or break). Errors
+    // stop the emulator, breaks pause it.
+    let illegal_opcode_policy = arg_value("--illegal-opcode")
+        .map(|name| {
+            name.parse()
+                .expect("Should be a known illegal opcode policy")
+        })
+        .unwrap_or_default();
+    // Pass --memory-timing=per-instruction to run the timer, PPU and DMA
+    // once per instruction, rather than at each memory access.
+    let memory_timing = arg_value("--memory-timing")
+        .map(|name| name.parse().expect("Should be a known memory timing"))
+        .unwrap_or_default();
+    // Pass --trace=<path> to write a gameboy-doctor style line for every
+    // instruction run. Narrow it down with --trace-pc=<start>-<end> (in
+    // hex) and --trace-bank=<n>, and add --trace-disasm and
+    // --trace-interrupts to annotate it.
+    let trace = arg_value("--trace").map(|path| (path, trace_options()));
+    // Pass --gdb=<port> to debug the game with gdb, or another client of the
+    // GDB remote protocol, connected to that port on localhost.
+    let gdb_server = arg_value("--gdb").map(|port| {
+        let port: u16 = port.parse().expect("Should be a port number");
+        let listener = TcpListener::bind(("127.0.0.1", port)).expect("Should listen for gdb");
+        println!("Waiting for gdb on 127.0.0.1:{port}");
+        GdbServer::new(listener).expect("Should create GDB server")
+    });
+    let rom = read_rom(ROM_NAME);
+    let header = Header::new(&rom);
+
+    // Start with the palette a CGB would pick for the game. Pass
+    // --palette=<name or file> to start with a built in palette, or one
+    // loaded from a palette file.
+    let mut palettes = PaletteCycle::new(header.cgb_palette());
+    if let Some(palette) = arg_value("--palette") {
+        palettes
+            .select(&palette)
+            .expect("Should be a known palette or palette file");
+    }
+
+    // The dot-matrix effect scales the filtered frame up further, to around
+    // the same size as the window would be without any effects.
+    let grid_scale = if simulate_lcd {
+        (LCD_SCALE / filter.factor()).max(1)
+    } else {
+        1
+    };
+    let frame_scale = filter.factor() * grid_scale;
+
     // Buffer written to by PPU and rendered by window
     let window_buffer = Arc::new(WindowBuffer::new());
+    let effects: Arc<dyn Renderer> = if simulate_lcd {
+        let grid = PixelGrid::new(window_buffer.clone(), grid_scale);
+        let scaler = Scaler::new(grid, filter);
+        Arc::new(Ghosting::new(ToneCurve::dmg_green(scaler), LCD_PERSISTENCE))
+    } else {
+        Arc::new(Scaler::new(window_buffer.clone(), filter))
+    };
+    let recorder = Arc::new(Recorder::new(effects));
 
     // Setup Emulator
-    let cartridge = cartridge_from_filepath("pokemon-red");
+    let saver = Box::new(FileSaver::new(ROM_NAME));
+    let cartridge = emulator_core::create_cartridge(rom, saver);
     let joypad = Arc::new(emulator_core::Joypad::new());
-    let ppu = emulator_core::PPU::new(window_buffer.clone());
+    let mut ppu = emulator_core::PPU::new(recorder.clone());
+    ppu.set_palette(palettes.current().clone());
     let mmu = emulator_core::MMU::new(ppu, cartridge, joypad.clone());
-    let cpu = emulator_core::CPU::new(mmu);
+    let mut cpu = if sgb {
+        emulator_core::CPU::new_sgb(mmu)
+    } else {
+        emulator_core::CPU::new(mmu)
+    };
+    cpu.set_illegal_opcode_policy(illegal_opcode_policy);
+    cpu.set_memory_timing(memory_timing);
+    if let Some((path, options)) = trace {
+        let file = File::create(&path).expect("Should create trace");
+        cpu.start_trace(options, file).expect("Should start trace");
+    }
 
     let emulator = emulator_core::Emulator::new(cpu);
 
+    if headless {
+        let frames = arg_value("--frames")
+            .map(|frames| frames.parse().expect("Should be a number of frames"))
+            .expect("Should pass --frames with --headless");
+        let path = arg_value("--record").expect("Should pass --record with --headless");
+        let format = arg_value("--record-format")
+            .map(|_| record_format)
+            .or_else(|| path.rsplit('.').next()?.parse().ok())
+            .unwrap_or(record_format);
+
+        let file = File::create(&path).expect("Should create recording");
+        recorder
+            .start(format, BufWriter::new(file))
+            .expect("Should start recording");
+        let result = emulator.run_frames(frames);
+        recorder.stop().expect("Should finish recording");
+
+        match result {
+            Ok(()) => println!("Recorded {frames} frames to {path}"),
+            Err(err) => eprintln!("Emulator stopped: {err}"),
+        }
+        return;
+    }
+
+    // Frames which have already been scaled up need less scaling by the
+    // window.
+    let (window_scale, window_factor) = match frame_scale {
+        1 => (minifb::Scale::X4, 4),
+        2 => (minifb::Scale::X2, 2),
+        _ => (minifb::Scale::X1, 1),
+    };
+
+    let (width, height) = if sgb {
+        (SGB_WIDTH, SGB_HEIGHT)
+    } else {
+        (WIDTH, HEIGHT)
+    };
+
     let mut window = minifb::Window::new(
         "GB Emulator",
-        WIDTH,
-        HEIGHT,
+        width * frame_scale,
+        height * frame_scale,
         minifb::WindowOptions {
-            scale: minifb::Scale::X4,
+            scale: window_scale,
             ..minifb::WindowOptions::default()
         },
     )
@@ -35,23 +189,123 @@ pub fn main() {
 
     let handle = emulator.spawn();
 
-    // Start the Window and update with the current value of the buffer
-    while window.is_open() && !window.is_key_down(minifb::Key::Escape) {
-        window
-            .update_with_buffer(window_buffer.buffer(), WIDTH, HEIGHT)
-            .expect("Should update")
+    // Serve gdb (or another GDB remote protocol client) alongside the window
+    // until it closes.
+    let gdb_stop = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        if let Some(server) = gdb_server {
+            let (handle, gdb_stop) = (&handle, &gdb_stop);
+            scope.spawn(move || {
+                if let Err(err) = server.run(handle, gdb_stop) {
+                    eprintln!("GDB server stopped: {err}");
+                }
+            });
+        }
+
+        let mut debug_keys = DebugKeys::new();
+        let mut vram_viewer = show_vram_viewer.then(VramViewer::new);
+        let mut frame: u32 = 0;
+
+        // Start the Window and update with the current value of the buffer
+        while window.is_open() && !window.is_key_down(minifb::Key::Escape) {
+            window_buffer.with_latest(|frame| {
+                window
+                    .update_with_buffer(frame.pixels, frame.width, frame.height)
+                    .expect("Should update")
+            });
+
+            debug_keys.update(&window, &handle);
+
+            // F12 saves a screenshot at native size, Shift+F12 at the size of
+            // the window.
+            if window.is_key_pressed(minifb::Key::F12, minifb::KeyRepeat::No) {
+                let shift = window.is_key_down(minifb::Key::LeftShift)
+                    || window.is_key_down(minifb::Key::RightShift);
+                let scale = if shift {
+                    frame_scale * window_factor
+                } else {
+                    1
+                };
+
+                match save_screenshot(&handle, ROM_NAME, scale) {
+                    Ok(path) => println!("Saved screenshot to {}", path.display()),
+                    Err(err) => eprintln!("Failed to save screenshot: {err}"),
+                }
+            }
+
+            // F8 switches to the next palette.
+            if window.is_key_pressed(minifb::Key::F8, minifb::KeyRepeat::No) {
+                let palette = palettes.next(&handle);
+                println!("Palette: {}", palette.name);
+            }
+
+            // F9 starts and stops recording.
+            if window.is_key_pressed(minifb::Key::F9, minifb::KeyRepeat::No) {
+                match toggle_recording(&recorder, ROM_NAME, record_format) {
+                    Ok(Some(path)) => println!("Recording to {}", path.display()),
+                    Ok(None) => println!("Stopped recording"),
+                    Err(err) => eprintln!("Failed to record: {err}"),
+                }
+            }
+
+            frame = frame.wrapping_add(1);
+
+            if let Some(viewer) = vram_viewer.as_mut() {
+                if frame.is_multiple_of(VRAM_VIEWER_REFRESH_FRAMES) {
+                    if let Some(views) = handle.debug_views() {
+                        viewer.update(&views);
+                    }
+                }
+            }
+        }
+
+        gdb_stop.store(true, Ordering::Relaxed);
+    });
+
+    if let Some(Err(err)) = handle.stop_trace() {
+        eprintln!("Failed to finish trace: {err}");
+    }
+
+    if let Err(err) = handle.shutdown() {
+        eprintln!("Emulator stopped: {err}");
     }
 
-    handle.shutdown();
+    if let Err(err) = recorder.stop() {
+        eprintln!("Failed to finish recording: {err}");
+    }
+}
+
+/// Value of a `--name=value` command line argument.
+fn arg_value(name: &str) -> Option<String> {
+    let prefix = format!("{name}=");
+    std::env::args().find_map(|arg| arg.strip_prefix(&prefix).map(str::to_string))
+}
+
+/// Filters and annotations for --trace, from the other --trace-* arguments.
+fn trace_options() -> TraceOptions {
+    let pc_range = arg_value("--trace-pc").map(|range| {
+        let (start, end) = range
+            .split_once('-')
+            .expect("Should be a range of addresses, like 0150-01FF");
+        let address = |s: &str| u16::from_str_radix(s, 16).expect("Should be a hex address");
+        address(start)..=address(end)
+    });
+    let bank =
+        arg_value("--trace-bank").map(|bank| bank.parse().expect("Should be a ROM bank number"));
+
+    TraceOptions {
+        pc_range,
+        bank,
+        disassembly: std::env::args().any(|arg| arg == "--trace-disasm"),
+        interrupts: std::env::args().any(|arg| arg == "--trace-interrupts"),
+    }
 }
 
-fn cartridge_from_filepath(rom_name: &str) -> Box<dyn Cartridge> {
+fn read_rom(rom_name: &str) -> Vec<u8> {
     let rom_path = "./roms/".to_string() + rom_name + ".gb";
     let mut fp = File::open(rom_path).expect("Should exist");
     let mut data = Vec::new();
     fp.read_to_end(&mut data).expect("Should read");
 
-    let saver = Box::new(FileSaver::new(rom_name));
-
-    emulator_core::create_cartridge(data, saver)
+    data
 }
@@ -0,0 +1,64 @@
+use std::fs;
+use std::io;
+
+use emulator_core::{EmulatorHandle, Palette};
+
+/// The app's original orange colour scheme.
+pub const AUTUMN: Palette = Palette::uniform("Autumn", [0xD58936, 0xA44200, 0x69140E, 0x3C1518]);
+
+/// The palettes which can be switched between while running: the palette a
+/// CGB would pick for the game, the app's own palette, the built in presets,
+/// the CGB's button combination palettes and any loaded from files.
+pub struct PaletteCycle {
+    palettes: Vec<Palette>,
+    current: usize,
+}
+
+impl PaletteCycle {
+    /// Start with the given palette for the game, usually from
+    /// [emulator_core::Header::cgb_palette].
+    pub fn new(game: Palette) -> Self {
+        let mut palettes = vec![game, AUTUMN];
+        palettes.extend(Palette::PRESETS);
+        palettes.extend(Palette::cgb_button_combos());
+
+        Self {
+            palettes,
+            current: 0,
+        }
+    }
+
+    /// Select a palette by preset name, or load it from a palette file if
+    /// there's no preset with that name. Palettes loaded from files are
+    /// added to the cycle.
+    pub fn select(&mut self, name_or_path: &str) -> io::Result<()> {
+        if let Some(index) = self
+            .palettes
+            .iter()
+            .position(|palette| palette.name.eq_ignore_ascii_case(name_or_path))
+        {
+            self.current = index;
+            return Ok(());
+        }
+
+        let palette = fs::read_to_string(name_or_path)?
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        self.palettes.push(palette);
+        self.current = self.palettes.len() - 1;
+
+        Ok(())
+    }
+
+    pub fn current(&self) -> &Palette {
+        &self.palettes[self.current]
+    }
+
+    /// Switch to the next palette, wrapping around to the first.
+    pub fn next(&mut self, handle: &EmulatorHandle) -> &Palette {
+        self.current = (self.current + 1) % self.palettes.len();
+        handle.set_palette(self.current().clone());
+
+        self.current()
+    }
+}
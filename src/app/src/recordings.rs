@@ -0,0 +1,34 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use emulator_core::{Recorder, RecordingFormat, Renderer};
+
+const RECORDING_DIR: &str = "recordings";
+
+/// Start recording to `recordings/<rom>-<timestamp>.<ext>`, or stop the
+/// recording in progress. Returns the path of a newly started recording.
+pub fn toggle_recording<R: Renderer>(
+    recorder: &Recorder<R>,
+    rom_name: &str,
+    format: RecordingFormat,
+) -> io::Result<Option<PathBuf>> {
+    if recorder.is_recording() {
+        recorder.stop()?;
+        return Ok(None);
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or_default();
+
+    fs::create_dir_all(RECORDING_DIR)?;
+    let path =
+        PathBuf::from(RECORDING_DIR).join(format!("{rom_name}-{timestamp}.{}", format.extension()));
+
+    recorder.start(format, BufWriter::new(File::create(&path)?))?;
+
+    Ok(Some(path))
+}
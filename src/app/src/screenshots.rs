@@ -0,0 +1,34 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use emulator_core::EmulatorHandle;
+
+const SCREENSHOT_DIR: &str = "screenshots";
+
+/// Save the last completed frame as `screenshots/<rom>-<timestamp>.png`,
+/// scaled up by the given factor. Returns the path of the saved file.
+pub fn save_screenshot(
+    handle: &EmulatorHandle,
+    rom_name: &str,
+    scale: usize,
+) -> io::Result<PathBuf> {
+    let screenshot = handle
+        .screenshot()
+        .ok_or_else(|| io::Error::other("Emulator is not running"))?
+        .scaled(scale);
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or_default();
+
+    fs::create_dir_all(SCREENSHOT_DIR)?;
+    let path = PathBuf::from(SCREENSHOT_DIR).join(format!("{rom_name}-{timestamp}.png"));
+
+    let mut writer = BufWriter::new(File::create(&path)?);
+    screenshot.write_png(&mut writer)?;
+
+    Ok(path)
+}
@@ -0,0 +1,69 @@
+use emulator_core::{DebugImage, DebugViews};
+use minifb::{Scale, Window, WindowOptions};
+
+/// Extra windows which display the PPU debugging views (tile data,
+/// background maps, OAM and palettes). Views are captured from the running
+/// emulator and pushed in with [VramViewer::update].
+pub struct VramViewer {
+    tiles: Window,
+    background_map_0: Window,
+    background_map_1: Window,
+    oam: Window,
+    palettes: Window,
+}
+
+impl VramViewer {
+    pub fn new() -> Self {
+        Self {
+            tiles: viewer_window("Tile Data", 128, 192, Scale::X2),
+            background_map_0: viewer_window("BG Map $9800", 256, 256, Scale::X2),
+            background_map_1: viewer_window("BG Map $9C00", 256, 256, Scale::X2),
+            oam: viewer_window("OAM", 64, 80, Scale::X4),
+            palettes: viewer_window("Palettes (BGP, OBP0, OBP1)", 32, 24, Scale::X8),
+        }
+    }
+
+    /// Redraw every viewer window with the given views. Windows which have
+    /// been closed are skipped.
+    pub fn update(&mut self, views: &DebugViews) {
+        update_window(&mut self.tiles, &views.tile_sheet);
+        update_window(&mut self.background_map_0, &views.background_map_0);
+        update_window(&mut self.background_map_1, &views.background_map_1);
+        update_window(&mut self.oam, &views.oam);
+        update_window(&mut self.palettes, &views.palette_swatches);
+    }
+}
+
+impl Default for VramViewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn viewer_window(title: &str, width: usize, height: usize, scale: Scale) -> Window {
+    let mut window = Window::new(
+        title,
+        width,
+        height,
+        WindowOptions {
+            scale,
+            ..WindowOptions::default()
+        },
+    )
+    .expect("Should create window");
+
+    // Rate limiting is left to the main window.
+    window.set_target_fps(0);
+
+    window
+}
+
+fn update_window(window: &mut Window, image: &DebugImage) {
+    if !window.is_open() {
+        return;
+    }
+
+    window
+        .update_with_buffer(&image.pixels, image.width, image.height)
+        .expect("Should update")
+}
@@ -1,16 +1,11 @@
-use std::cell::UnsafeCell;
-
-use emulator_core::Renderer;
-
-use crate::{HEIGHT, WIDTH};
+use emulator_core::{Frame, FrameBuffer, Renderer};
 
+/// Frames rendered by the PPU, shared between the emulator thread and the
+/// window.
 pub struct WindowBuffer {
-    buffer: UnsafeCell<[u32; WIDTH * HEIGHT]>,
+    frames: FrameBuffer,
 }
 
-unsafe impl Sync for WindowBuffer {}
-unsafe impl Send for WindowBuffer {}
-
 impl Default for WindowBuffer {
     fn default() -> Self {
         Self::new()
@@ -20,29 +15,18 @@ impl Default for WindowBuffer {
 impl WindowBuffer {
     pub fn new() -> Self {
         Self {
-            buffer: UnsafeCell::new([0; WIDTH * HEIGHT]),
+            frames: FrameBuffer::new(),
         }
     }
 
-    pub fn buffer(&self) -> &[u32; WIDTH * HEIGHT] {
-        unsafe { &*self.buffer.get() }
+    /// Call the given function with the most recently completed frame.
+    pub fn with_latest<T>(&self, f: impl FnOnce(Frame) -> T) -> T {
+        self.frames.with_latest(f)
     }
 }
 
 impl Renderer for WindowBuffer {
-    fn render(&self, buffer: [u32; WIDTH * HEIGHT]) {
-        unsafe {
-            let value = self.buffer.get();
-            *value = buffer;
-        }
-    }
-
-    fn palette(&self, color: emulator_core::Color) -> u32 {
-        match color {
-            emulator_core::Color::White => 0xd58936,
-            emulator_core::Color::LightGray => 0xa44200,
-            emulator_core::Color::DarkGray => 0x69140e,
-            emulator_core::Color::Black => 0x3c1518,
-        }
+    fn render(&self, frame: &Frame) {
+        self.frames.render(frame)
     }
 }
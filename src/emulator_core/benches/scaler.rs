@@ -0,0 +1,39 @@
+//! Each filter has to run once per frame, well within the ~16.7ms frame
+//! budget alongside emulation. Run with `cargo bench -p emulator_core`.
+use criterion::{criterion_group, criterion_main, Criterion};
+use emulator_core::Filter;
+
+const WIDTH: usize = 160;
+const HEIGHT: usize = 144;
+
+/// A frame with a mix of flat areas and edges, roughly like a game screen.
+fn test_frame() -> Vec<u32> {
+    const SHADES: [u32; 4] = [0xFFFFFF, 0xA8A8A8, 0x454545, 0x000000];
+
+    (0..WIDTH * HEIGHT)
+        .map(|i| {
+            let (x, y) = (i % WIDTH, i / WIDTH);
+            SHADES[((x / 8 + y / 8) + (x * y) % 3) % 4]
+        })
+        .collect()
+}
+
+fn scalers(c: &mut Criterion) {
+    let frame = test_frame();
+    let mut output = Vec::new();
+
+    for (name, filter) in [
+        ("scale2x", Filter::Scale2x),
+        ("scale3x", Filter::Scale3x),
+        ("scale4x", Filter::Scale4x),
+        ("eagle", Filter::Eagle),
+        ("xbr-lite", Filter::XbrLite),
+    ] {
+        c.bench_function(name, |b| {
+            b.iter(|| filter.apply(&frame, WIDTH, HEIGHT, &mut output))
+        });
+    }
+}
+
+criterion_group!(benches, scalers);
+criterion_main!(benches);
@@ -21,6 +21,11 @@ pub trait Cartridge: Send {
     /// Write a byte to the cartridge's ROM
     fn write_rom(&mut self, address: u16, value: u8);
 
+    /// The ROM bank currently mapped at 0x4000-0x7FFF
+    fn rom_bank(&self) -> usize {
+        1
+    }
+
     /// Save the state of RAM
     fn save(&mut self) {}
 
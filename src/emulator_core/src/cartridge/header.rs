@@ -1,4 +1,15 @@
+use crate::mmu::Palette;
+
+/// The cartridge header, parsed from $0100-$014F of the ROM.
 pub struct Header {
+    /// Game title, padded with zeros. Later cartridges use the last 4-5
+    /// bytes for a manufacturer code and CGB flag.
+    pub title: [u8; 16],
+    /// Licensee code at $014B. 0x33 means the licensee is given by
+    /// [Header::new_licensee] instead.
+    pub old_licensee: u8,
+    /// Two ASCII characters at $0144-$0145.
+    pub new_licensee: [u8; 2],
     pub cartridge_type: CartridgeType,
     // Enum for rom size
     pub rom_size: ROMSize,
@@ -9,12 +20,34 @@ pub struct Header {
 impl Header {
     pub fn new(data: &[u8]) -> Self {
         Self {
+            title: data[0x134..=0x143].try_into().expect("Should be 16 bytes"),
+            old_licensee: data[0x14B],
+            new_licensee: [data[0x144], data[0x145]],
             cartridge_type: (&data[0x147]).try_into().expect("Unknown cartridge type"),
             rom_size: (&data[0x148]).try_into().expect("Unknown rom size"),
             ram_size: (&data[0x149]).try_into().expect("Unknown ram size"),
         }
     }
 
+    /// Sum of the title bytes, used by the CGB boot ROM to pick a colour
+    /// palette for DMG games.
+    pub fn title_checksum(&self) -> u8 {
+        self.title
+            .iter()
+            .fold(0, |sum, &byte| sum.wrapping_add(byte))
+    }
+
+    /// Was the game published by Nintendo.
+    pub fn is_nintendo(&self) -> bool {
+        self.old_licensee == 0x01 || (self.old_licensee == 0x33 && self.new_licensee == *b"01")
+    }
+
+    /// The colour palette a CGB would pick for the game when playing it in
+    /// DMG mode.
+    pub fn cgb_palette(&self) -> Palette {
+        Palette::cgb_compatibility(self.title_checksum(), self.title[3], self.is_nintendo())
+    }
+
     pub fn rom_bank_count(&self) -> usize {
         match self.rom_size {
             ROMSize::KB32 => 2,
@@ -142,3 +175,32 @@ impl TryFrom<&u8> for RAMSize {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom_with_title(title: &[u8], old_licensee: u8, new_licensee: &[u8; 2]) -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x134..0x134 + title.len()].copy_from_slice(title);
+        rom[0x144..=0x145].copy_from_slice(new_licensee);
+        rom[0x14B] = old_licensee;
+        rom
+    }
+
+    #[test]
+    fn title_and_checksum() {
+        let header = Header::new(&rom_with_title(b"TETRIS", 0x01, b"\0\0"));
+
+        assert_eq!(&header.title[..7], b"TETRIS\0");
+        assert_eq!(header.title_checksum(), 0xDB);
+    }
+
+    #[test]
+    fn licensee() {
+        assert!(Header::new(&rom_with_title(b"", 0x01, b"\0\0")).is_nintendo());
+        assert!(Header::new(&rom_with_title(b"", 0x33, b"01")).is_nintendo());
+        assert!(!Header::new(&rom_with_title(b"", 0x33, b"08")).is_nintendo());
+        assert!(!Header::new(&rom_with_title(b"", 0x08, b"01")).is_nintendo());
+    }
+}
@@ -68,6 +68,10 @@ impl Cartridge for MBC1 {
         *self.rom.get(remapped_address).unwrap_or(&0xff)
     }
 
+    fn rom_bank(&self) -> usize {
+        self.rom_bank as usize
+    }
+
     /// Writing to ROM, doesn't actually write to ROM, instead the MBC
     /// interprets writes to ROM memory address as control registers
     /// which alter the state of the MBC unit. The address still
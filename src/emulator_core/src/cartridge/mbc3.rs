@@ -99,6 +99,10 @@ impl Cartridge for MBC3 {
         *self.rom.get(remapped_address).unwrap_or(&0xff)
     }
 
+    fn rom_bank(&self) -> usize {
+        self.rom_bank as usize
+    }
+
     /// Writing to ROM, doesn't actually write to ROM, instead the MBC
     /// interprets writes to ROM memory address as control registers
     /// which alter the state of the MBC unit. The address still
@@ -333,6 +337,7 @@ mod tests {
                 // Switch to ith bank
                 mbc3.write_rom(0x2000, i as u8);
                 assert_eq!(mbc3.read_rom(0x4000), i as u8);
+                assert_eq!(mbc3.rom_bank(), i);
             }
         }
 
@@ -8,7 +8,7 @@ pub struct NoMBC {
 
 impl NoMBC {
     pub fn new(rom: Vec<u8>) -> Self {
-        let ram = vec![0; 8000];
+        let ram = vec![0; 0x2000];
         NoMBC { rom, ram }
     }
 }
@@ -32,3 +32,18 @@ impl Cartridge for NoMBC {
 
     fn write_rom(&mut self, _address: u16, _value: u8) {}
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn ram_covers_the_whole_8kib() {
+        let mut no_mbc = NoMBC::new(vec![0; 0x8000]);
+
+        no_mbc.write_ram(0xBFFF, 0x42);
+
+        assert_eq!(no_mbc.read_ram(0xBFFF), 0x42);
+    }
+}
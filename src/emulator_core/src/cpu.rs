@@ -1,23 +1,65 @@
 mod alu_operations;
 mod bitwise_operations;
 mod cb_instructions;
+mod debugger;
+mod illegal_opcode;
 mod interrupts;
 mod jp_operations;
+mod memory_timing;
 mod shift_operations;
 mod stack_operations;
+mod state;
+mod stop;
+mod trace;
 use alu_operations::*;
 use jp_operations::*;
 use stack_operations::*;
 
+pub use debugger::Access;
+pub use debugger::Breakpoint;
+pub use debugger::BreakpointId;
+pub use debugger::Comparison;
+pub use debugger::Condition;
+pub use debugger::Debugger;
+pub use debugger::Register;
+pub use debugger::RunMode;
+pub use debugger::Watchpoint;
+pub use debugger::WatchpointId;
+pub use illegal_opcode::BreakReason;
+pub use illegal_opcode::IllegalOpcodePolicy;
+pub use illegal_opcode::StepError;
+pub use memory_timing::MemoryTiming;
+pub use state::CpuState;
+pub use state::Flags;
+pub use trace::TraceOptions;
+
 use crate::registers::*;
 use crate::MMU;
 
 pub struct CPU {
     halted: bool,
+    // HALT with IME=0 and an interrupt pending doesn't halt, but the next
+    // opcode fetch fails to increment PC.
+    halt_bug: bool,
     pub mmu: MMU,
     registers: Registers,
     ime: bool,
+    // EI only enables interrupts after the instruction which follows it.
+    ime_scheduled: bool,
     stopped: bool,
+    // Cycles of the current instruction the rest of the system has already
+    // been run for.
+    ticks: u8,
+    // Interrupts pending before the last cycle of the current instruction.
+    // Only these can interrupt once it's done, one requested during the
+    // last cycle waits for the next instruction.
+    pending_interrupts: u8,
+    illegal_opcode: Option<u8>,
+    illegal_opcode_policy: IllegalOpcodePolicy,
+    locked_up: bool,
+    memory_timing: MemoryTiming,
+    trace: Option<Box<trace::Trace>>,
+    debugger: Option<Box<Debugger>>,
     clock: u32,
 }
 
@@ -34,84 +76,176 @@ impl CPU {
     pub fn new(mmu: MMU) -> Self {
         CPU {
             halted: false,
+            halt_bug: false,
             registers: Registers::new(),
             ime: false,
+            ime_scheduled: false,
             stopped: false,
+            ticks: 0,
+            pending_interrupts: 0,
+            illegal_opcode: None,
+            illegal_opcode_policy: IllegalOpcodePolicy::default(),
+            locked_up: false,
+            memory_timing: MemoryTiming::default(),
+            trace: None,
+            debugger: None,
             mmu,
             clock: 0,
         }
     }
 
-    pub fn step(&mut self) -> u8 {
+    /// A Super Game Boy, starting with the registers its boot ROM leaves
+    /// behind.
+    pub fn new_sgb(mut mmu: MMU) -> Self {
+        mmu.set_sgb_enabled(true);
+
+        CPU {
+            registers: Registers::sgb(),
+            ..CPU::new(mmu)
+        }
+    }
+
+    /// Run a single instruction, or a single cycle while halted, stopped or
+    /// locked up. Returns the number of M-cycles taken, including
+    /// dispatching any interrupt.
+    pub fn step(&mut self) -> Result<u8, StepError> {
         if self.stopped {
-            panic!("CPU is stopped");
+            return Ok(self.stopped_step());
+        }
+
+        if self.locked_up {
+            return Ok(self.locked_up_step());
         }
 
-        // self.debug_output();
-        let cycles = if !self.halted {
+        if self.debugger.is_some() && !self.halted {
+            self.check_breakpoints()?;
+        }
+
+        let enable_ime = self.ime_scheduled;
+        self.ticks = 0;
+        let opcode = if !self.halted {
+            if self.trace.is_some() {
+                self.trace_instruction();
+            }
             let opcode = self.fetch_u8();
-            self.handle_op(opcode)
+            if std::mem::take(&mut self.halt_bug) {
+                self.registers
+                    .update_sixteen(SixteenBitRegister::PC, |pc| pc.wrapping_sub(1));
+            }
+            Some(opcode)
         } else {
-            1
+            None
+        };
+        let cycles = match opcode {
+            Some(opcode) => self.handle_op(opcode),
+            None => 1,
         };
 
-        self.mmu.step(cycles);
+        debug_assert!(self.ticks <= cycles, "More accesses than cycles");
+        while self.ticks < cycles {
+            self.run_cycle();
+        }
+
+        if let Some(opcode) = self.illegal_opcode.take() {
+            self.clock += cycles as u32;
+            self.handle_illegal_opcode(opcode)?;
+            return Ok(cycles);
+        }
+
+        if enable_ime && self.ime_scheduled {
+            self.ime = true;
+            self.ime_scheduled = false;
+        }
 
         let interrupt_cycles = self.interrupt_step();
 
         self.clock += cycles as u32 + interrupt_cycles as u32;
 
-        cycles + interrupt_cycles
-    }
+        if self.debugger.is_some() {
+            self.check_watchpoints(opcode)?;
+        }
 
-    pub fn debug_output(&self) {
-        println!("A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
-                 self.registers.read_eight(EightBitRegister::A),
-                 self.registers.read_eight(EightBitRegister::F),
-                 self.registers.read_eight(EightBitRegister::B),
-                 self.registers.read_eight(EightBitRegister::C),
-                 self.registers.read_eight(EightBitRegister::D),
-                 self.registers.read_eight(EightBitRegister::E),
-                 self.registers.read_eight(EightBitRegister::H),
-                 self.registers.read_eight(EightBitRegister::L),
-                 self.registers.read_sixteen(SixteenBitRegister::SP),
-                 self.registers.read_sixteen(SixteenBitRegister::PC),
-                 self.mmu.read_u8(self.registers.read_sixteen(SixteenBitRegister::PC)),
-                 self.mmu.read_u8(self.registers.read_sixteen(SixteenBitRegister::PC) + 1),
-                 self.mmu.read_u8(self.registers.read_sixteen(SixteenBitRegister::PC) + 2),
-                 self.mmu.read_u8(self.registers.read_sixteen(SixteenBitRegister::PC) + 3)
-        );
+        Ok(cycles + interrupt_cycles)
     }
 
+    #[deprecated(note = "use CPU::state, which doesn't consume the CPU")]
     pub fn registers(self) -> RegisterDump {
+        let state = self.state();
         RegisterDump {
-            b: self.registers.read_eight(EightBitRegister::B),
-            c: self.registers.read_eight(EightBitRegister::C),
-            d: self.registers.read_eight(EightBitRegister::D),
-            e: self.registers.read_eight(EightBitRegister::E),
-            h: self.registers.read_eight(EightBitRegister::H),
-            l: self.registers.read_eight(EightBitRegister::L),
+            b: state.b(),
+            c: state.c(),
+            d: state.d(),
+            e: state.e(),
+            h: state.h(),
+            l: state.l(),
         }
     }
 
+    /// Fetch the next byte of the instruction, taking one cycle. Not
+    /// counted as a read by watchpoints.
     fn fetch_u8(&mut self) -> u8 {
         let value = self
             .mmu
             .read_u8(self.registers.read_sixteen(SixteenBitRegister::PC));
+        self.tick();
         self.registers
             .update_sixteen(SixteenBitRegister::PC, |pc| pc.wrapping_add(1));
         value
     }
 
     fn fetch_u16(&mut self) -> u16 {
-        let value = self
-            .mmu
-            .read_u16(self.registers.read_sixteen(SixteenBitRegister::PC));
-        self.registers
-            .update_sixteen(SixteenBitRegister::PC, |pc| pc.wrapping_add(2));
+        let low = self.fetch_u8() as u16;
+        let high = self.fetch_u8() as u16;
+
+        (high << 8) | low
+    }
+
+    /// Read from memory, taking one cycle. The rest of the system runs for
+    /// that cycle straight after the access, so that reads see the timer
+    /// and PPU as they are at that point of the instruction.
+    fn read_u8(&mut self, addr: u16) -> u8 {
+        let value = self.mmu.read_u8(addr);
+        self.watch(addr, Access::Read, value);
+        self.tick();
         value
     }
 
+    /// Write to memory, taking one cycle.
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        self.mmu.write_u8(addr, value);
+        self.watch(addr, Access::Write, value);
+        self.tick();
+    }
+
+    fn read_u16(&mut self, addr: u16) -> u16 {
+        let low = self.read_u8(addr) as u16;
+        let high = self.read_u8(addr.wrapping_add(1)) as u16;
+
+        (high << 8) | low
+    }
+
+    fn write_u16(&mut self, addr: u16, value: u16) {
+        let [low, high] = value.to_le_bytes();
+        self.write_u8(addr, low);
+        self.write_u8(addr.wrapping_add(1), high);
+    }
+
+    /// Run the rest of the system for one cycle of the current instruction,
+    /// unless it's only run once the instruction is done. Instructions only
+    /// need to tick for internal cycles which come before a memory access,
+    /// any left over are run once the instruction is done.
+    fn tick(&mut self) {
+        if self.memory_timing == MemoryTiming::PerAccess {
+            self.run_cycle();
+        }
+    }
+
+    fn run_cycle(&mut self) {
+        self.pending_interrupts = self.mmu.interrupts.interrupt_mask();
+        self.mmu.step(1);
+        self.ticks += 1;
+    }
+
     /// Handles provided opcode, updating the CPU state and returning the number of cycles taken.
     fn handle_op(&mut self, opcode: u8) -> u8 {
         match opcode {
@@ -127,7 +261,7 @@ impl CPU {
             0x02 => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::BC);
                 let value = self.registers.read_eight(EightBitRegister::A);
-                self.mmu.write_u8(addr, value);
+                self.write_u8(addr, value);
                 2
             }
             // INC BC
@@ -161,7 +295,7 @@ impl CPU {
             0x08 => {
                 let value = self.fetch_u16();
                 let sp = self.registers.read_sixteen(SixteenBitRegister::SP);
-                self.mmu.write_u16(value, sp);
+                self.write_u16(value, sp);
                 5
             }
             // ADD HL, BC
@@ -205,10 +339,7 @@ impl CPU {
                 1
             }
             // STOP n8
-            0x10 => {
-                // self.stopped = true;
-                1
-            }
+            0x10 => self.stop(),
             // LD DE, d16
             0x11 => {
                 let value = self.fetch_u16();
@@ -219,7 +350,7 @@ impl CPU {
             0x12 => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::DE);
                 let value = self.registers.read_eight(EightBitRegister::A);
-                self.mmu.write_u8(addr, value);
+                self.write_u8(addr, value);
                 2
             }
             // INC DE
@@ -315,7 +446,7 @@ impl CPU {
             0x22 => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
                 let value = self.registers.read_eight(EightBitRegister::A);
-                self.mmu.write_u8(addr, value);
+                self.write_u8(addr, value);
                 self.registers
                     .update_sixteen(SixteenBitRegister::HL, |hl| hl.wrapping_add(1));
                 2
@@ -363,7 +494,7 @@ impl CPU {
             // LD A, (HL+)
             0x2A => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
                 self.registers.write_eight(EightBitRegister::A, value);
                 self.registers
                     .update_sixteen(SixteenBitRegister::HL, |hl| hl.wrapping_add(1));
@@ -414,7 +545,7 @@ impl CPU {
             0x32 => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
                 let value = self.registers.read_eight(EightBitRegister::A);
-                self.mmu.write_u8(addr, value);
+                self.write_u8(addr, value);
                 self.registers
                     .update_sixteen(SixteenBitRegister::HL, |hl| hl.wrapping_sub(1));
                 2
@@ -428,24 +559,24 @@ impl CPU {
             // INC (HL)
             0x34 => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
                 let result = alu_inc_value(&mut self.registers, value);
-                self.mmu.write_u8(addr, result);
+                self.write_u8(addr, result);
                 3
             }
             // DEC (HL)
             0x35 => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
                 let result = alu_dec_value(&mut self.registers, value);
-                self.mmu.write_u8(addr, result);
+                self.write_u8(addr, result);
                 3
             }
             // LD (HL), d8
             0x36 => {
                 let value = self.fetch_u8();
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                self.mmu.write_u8(addr, value);
+                self.write_u8(addr, value);
                 3
             }
             // SCF
@@ -469,7 +600,7 @@ impl CPU {
             // LD A, (HL-)
             0x3A => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
                 self.registers.write_eight(EightBitRegister::A, value);
                 self.registers
                     .update_sixteen(SixteenBitRegister::HL, |hl| hl.wrapping_sub(1));
@@ -560,7 +691,7 @@ impl CPU {
             // LD B, (HL)
             0x46 => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
 
                 self.registers.write_eight(EightBitRegister::B, value);
 
@@ -640,7 +771,7 @@ impl CPU {
             // LD C, (HL)
             0x4E => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
 
                 self.registers.write_eight(EightBitRegister::C, value);
 
@@ -720,7 +851,7 @@ impl CPU {
             // LD D, (HL)
             0x56 => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
 
                 self.registers.write_eight(EightBitRegister::D, value);
 
@@ -800,7 +931,7 @@ impl CPU {
             // LD E, (HL)
             0x5E => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
 
                 self.registers.write_eight(EightBitRegister::E, value);
 
@@ -880,7 +1011,7 @@ impl CPU {
             // LD H, (HL)
             0x66 => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
 
                 self.registers.write_eight(EightBitRegister::H, value);
 
@@ -960,7 +1091,7 @@ impl CPU {
             // LD L, (HL)
             0x6E => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
 
                 self.registers.write_eight(EightBitRegister::L, value);
 
@@ -982,7 +1113,7 @@ impl CPU {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
                 let value = self.registers.read_eight(EightBitRegister::B);
 
-                self.mmu.write_u8(addr, value);
+                self.write_u8(addr, value);
 
                 2
             }
@@ -992,7 +1123,7 @@ impl CPU {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
                 let value = self.registers.read_eight(EightBitRegister::C);
 
-                self.mmu.write_u8(addr, value);
+                self.write_u8(addr, value);
 
                 2
             }
@@ -1002,7 +1133,7 @@ impl CPU {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
                 let value = self.registers.read_eight(EightBitRegister::D);
 
-                self.mmu.write_u8(addr, value);
+                self.write_u8(addr, value);
 
                 2
             }
@@ -1012,7 +1143,7 @@ impl CPU {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
                 let value = self.registers.read_eight(EightBitRegister::E);
 
-                self.mmu.write_u8(addr, value);
+                self.write_u8(addr, value);
 
                 2
             }
@@ -1022,7 +1153,7 @@ impl CPU {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
                 let value = self.registers.read_eight(EightBitRegister::H);
 
-                self.mmu.write_u8(addr, value);
+                self.write_u8(addr, value);
 
                 2
             }
@@ -1032,14 +1163,18 @@ impl CPU {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
                 let value = self.registers.read_eight(EightBitRegister::L);
 
-                self.mmu.write_u8(addr, value);
+                self.write_u8(addr, value);
 
                 2
             }
 
             // HALT
             0x76 => {
-                self.halted = true;
+                if !self.ime && self.mmu.interrupts.has_interrupt() {
+                    self.halt_bug = true;
+                } else {
+                    self.halted = true;
+                }
 
                 1
             }
@@ -1049,7 +1184,7 @@ impl CPU {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
                 let value = self.registers.read_eight(EightBitRegister::A);
 
-                self.mmu.write_u8(addr, value);
+                self.write_u8(addr, value);
 
                 2
             }
@@ -1117,7 +1252,7 @@ impl CPU {
             // LD A, (HL)
             0x7E => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
 
                 self.registers.write_eight(EightBitRegister::A, value);
 
@@ -1177,7 +1312,7 @@ impl CPU {
             // ADD A, (HL)
             0x86 => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
                 alu_add_value(&mut self.registers, value);
                 2
             }
@@ -1227,7 +1362,7 @@ impl CPU {
             // ADC A, (HL)
             0x8E => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
                 alu_adc_value(&mut self.registers, value);
                 2
             }
@@ -1277,7 +1412,7 @@ impl CPU {
             // SUB (HL)
             0x96 => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
                 alu_sub_value(&mut self.registers, value);
                 2
             }
@@ -1327,7 +1462,7 @@ impl CPU {
             // SBC A, (HL)
             0x9E => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
                 alu_sbc_value(&mut self.registers, value);
                 2
             }
@@ -1377,7 +1512,7 @@ impl CPU {
             // AND (HL)
             0xA6 => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
                 alu_and_value(&mut self.registers, value);
                 2
             }
@@ -1427,7 +1562,7 @@ impl CPU {
             // XOR (HL)
             0xAE => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
                 alu_xor_value(&mut self.registers, value);
                 2
             }
@@ -1477,7 +1612,7 @@ impl CPU {
             // OR (HL)
             0xB6 => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
                 alu_or_value(&mut self.registers, value);
                 2
             }
@@ -1527,7 +1662,7 @@ impl CPU {
             // CP (HL)
             0xBE => {
                 let addr = self.registers.read_sixteen(SixteenBitRegister::HL);
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
                 alu_cp_value(&mut self.registers, value);
                 2
             }
@@ -1677,10 +1812,6 @@ impl CPU {
                 }
             }
 
-            0xD3 => {
-                panic!("Unknown opcode: {:#04x}", opcode);
-            }
-
             // CALL NC, a16
             0xD4 => {
                 let value = self.fetch_u16();
@@ -1773,7 +1904,7 @@ impl CPU {
 
                 let value = self.registers.read_eight(EightBitRegister::A);
 
-                self.mmu.write_u8(addr, value);
+                self.write_u8(addr, value);
 
                 3
             }
@@ -1793,7 +1924,7 @@ impl CPU {
                 let addr = 0xFF00 + offset;
                 let value = self.registers.read_eight(EightBitRegister::A);
 
-                self.mmu.write_u8(addr, value);
+                self.write_u8(addr, value);
 
                 2
             }
@@ -1845,7 +1976,7 @@ impl CPU {
 
                 let value = self.registers.read_eight(EightBitRegister::A);
 
-                self.mmu.write_u8(addr, value);
+                self.write_u8(addr, value);
 
                 4
             }
@@ -1871,7 +2002,7 @@ impl CPU {
                 let offset = self.fetch_u8() as u16;
 
                 let addr = offset + 0xFF00;
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
 
                 self.registers.write_eight(EightBitRegister::A, value);
 
@@ -1891,7 +2022,7 @@ impl CPU {
             0xF2 => {
                 let offset = self.registers.read_eight(EightBitRegister::C) as u16;
                 let addr = 0xFF00 + offset;
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
 
                 self.registers.write_eight(EightBitRegister::A, value);
 
@@ -1901,6 +2032,7 @@ impl CPU {
             // DI
             0xF3 => {
                 self.ime = false;
+                self.ime_scheduled = false;
 
                 1
             }
@@ -1949,7 +2081,7 @@ impl CPU {
             // LD A, [n16]
             0xFA => {
                 let addr = self.fetch_u16();
-                let value = self.mmu.read_u8(addr);
+                let value = self.read_u8(addr);
 
                 self.registers.write_eight(EightBitRegister::A, value);
 
@@ -1958,7 +2090,7 @@ impl CPU {
 
             // EI
             0xFB => {
-                self.ime = true;
+                self.ime_scheduled = true;
 
                 1
             }
@@ -1979,10 +2111,59 @@ impl CPU {
                 4
             }
 
-            //
-            _ => {
-                panic!("Unknown opcode: {:#04x}", opcode);
+            // Illegal opcodes
+            0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD => {
+                self.illegal_opcode(opcode)
             }
         }
     }
 }
+
+/// CPU fixtures shared by the tests of every CPU feature.
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Arc;
+
+    use crate::cartridge::{Cartridge, NoMBC};
+    use crate::mmu::ppu::PPU;
+    use crate::mmu::TestRenderer;
+    use crate::Joypad;
+
+    use super::*;
+
+    /// A CPU running an empty cartridge.
+    pub(crate) fn mock_cpu() -> CPU {
+        cpu_running(&[])
+    }
+
+    /// A CPU about to run the given code from the cartridge entry point at
+    /// 0x0100.
+    pub(crate) fn cpu_running(code: &[u8]) -> CPU {
+        cpu_with_joypad(code).0
+    }
+
+    /// Like [cpu_running], also returning the joypad wired into the CPU.
+    pub(crate) fn cpu_with_joypad(code: &[u8]) -> (CPU, Arc<Joypad>) {
+        let mut rom = vec![0; 0x8000];
+        rom[0x100..0x100 + code.len()].copy_from_slice(code);
+        let joypad = Arc::new(Joypad::new());
+
+        (cpu_for(Box::new(NoMBC::new(rom)), joypad.clone()), joypad)
+    }
+
+    /// A CPU running the given cartridge, usually a mock.
+    pub(crate) fn cpu_with_cartridge(cartridge: Box<dyn Cartridge>) -> CPU {
+        cpu_for(cartridge, Arc::new(Joypad::new()))
+    }
+
+    fn cpu_for(cartridge: Box<dyn Cartridge>, joypad: Arc<Joypad>) -> CPU {
+        let ppu = PPU::new(Arc::new(TestRenderer));
+        let mmu = MMU::new(ppu, cartridge, joypad);
+
+        CPU::new(mmu)
+    }
+
+    pub(crate) fn pc(cpu: &CPU) -> u16 {
+        cpu.registers.read_sixteen(SixteenBitRegister::PC)
+    }
+}
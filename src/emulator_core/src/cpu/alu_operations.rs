@@ -222,7 +222,7 @@ pub fn alu_cp(registers: &mut Registers, reg: EightBitRegister) {
 pub fn rlca(registers: &mut Registers) {
     let value = registers.read_eight(EightBitRegister::A);
     let carry = value & 0x80 != 0;
-    let result = (value << 1) | (value >> 7);
+    let result = value.rotate_left(1);
 
     registers.write_eight(EightBitRegister::A, result);
     registers.set_zero_flag(false);
@@ -235,7 +235,7 @@ pub fn rlca(registers: &mut Registers) {
 pub fn rrca(registers: &mut Registers) {
     let value = registers.read_eight(EightBitRegister::A);
     let carry = value & 0x01 != 0;
-    let result = (value >> 1) | (value << 7);
+    let result = value.rotate_right(1);
 
     registers.write_eight(EightBitRegister::A, result);
     registers.set_zero_flag(false);
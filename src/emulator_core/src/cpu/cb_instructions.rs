@@ -43,9 +43,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // RLC (HL)
         0x06 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = rlc_val(&mut cpu.registers, value);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -94,9 +94,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // RRC (HL)
         0x0E => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = rrc_val(&mut cpu.registers, value);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -145,9 +145,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // RL (HL)
         0x16 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = rl_val(&mut cpu.registers, value);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -196,9 +196,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // RR (HL)
         0x1E => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = rr_val(&mut cpu.registers, value);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -247,9 +247,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // SLA (HL)
         0x26 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = sla_val(&mut cpu.registers, value);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -298,9 +298,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // SRA (HL)
         0x2E => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = sra_val(&mut cpu.registers, value);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -349,9 +349,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // SWAP (HL)
         0x36 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = swap_value(&mut cpu.registers, value);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -405,9 +405,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // SRL (HL)
         0x3E => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = srl_val(&mut cpu.registers, value);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -456,7 +456,7 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // BIT 0, (HL)
         0x46 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             bit_val(&mut cpu.registers, value, 0);
 
             3
@@ -506,7 +506,7 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // BIT 1, (HL)
         0x4E => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             bit_val(&mut cpu.registers, value, 1);
 
             3
@@ -556,7 +556,7 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // BIT 2, (HL)
         0x56 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             bit_val(&mut cpu.registers, value, 2);
 
             3
@@ -606,7 +606,7 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // BIT 3, (HL)
         0x5e => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             bit_val(&mut cpu.registers, value, 3);
 
             3
@@ -656,7 +656,7 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // BIT 4, (HL)
         0x66 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             bit_val(&mut cpu.registers, value, 4);
 
             3
@@ -706,7 +706,7 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // BIT 5, (HL)
         0x6e => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             bit_val(&mut cpu.registers, value, 5);
 
             3
@@ -756,7 +756,7 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // BIT 6, (HL)
         0x76 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             bit_val(&mut cpu.registers, value, 6);
 
             3
@@ -806,7 +806,7 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // BIT 7, (HL)
         0x7e => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             bit_val(&mut cpu.registers, value, 7);
 
             3
@@ -857,9 +857,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // RES 0, (HL)
         0x86 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = res_val(value, 0);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -908,9 +908,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // RES 1, (HL)
         0x8E => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = res_val(value, 1);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -959,9 +959,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // RES 2, (HL)
         0x96 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = res_val(value, 2);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -1010,9 +1010,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // RES 3, (HL)
         0x9e => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = res_val(value, 3);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -1061,9 +1061,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // RES 4, (HL)
         0xa6 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = res_val(value, 4);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -1113,9 +1113,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // RES 5, (HL)
         0xae => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = res_val(value, 5);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -1164,9 +1164,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // RES 6, (HL)
         0xb6 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = res_val(value, 6);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -1215,9 +1215,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // RES 7, (HL)
         0xbe => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = res_val(value, 7);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -1266,9 +1266,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // SET 0, (HL)
         0xc6 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = set_val(value, 0);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -1317,9 +1317,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // SET 1, (HL)
         0xce => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = set_val(value, 1);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -1368,9 +1368,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // SET 2, (HL)
         0xd6 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = set_val(value, 2);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -1419,9 +1419,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // SET 3, (HL)
         0xde => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = set_val(value, 3);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -1470,9 +1470,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // SET 4, (HL)
         0xe6 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = set_val(value, 4);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -1522,9 +1522,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // SET 5, (HL)
         0xee => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = set_val(value, 5);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -1573,9 +1573,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // SET 6, (HL)
         0xf6 => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = set_val(value, 6);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -1624,9 +1624,9 @@ pub fn handle_cb_instructions(cpu: &mut CPU, opcode: u8) -> u8 {
         // SET 7, (HL)
         0xfe => {
             let address = cpu.registers.read_sixteen(SixteenBitRegister::HL);
-            let value = cpu.mmu.read_u8(address);
+            let value = cpu.read_u8(address);
             let result = set_val(value, 7);
-            cpu.mmu.write_u8(address, result);
+            cpu.write_u8(address, result);
 
             4
         }
@@ -0,0 +1,620 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use super::*;
+use crate::mmu::Interrupt;
+
+/// A register, or pair of registers, read by a [Condition].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+    PC,
+}
+
+impl Register {
+    /// The value of the register in `state`.
+    pub fn read(&self, state: &CpuState) -> u16 {
+        match self {
+            Register::A => state.a as u16,
+            Register::F => state.f.bits() as u16,
+            Register::B => state.b() as u16,
+            Register::C => state.c() as u16,
+            Register::D => state.d() as u16,
+            Register::E => state.e() as u16,
+            Register::H => state.h() as u16,
+            Register::L => state.l() as u16,
+            Register::AF => state.af(),
+            Register::BC => state.bc,
+            Register::DE => state.de,
+            Register::HL => state.hl,
+            Register::SP => state.sp,
+            Register::PC => state.pc,
+        }
+    }
+}
+
+impl FromStr for Register {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "a" => Ok(Register::A),
+            "f" => Ok(Register::F),
+            "b" => Ok(Register::B),
+            "c" => Ok(Register::C),
+            "d" => Ok(Register::D),
+            "e" => Ok(Register::E),
+            "h" => Ok(Register::H),
+            "l" => Ok(Register::L),
+            "af" => Ok(Register::AF),
+            "bc" => Ok(Register::BC),
+            "de" => Ok(Register::DE),
+            "hl" => Ok(Register::HL),
+            "sp" => Ok(Register::SP),
+            "pc" => Ok(Register::PC),
+            _ => Err(format!("Unknown register: {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// Compares a register with a value, so a [Breakpoint] only stops the CPU
+/// when it's met.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    pub fn matches(&self, state: &CpuState) -> bool {
+        let register = self.register.read(state);
+
+        match self.comparison {
+            Comparison::Equal => register == self.value,
+            Comparison::NotEqual => register != self.value,
+            Comparison::Less => register < self.value,
+            Comparison::LessOrEqual => register <= self.value,
+            Comparison::Greater => register > self.value,
+            Comparison::GreaterOrEqual => register >= self.value,
+        }
+    }
+}
+
+/// Stops the CPU before it runs the instruction at `address`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub address: u16,
+    /// Only stop when this ROM bank is mapped at `address`, see
+    /// [MMU::rom_bank]. Never matches outside ROM.
+    pub bank: Option<usize>,
+    /// Only stop when the condition is met.
+    pub condition: Option<Condition>,
+}
+
+impl Breakpoint {
+    /// A breakpoint at `address` in any bank, without a condition.
+    pub fn new(address: u16) -> Breakpoint {
+        Breakpoint {
+            address,
+            bank: None,
+            condition: None,
+        }
+    }
+}
+
+/// How a watched address is accessed by the CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+/// Stops the CPU when it accesses an address in `range`. Reads and writes
+/// stop the CPU once the instruction making them has finished, executing
+/// stops it before the instruction runs.
+///
+/// Only the CPU's own accesses are watched, not those made by OAM DMA, and
+/// fetching an instruction doesn't count as reading it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub range: RangeInclusive<u16>,
+    pub access: Access,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BreakpointId(u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WatchpointId(u32);
+
+/// How far the CPU runs before stopping by itself, see [CPU::set_run_mode].
+/// Breakpoints and watchpoints can stop it sooner.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RunMode {
+    /// Only stop at breakpoints and watchpoints.
+    #[default]
+    Continue,
+    /// Run one instruction, stopping in the handler if an interrupt is
+    /// dispatched after it.
+    StepInto,
+    /// Run one instruction, or the whole of a subroutine it calls.
+    StepOver,
+    /// Run until the current subroutine or interrupt handler returns.
+    StepOut,
+    /// Run until the PPU has finished the next frame.
+    NextFrame,
+    /// Run until the next interrupt is dispatched, stopping at the start of
+    /// its handler.
+    NextInterrupt,
+}
+
+/// Where the current [RunMode] stops the CPU.
+#[derive(Debug, Default, Clone, Copy)]
+enum Goal {
+    #[default]
+    None,
+    /// After the next instruction or interrupt dispatch.
+    Step,
+    /// Before the instruction at `address`, once the stack is back to `sp`.
+    ReturnTo {
+        address: u16,
+        sp: u16,
+    },
+    /// After a return which pops the stack above `sp`.
+    Return {
+        sp: u16,
+    },
+    /// Once the PPU has finished the frame after this one.
+    Frame(u64),
+    Interrupt,
+}
+
+/// Breakpoints, watchpoints and stepping, reached through
+/// [CPU::debugger_mut]. Whenever the debugger stops the CPU, [CPU::step]
+/// returns [StepError::Break].
+#[derive(Debug, Default)]
+pub struct Debugger {
+    breakpoints: Vec<(BreakpointId, Breakpoint)>,
+    watchpoints: Vec<(WatchpointId, Watchpoint)>,
+    next_id: u32,
+    goal: Goal,
+    /// Where the CPU last stopped. Breakpoints there are skipped by the
+    /// next step, so carrying on doesn't stop in the same place again.
+    stopped_at: Option<u16>,
+    /// First read or write watchpoint hit by the current instruction.
+    hit: Option<BreakReason>,
+    /// Interrupt dispatched by the current step.
+    interrupt: Option<Interrupt>,
+}
+
+/// Opcodes which return from a subroutine (RET, RETI and RET cc).
+const RETURNS: [u8; 6] = [0xC0, 0xC8, 0xC9, 0xD0, 0xD8, 0xD9];
+
+impl Debugger {
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> BreakpointId {
+        self.next_id += 1;
+        let id = BreakpointId(self.next_id);
+        self.breakpoints.push((id, breakpoint));
+
+        id
+    }
+
+    /// Returns false if there was no such breakpoint.
+    pub fn remove_breakpoint(&mut self, id: BreakpointId) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints.retain(|(other, _)| *other != id);
+
+        self.breakpoints.len() != count
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = (BreakpointId, &Breakpoint)> {
+        self.breakpoints
+            .iter()
+            .map(|(id, breakpoint)| (*id, breakpoint))
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> WatchpointId {
+        self.next_id += 1;
+        let id = WatchpointId(self.next_id);
+        self.watchpoints.push((id, watchpoint));
+
+        id
+    }
+
+    /// Returns false if there was no such watchpoint.
+    pub fn remove_watchpoint(&mut self, id: WatchpointId) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|(other, _)| *other != id);
+
+        self.watchpoints.len() != count
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = (WatchpointId, &Watchpoint)> {
+        self.watchpoints
+            .iter()
+            .map(|(id, watchpoint)| (*id, watchpoint))
+    }
+
+    /// Remove every breakpoint and watchpoint.
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+    }
+
+    fn watchpoint(&self, address: u16, access: Access) -> Option<WatchpointId> {
+        self.watchpoints
+            .iter()
+            .find(|(_, watchpoint)| {
+                watchpoint.access == access && watchpoint.range.contains(&address)
+            })
+            .map(|(id, _)| *id)
+    }
+}
+
+impl CPU {
+    pub fn debugger(&self) -> Option<&Debugger> {
+        self.debugger.as_deref()
+    }
+
+    /// The debugger, attached the first time it's asked for. Until then
+    /// stepping doesn't check for breakpoints at all.
+    pub fn debugger_mut(&mut self) -> &mut Debugger {
+        self.debugger.get_or_insert_with(Box::default)
+    }
+
+    /// Remove the debugger, with all its breakpoints and watchpoints.
+    pub fn detach_debugger(&mut self) {
+        self.debugger = None;
+    }
+
+    /// Choose how far the CPU runs from here before stopping by itself.
+    /// Stopping for any reason goes back to [RunMode::Continue].
+    pub fn set_run_mode(&mut self, mode: RunMode) {
+        let pc = self.registers.read_sixteen(SixteenBitRegister::PC);
+        let sp = self.registers.read_sixteen(SixteenBitRegister::SP);
+
+        let goal = match mode {
+            RunMode::Continue => Goal::None,
+            RunMode::StepInto => Goal::Step,
+            RunMode::StepOver => match self.mmu.read_u8(pc) {
+                // CALL and CALL cc
+                0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC => Goal::ReturnTo {
+                    address: pc.wrapping_add(3),
+                    sp,
+                },
+                // RST
+                opcode if opcode & 0xC7 == 0xC7 => Goal::ReturnTo {
+                    address: pc.wrapping_add(1),
+                    sp,
+                },
+                _ => Goal::Step,
+            },
+            RunMode::StepOut => Goal::Return { sp },
+            RunMode::NextFrame => Goal::Frame(self.mmu.ppu.frame_number()),
+            RunMode::NextInterrupt => Goal::Interrupt,
+        };
+
+        if matches!(goal, Goal::None) && self.debugger.is_none() {
+            return;
+        }
+        self.debugger_mut().goal = goal;
+    }
+
+    /// Read memory as the CPU currently sees it, without taking any time.
+    pub fn read_memory(&self, addr: u16) -> u8 {
+        self.mmu.read_u8(addr)
+    }
+
+    /// Write to memory as the CPU would, without taking any time. Writes to
+    /// ROM reach the cartridge's bank controller as usual.
+    pub fn write_memory(&mut self, addr: u16, value: u8) {
+        self.mmu.write_u8(addr, value);
+    }
+
+    /// Note a read or write by the current instruction for watchpoints.
+    #[inline]
+    pub(super) fn watch(&mut self, addr: u16, access: Access, value: u8) {
+        let Some(debugger) = self.debugger.as_mut() else {
+            return;
+        };
+
+        if debugger.hit.is_none() {
+            if let Some(id) = debugger.watchpoint(addr, access) {
+                debugger.hit = Some(BreakReason::Watchpoint {
+                    id,
+                    address: addr,
+                    access,
+                    value,
+                });
+            }
+        }
+    }
+
+    /// Note an interrupt dispatched by the current step.
+    pub(super) fn watch_interrupt(&mut self, interrupt: Interrupt) {
+        if let Some(debugger) = self.debugger.as_mut() {
+            debugger.interrupt = Some(interrupt);
+        }
+    }
+
+    /// Check for anything which stops the CPU before it runs the
+    /// instruction at PC.
+    pub(super) fn check_breakpoints(&mut self) -> Result<(), StepError> {
+        let pc = self.registers.read_sixteen(SixteenBitRegister::PC);
+        let sp = self.registers.read_sixteen(SixteenBitRegister::SP);
+        let Some(debugger) = self.debugger.as_deref() else {
+            return Ok(());
+        };
+
+        if debugger.stopped_at == Some(pc) {
+            self.debugger_mut().stopped_at = None;
+            return Ok(());
+        }
+
+        let returned = match debugger.goal {
+            Goal::ReturnTo { address, sp: goal } => pc == address && sp >= goal,
+            _ => false,
+        };
+        let breakpoint = || {
+            debugger.breakpoints.iter().find(|(_, breakpoint)| {
+                breakpoint.address == pc
+                    && breakpoint
+                        .bank
+                        .is_none_or(|bank| self.mmu.rom_bank(pc) == Some(bank))
+                    && breakpoint
+                        .condition
+                        .is_none_or(|condition| condition.matches(&self.state()))
+            })
+        };
+        let executed = || {
+            debugger
+                .watchpoint(pc, Access::Execute)
+                .map(|id| BreakReason::Watchpoint {
+                    id,
+                    address: pc,
+                    access: Access::Execute,
+                    value: self.mmu.read_u8(pc),
+                })
+        };
+
+        let reason = if returned {
+            Some(BreakReason::Step)
+        } else if let Some((id, _)) = breakpoint() {
+            Some(BreakReason::Breakpoint(*id))
+        } else {
+            executed()
+        };
+
+        match reason {
+            Some(reason) => Err(self.stop_at(pc, reason)),
+            None => Ok(()),
+        }
+    }
+
+    /// Check for anything which stops the CPU once a step has finished,
+    /// given the opcode it ran if it ran one.
+    pub(super) fn check_watchpoints(&mut self, opcode: Option<u8>) -> Result<(), StepError> {
+        let pc = self.registers.read_sixteen(SixteenBitRegister::PC);
+        let sp = self.registers.read_sixteen(SixteenBitRegister::SP);
+        let frame = self.mmu.ppu.frame_number();
+        let Some(debugger) = self.debugger.as_mut() else {
+            return Ok(());
+        };
+
+        let interrupt = debugger.interrupt.take();
+        let reason = debugger.hit.take().or(match debugger.goal {
+            Goal::Step if opcode.is_some() || interrupt.is_some() => Some(BreakReason::Step),
+            Goal::Return { sp: goal }
+                if opcode.is_some_and(|opcode| RETURNS.contains(&opcode)) && sp > goal =>
+            {
+                Some(BreakReason::Step)
+            }
+            Goal::Frame(number) if frame != number => Some(BreakReason::Frame),
+            Goal::Interrupt => interrupt.map(BreakReason::Interrupt),
+            _ => None,
+        });
+
+        match reason {
+            Some(reason) => Err(self.stop_at(pc, reason)),
+            None => Ok(()),
+        }
+    }
+
+    /// Stop at `address`, going back to [RunMode::Continue].
+    fn stop_at(&mut self, address: u16, reason: BreakReason) -> StepError {
+        let debugger = self.debugger_mut();
+        debugger.goal = Goal::None;
+        debugger.stopped_at = Some(address);
+
+        StepError::Break { address, reason }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::tests::{cpu_running, pc};
+
+    use super::*;
+
+    /// Step until the CPU stops, returning where and why.
+    fn run_to_break(cpu: &mut CPU) -> (u16, BreakReason) {
+        for _ in 0..100_000 {
+            match cpu.step() {
+                Ok(_) => {}
+                Err(StepError::Break { address, reason }) => return (address, reason),
+                Err(err) => panic!("Unexpected error: {err}"),
+            }
+        }
+
+        panic!("Should have stopped");
+    }
+
+    /// LD A, $01, then a loop of CALL $0110, JR -5 with the subroutine at
+    /// 0x0110 of INC A, RET.
+    fn cpu_with_subroutine() -> CPU {
+        let mut code = [0; 0x12];
+        code[..7].copy_from_slice(&[0x3E, 0x01, 0xCD, 0x10, 0x01, 0x18, 0xFB]);
+        code[0x10..].copy_from_slice(&[0x3C, 0xC9]);
+
+        cpu_running(&code)
+    }
+
+    #[test]
+    fn stops_at_breakpoints_once() {
+        let mut cpu = cpu_with_subroutine();
+        let id = cpu.debugger_mut().add_breakpoint(Breakpoint::new(0x0110));
+
+        assert_eq!(
+            run_to_break(&mut cpu),
+            (0x0110, BreakReason::Breakpoint(id))
+        );
+        assert_eq!(pc(&cpu), 0x0110);
+        // Carrying on runs the instruction at the breakpoint, then calls it
+        // again.
+        cpu.step().unwrap();
+        assert_eq!(
+            run_to_break(&mut cpu),
+            (0x0110, BreakReason::Breakpoint(id))
+        );
+
+        assert!(cpu.debugger_mut().remove_breakpoint(id));
+        assert!(!cpu.debugger_mut().remove_breakpoint(id));
+        for _ in 0..1000 {
+            cpu.step().unwrap();
+        }
+    }
+
+    #[test]
+    fn breakpoint_conditions_and_banks() {
+        let mut cpu = cpu_with_subroutine();
+        let condition = Condition {
+            register: "a".parse().unwrap(),
+            comparison: Comparison::GreaterOrEqual,
+            value: 0x03,
+        };
+        let id = cpu.debugger_mut().add_breakpoint(Breakpoint {
+            condition: Some(condition),
+            ..Breakpoint::new(0x0110)
+        });
+        cpu.debugger_mut().add_breakpoint(Breakpoint {
+            bank: Some(1),
+            ..Breakpoint::new(0x0100)
+        });
+
+        assert_eq!(
+            run_to_break(&mut cpu),
+            (0x0110, BreakReason::Breakpoint(id))
+        );
+        assert_eq!(cpu.state().a, 0x03);
+    }
+
+    #[test]
+    fn watches_reads_writes_and_execution() {
+        // LD A, [$C000], LD [$C001], A, JP $0100
+        let mut cpu = cpu_running(&[0xFA, 0x00, 0xC0, 0xEA, 0x01, 0xC0, 0xC3, 0x00, 0x01]);
+        cpu.write_memory(0xC000, 0x42);
+        let write = cpu.debugger_mut().add_watchpoint(Watchpoint {
+            range: 0xC001..=0xC0FF,
+            access: Access::Write,
+        });
+        let read = cpu.debugger_mut().add_watchpoint(Watchpoint {
+            range: 0xC000..=0xC000,
+            access: Access::Read,
+        });
+
+        let hit = |id, address, access, value| BreakReason::Watchpoint {
+            id,
+            address,
+            access,
+            value,
+        };
+        assert_eq!(
+            run_to_break(&mut cpu),
+            (0x0103, hit(read, 0xC000, Access::Read, 0x42))
+        );
+        assert_eq!(
+            run_to_break(&mut cpu),
+            (0x0106, hit(write, 0xC001, Access::Write, 0x42))
+        );
+
+        // The jump the CPU stopped before is skipped, so this stops back at
+        // the start.
+        let execute = cpu.debugger_mut().add_watchpoint(Watchpoint {
+            range: 0x0100..=0x0102,
+            access: Access::Execute,
+        });
+        assert_eq!(
+            run_to_break(&mut cpu),
+            (0x0100, hit(execute, 0x0100, Access::Execute, 0xFA))
+        );
+        assert_eq!(cpu.read_memory(0xC001), 0x42);
+    }
+
+    #[test]
+    fn steps_into_over_and_out() {
+        let mut cpu = cpu_with_subroutine();
+
+        cpu.set_run_mode(RunMode::StepInto);
+        assert_eq!(run_to_break(&mut cpu), (0x0102, BreakReason::Step));
+        cpu.set_run_mode(RunMode::StepInto);
+        assert_eq!(run_to_break(&mut cpu), (0x0110, BreakReason::Step));
+        cpu.set_run_mode(RunMode::StepOut);
+        assert_eq!(run_to_break(&mut cpu), (0x0105, BreakReason::Step));
+        assert_eq!(cpu.state().a, 0x02);
+
+        // Round the loop to the call, then over it.
+        cpu.set_run_mode(RunMode::StepOver);
+        assert_eq!(run_to_break(&mut cpu), (0x0102, BreakReason::Step));
+        cpu.set_run_mode(RunMode::StepOver);
+        assert_eq!(run_to_break(&mut cpu), (0x0105, BreakReason::Step));
+        assert_eq!(cpu.state().a, 0x03);
+    }
+
+    #[test]
+    fn runs_to_the_next_frame_and_interrupt() {
+        let mut cpu = cpu_with_subroutine();
+        cpu.write_memory(0xFF40, 0x80);
+
+        cpu.set_run_mode(RunMode::NextFrame);
+        assert!(matches!(run_to_break(&mut cpu), (_, BreakReason::Frame)));
+        cpu.set_run_mode(RunMode::NextFrame);
+        let start = cpu.state().clock;
+        run_to_break(&mut cpu);
+        let frame = cpu.state().clock - start;
+        assert!((154 * 114 - 8..154 * 114 + 8).contains(&frame), "{frame}");
+
+        let mut state = cpu.state();
+        state.ime = true;
+        cpu.set_state(state);
+        cpu.write_memory(0xFFFF, 0x01);
+        cpu.set_run_mode(RunMode::NextInterrupt);
+        assert_eq!(
+            run_to_break(&mut cpu),
+            (0x0040, BreakReason::Interrupt(Interrupt::VBlank))
+        );
+    }
+}
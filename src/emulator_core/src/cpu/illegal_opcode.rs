@@ -0,0 +1,236 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::*;
+use crate::mmu::Interrupt;
+
+/// What the CPU does when it fetches one of the 11 opcodes which don't
+/// exist (0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC and
+/// 0xFD).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IllegalOpcodePolicy {
+    /// Lock up as the hardware does. The CPU stops executing instructions
+    /// and servicing interrupts, while the rest of the system keeps running.
+    #[default]
+    LockUp,
+    /// Lock up, and return [StepError::IllegalOpcode] from [CPU::step].
+    Error,
+    /// Stop just before the illegal opcode, returning [StepError::Break]
+    /// from [CPU::step] so it can be looked at in the debugger.
+    Break,
+}
+
+impl FromStr for IllegalOpcodePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "lockup" | "lock-up" => Ok(IllegalOpcodePolicy::LockUp),
+            "error" => Ok(IllegalOpcodePolicy::Error),
+            "break" => Ok(IllegalOpcodePolicy::Break),
+            _ => Err(format!("Unknown illegal opcode policy: {s}")),
+        }
+    }
+}
+
+/// Why the CPU stopped before running an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakReason {
+    IllegalOpcode(u8),
+    Breakpoint(BreakpointId),
+    /// A watched `address` was accessed, reading or writing `value`, or
+    /// about to be executed with `value` as its opcode.
+    Watchpoint {
+        id: WatchpointId,
+        address: u16,
+        access: Access,
+        value: u8,
+    },
+    /// Finished stepping into, over or out.
+    Step,
+    /// The PPU finished a frame, see [RunMode::NextFrame].
+    Frame,
+    /// The interrupt was dispatched, see [RunMode::NextInterrupt].
+    Interrupt(Interrupt),
+    /// Paused from outside the CPU, by the emulator thread.
+    Paused,
+}
+
+impl fmt::Display for BreakReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakReason::IllegalOpcode(opcode) => {
+                write!(f, "before illegal opcode {opcode:#04x}")
+            }
+            BreakReason::Breakpoint(_) => write!(f, "at a breakpoint"),
+            BreakReason::Watchpoint {
+                address,
+                access: Access::Read,
+                value,
+                ..
+            } => write!(f, "after reading {value:#04x} from {address:#06x}"),
+            BreakReason::Watchpoint {
+                address,
+                access: Access::Write,
+                value,
+                ..
+            } => write!(f, "after writing {value:#04x} to {address:#06x}"),
+            BreakReason::Watchpoint {
+                access: Access::Execute,
+                ..
+            } => write!(f, "at a watched address"),
+            BreakReason::Step => write!(f, "after stepping"),
+            BreakReason::Frame => write!(f, "after a frame"),
+            BreakReason::Interrupt(interrupt) => {
+                write!(f, "at the start of the {interrupt:?} interrupt")
+            }
+            BreakReason::Paused => write!(f, "when paused"),
+        }
+    }
+}
+
+/// Returned by [CPU::step] when the CPU can't carry on as normal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepError {
+    /// An illegal opcode was fetched from `address`, and the CPU has locked
+    /// up.
+    IllegalOpcode { opcode: u8, address: u16 },
+    /// The CPU stopped at `address` for the debugger, without running the
+    /// instruction there. Stepping again carries on from that instruction.
+    Break { address: u16, reason: BreakReason },
+}
+
+impl fmt::Display for StepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepError::IllegalOpcode { opcode, address } => {
+                write!(f, "illegal opcode {opcode:#04x} at {address:#06x}")
+            }
+            StepError::Break { address, reason } => {
+                write!(f, "stopped at {address:#06x} {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StepError {}
+
+impl CPU {
+    /// Choose what happens when the CPU fetches an illegal opcode. Defaults
+    /// to locking up.
+    pub fn set_illegal_opcode_policy(&mut self, policy: IllegalOpcodePolicy) {
+        self.illegal_opcode_policy = policy;
+    }
+
+    /// Has the CPU locked up after fetching an illegal opcode.
+    pub fn locked_up(&self) -> bool {
+        self.locked_up
+    }
+
+    /// Note an illegal opcode has been fetched, to be handled once the
+    /// fetch has finished. Takes the single cycle of the fetch.
+    pub(super) fn illegal_opcode(&mut self, opcode: u8) -> u8 {
+        self.illegal_opcode = Some(opcode);
+
+        1
+    }
+
+    /// Apply the illegal opcode policy to an opcode fetched by the last
+    /// instruction.
+    pub(super) fn handle_illegal_opcode(&mut self, opcode: u8) -> Result<(), StepError> {
+        let address = self
+            .registers
+            .read_sixteen(SixteenBitRegister::PC)
+            .wrapping_sub(1);
+
+        match self.illegal_opcode_policy {
+            IllegalOpcodePolicy::LockUp => {
+                self.locked_up = true;
+                Ok(())
+            }
+            IllegalOpcodePolicy::Error => {
+                self.locked_up = true;
+                Err(StepError::IllegalOpcode { opcode, address })
+            }
+            IllegalOpcodePolicy::Break => {
+                self.registers
+                    .write_sixteen(SixteenBitRegister::PC, address);
+                Err(StepError::Break {
+                    address,
+                    reason: BreakReason::IllegalOpcode(opcode),
+                })
+            }
+        }
+    }
+
+    /// Once locked up only the rest of the system keeps running.
+    pub(super) fn locked_up_step(&mut self) -> u8 {
+        self.mmu.step(1);
+
+        self.clock += 1;
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::tests::{cpu_running, pc};
+
+    use super::*;
+
+    /// A CPU about to run INC A, then the illegal opcode 0xD3.
+    fn cpu_at_illegal_opcode(policy: IllegalOpcodePolicy) -> CPU {
+        let mut cpu = cpu_running(&[0x3C, 0xD3, 0x3C]);
+        cpu.set_illegal_opcode_policy(policy);
+        cpu
+    }
+
+    #[test]
+    fn locks_up() {
+        let mut cpu = cpu_at_illegal_opcode(IllegalOpcodePolicy::LockUp);
+        cpu.step().unwrap();
+        assert_eq!(cpu.step(), Ok(1));
+        assert!(cpu.locked_up());
+
+        cpu.ime = true;
+        cpu.mmu.write_u8(0xFFFF, 0x04);
+        cpu.mmu.write_u8(0xFF0F, 0x04);
+        for _ in 0..10 {
+            assert_eq!(cpu.step(), Ok(1));
+        }
+
+        assert_eq!(pc(&cpu), 0x102);
+        assert_eq!(cpu.registers.read_eight(EightBitRegister::A), 0x02);
+    }
+
+    #[test]
+    fn returns_an_error() {
+        let mut cpu = cpu_at_illegal_opcode(IllegalOpcodePolicy::Error);
+        cpu.step().unwrap();
+
+        assert_eq!(
+            cpu.step(),
+            Err(StepError::IllegalOpcode {
+                opcode: 0xD3,
+                address: 0x101
+            })
+        );
+        assert!(cpu.locked_up());
+        assert_eq!(cpu.step(), Ok(1));
+    }
+
+    #[test]
+    fn breaks_before_the_opcode() {
+        let mut cpu = cpu_at_illegal_opcode(IllegalOpcodePolicy::Break);
+        cpu.step().unwrap();
+
+        let error = Err(StepError::Break {
+            address: 0x101,
+            reason: BreakReason::IllegalOpcode(0xD3),
+        });
+        assert_eq!(cpu.step(), error);
+        assert!(!cpu.locked_up());
+        assert_eq!(pc(&cpu), 0x101);
+        assert_eq!(cpu.step(), error);
+    }
+}
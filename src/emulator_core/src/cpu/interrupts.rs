@@ -1,5 +1,3 @@
-use crate::mmu::Interrupt;
-
 use super::*;
 
 impl CPU {
@@ -7,7 +5,8 @@ impl CPU {
         match (self.ime, self.halted) {
             (false, false) => 0,
             (false, true) => self.halted_pending_interrupt(),
-            (true, false) => self.interrupt(),
+            (true, false) if self.pending_interrupts != 0 => self.interrupt(),
+            (true, false) => 0,
             (true, true) => self.halted_interrupt(),
         }
     }
@@ -17,8 +16,7 @@ impl CPU {
             return 0;
         }
 
-        self.halted = false;
-        0
+        self.wake()
     }
 
     fn halted_interrupt(&mut self) -> u8 {
@@ -26,25 +24,69 @@ impl CPU {
             return 0;
         }
 
+        self.wake() + self.interrupt()
+    }
+
+    /// Leaving HALT takes an extra cycle, whether or not the interrupt is
+    /// then serviced.
+    fn wake(&mut self) -> u8 {
         self.halted = false;
-        self.interrupt()
+        self.mmu.step(1);
+        1
     }
 
+    /// Dispatch the highest priority pending interrupt, taking 5 cycles: two
+    /// waiting, two pushing PC and one jumping to the handler. The interrupt
+    /// is only picked once the upper byte of PC has been pushed, so if that
+    /// push overwrites IE and nothing is left pending, the dispatch is
+    /// cancelled and jumps to 0x0000 instead.
     fn interrupt(&mut self) -> u8 {
+        if !self.mmu.interrupts.has_interrupt() {
+            return 0;
+        }
+
+        self.ime = false;
+        self.mmu.step(2);
+
+        // After EI; HALT with an interrupt pending, the handler returns to
+        // the HALT.
+        if std::mem::take(&mut self.halt_bug) {
+            self.registers
+                .update_sixteen(SixteenBitRegister::PC, |pc| pc.wrapping_sub(1));
+        }
+
+        let [low, high] = self
+            .registers
+            .read_sixteen(SixteenBitRegister::PC)
+            .to_le_bytes();
+        let sp = self.registers.read_sixteen(SixteenBitRegister::SP);
+
+        self.mmu.write_u8(sp.wrapping_sub(1), high);
+        self.watch(sp.wrapping_sub(1), Access::Write, high);
+        self.mmu.step(1);
         let interrupts = self.mmu.interrupts.interrupt_mask();
 
-        for i in 0..5 {
-            if interrupts & (1 << i) != 0 {
-                let interrupt: Interrupt = (1 << i).into();
-                self.ime = false;
-                self.mmu.interrupts.interrupt_service(interrupt);
-                stack_push(self, self.registers.read_sixteen(SixteenBitRegister::PC));
+        self.mmu.write_u8(sp.wrapping_sub(2), low);
+        self.watch(sp.wrapping_sub(2), Access::Write, low);
+        self.registers
+            .write_sixteen(SixteenBitRegister::SP, sp.wrapping_sub(2));
+        self.mmu.step(1);
+
+        let interrupt = (0..5).find(|i| interrupts & (1 << i) != 0);
+        match interrupt {
+            Some(i) => {
+                self.mmu.interrupts.interrupt_service((1 << i).into());
                 self.interrupt_jump(i);
-                return 4;
+                self.watch_interrupt((1 << i).into());
             }
+            None => self.registers.write_sixteen(SixteenBitRegister::PC, 0x0000),
         }
+        if self.trace.is_some() {
+            self.trace_interrupt(interrupt.map(|i| (1 << i).into()));
+        }
+        self.mmu.step(1);
 
-        0
+        5
     }
 
     fn interrupt_jump(&mut self, interrupt: u8) {
@@ -58,3 +100,112 @@ impl CPU {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::tests::{cpu_running, pc};
+    use crate::mmu::Interrupt;
+
+    use super::*;
+
+    /// A CPU with no interrupts enabled or requested, about to run the
+    /// given code.
+    fn cpu_without_interrupts(code: &[u8]) -> CPU {
+        let mut cpu = cpu_running(code);
+        cpu.mmu.write_u8(0xFFFF, 0x00);
+        cpu.mmu.write_u8(0xFF0F, 0x00);
+        cpu
+    }
+
+    fn a(cpu: &CPU) -> u8 {
+        cpu.registers.read_eight(EightBitRegister::A)
+    }
+
+    #[test]
+    fn halt_bug_reads_next_byte_twice() {
+        // HALT, INC A, NOP
+        let mut cpu = cpu_without_interrupts(&[0x76, 0x3C, 0x00]);
+        cpu.mmu.write_u8(0xFFFF, 0x04);
+        cpu.mmu.write_u8(0xFF0F, 0x04);
+        let start = a(&cpu);
+
+        cpu.step().unwrap();
+        assert!(!cpu.halted);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+
+        assert_eq!(a(&cpu), start.wrapping_add(2));
+        assert_eq!(cpu.registers.read_sixteen(SixteenBitRegister::PC), 0x102);
+    }
+
+    #[test]
+    fn waking_from_halt_takes_a_cycle() {
+        // HALT, INC A
+        let mut cpu = cpu_without_interrupts(&[0x76, 0x3C]);
+        cpu.mmu.write_u8(0xFFFF, 0x04);
+        let start = a(&cpu);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.step(), Ok(1));
+        assert!(cpu.halted);
+
+        cpu.mmu.interrupts.request_interrupt(Interrupt::Timer);
+        assert_eq!(cpu.step(), Ok(2));
+        assert!(!cpu.halted);
+
+        cpu.step().unwrap();
+        assert_eq!(a(&cpu), start.wrapping_add(1));
+    }
+
+    #[test]
+    fn ei_enables_interrupts_after_the_next_instruction() {
+        // EI, INC A, INC A
+        let mut cpu = cpu_without_interrupts(&[0xFB, 0x3C, 0x3C]);
+        cpu.mmu.write_u8(0xFFFF, 0x04);
+        cpu.mmu.interrupts.request_interrupt(Interrupt::Timer);
+        let start = a(&cpu);
+
+        assert_eq!(cpu.step(), Ok(1));
+        assert_eq!(pc(&cpu), 0x101);
+
+        // The interrupt is dispatched after INC A, taking 5 cycles.
+        assert_eq!(cpu.step(), Ok(1 + 5));
+        assert_eq!(pc(&cpu), 0x50);
+        assert_eq!(a(&cpu), start.wrapping_add(1));
+        assert_eq!(cpu.mmu.read_u16(0xFFFC), 0x102);
+        assert_eq!(cpu.mmu.interrupts.interrupt_mask(), 0);
+    }
+
+    #[test]
+    fn di_cancels_ei() {
+        // EI, DI, NOP
+        let mut cpu = cpu_without_interrupts(&[0xFB, 0xF3, 0x00]);
+        cpu.mmu.write_u8(0xFFFF, 0x04);
+        cpu.mmu.interrupts.request_interrupt(Interrupt::Timer);
+
+        for _ in 0..3 {
+            cpu.step().unwrap();
+        }
+
+        assert_eq!(pc(&cpu), 0x103);
+        assert!(cpu.mmu.interrupts.has_interrupt());
+    }
+
+    #[test]
+    fn pushing_over_ie_cancels_dispatch() {
+        // EI, NOP
+        let mut cpu = cpu_without_interrupts(&[0xFB, 0x00]);
+        cpu.registers.write_sixteen(SixteenBitRegister::SP, 0x0000);
+        cpu.mmu.write_u8(0xFFFF, 0x04);
+        cpu.mmu.interrupts.request_interrupt(Interrupt::Timer);
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+
+        // The upper byte of PC, 0x01, replaced IE before the interrupt was
+        // picked, leaving nothing to dispatch.
+        assert_eq!(pc(&cpu), 0x0000);
+        assert_eq!(cpu.mmu.read_u8(0xFFFF), 0x01);
+        assert_eq!(cpu.mmu.read_u8(0xFF0F) & 0x04, 0x04);
+    }
+}
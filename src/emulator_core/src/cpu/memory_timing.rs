@@ -0,0 +1,76 @@
+use std::str::FromStr;
+
+use super::*;
+
+/// When the rest of the system (timer, PPU and DMA) is run for the cycles
+/// of an instruction.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryTiming {
+    /// Run the rest of the system for a cycle at each memory access, so
+    /// reads and writes land at the right point of the instruction.
+    #[default]
+    PerAccess,
+    /// Run the rest of the system for the whole instruction once it's done.
+    /// Cheaper, but the timer, PPU and DMA only ever see the state from
+    /// between instructions.
+    PerInstruction,
+}
+
+impl FromStr for MemoryTiming {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "access" | "per-access" => Ok(MemoryTiming::PerAccess),
+            "instruction" | "per-instruction" => Ok(MemoryTiming::PerInstruction),
+            _ => Err(format!("Unknown memory timing: {s}")),
+        }
+    }
+}
+
+impl CPU {
+    /// Choose when the rest of the system is run for the cycles of an
+    /// instruction. Defaults to at each memory access.
+    pub fn set_memory_timing(&mut self, timing: MemoryTiming) {
+        self.memory_timing = timing;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::tests::{cpu_running, mock_cpu};
+
+    use super::*;
+
+    #[test]
+    fn parse_memory_timing() {
+        assert_eq!("per-access".parse(), Ok(MemoryTiming::PerAccess));
+        assert_eq!("Instruction".parse(), Ok(MemoryTiming::PerInstruction));
+        assert!("per-cycle".parse::<MemoryTiming>().is_err());
+    }
+
+    #[test]
+    fn accesses_only_tick_per_access() {
+        let mut cpu = mock_cpu();
+        cpu.fetch_u8();
+        assert_eq!(cpu.ticks, 1);
+
+        let mut cpu = mock_cpu();
+        cpu.set_memory_timing(MemoryTiming::PerInstruction);
+        cpu.fetch_u8();
+        assert_eq!(cpu.ticks, 0);
+    }
+
+    #[test]
+    fn instructions_take_as_long_either_way() {
+        // LD [HL], n8
+        let code = [0x36, 0x12];
+        let mut per_access = cpu_running(&code);
+        let mut per_instruction = cpu_running(&code);
+        per_instruction.set_memory_timing(MemoryTiming::PerInstruction);
+
+        assert_eq!(per_access.step(), Ok(3));
+        assert_eq!(per_instruction.step(), Ok(3));
+        assert_eq!(per_access.ticks, per_instruction.ticks);
+    }
+}
@@ -48,7 +48,7 @@ pub fn rr(registers: &mut Registers, reg: EightBitRegister) {
 pub fn rlc_val(registers: &mut Registers, value: u8) -> u8 {
     let carry = value & 0b1000_0000 == 0b1000_0000;
 
-    let result = value << 1 | (value >> 7);
+    let result = value.rotate_left(1);
     registers.set_carry_flag(carry);
     registers.set_half_carry_flag(false);
     registers.set_subtract_flag(false);
@@ -68,7 +68,7 @@ pub fn rlc(registers: &mut Registers, reg: EightBitRegister) {
 pub fn rrc_val(registers: &mut Registers, value: u8) -> u8 {
     let carry = value & 0b0000_0001 == 0b0000_0001;
 
-    let result = value >> 1 | (value << 7);
+    let result = value.rotate_right(1);
     registers.set_carry_flag(carry);
     registers.set_half_carry_flag(false);
     registers.set_subtract_flag(false);
@@ -1,5 +1,3 @@
-use std::ops::Sub;
-
 use super::*;
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -62,11 +60,18 @@ pub fn ret(cpu: &mut CPU) {
     cpu.registers.write_sixteen(SixteenBitRegister::PC, address);
 }
 
+/// Conditional returns check the condition in an internal cycle, before
+/// popping the address.
+fn ret_taken(cpu: &mut CPU) -> ReturnResult {
+    cpu.tick();
+    ret(cpu);
+    ReturnResult::Returned
+}
+
 /// Return from a subroutine if the zero flag is not set.
 pub fn ret_nz(cpu: &mut CPU) -> ReturnResult {
     if !cpu.registers.get_zero_flag() {
-        ret(cpu);
-        ReturnResult::Returned
+        ret_taken(cpu)
     } else {
         ReturnResult::DidNotReturn
     }
@@ -75,8 +80,7 @@ pub fn ret_nz(cpu: &mut CPU) -> ReturnResult {
 /// Return from a subroutine if the zero flag is set.
 pub fn ret_z(cpu: &mut CPU) -> ReturnResult {
     if cpu.registers.get_zero_flag() {
-        ret(cpu);
-        ReturnResult::Returned
+        ret_taken(cpu)
     } else {
         ReturnResult::DidNotReturn
     }
@@ -85,8 +89,7 @@ pub fn ret_z(cpu: &mut CPU) -> ReturnResult {
 /// Return from a subroutine if the carry flag is not set.
 pub fn ret_nc(cpu: &mut CPU) -> ReturnResult {
     if !cpu.registers.get_carry_flag() {
-        ret(cpu);
-        ReturnResult::Returned
+        ret_taken(cpu)
     } else {
         ReturnResult::DidNotReturn
     }
@@ -95,37 +98,38 @@ pub fn ret_nc(cpu: &mut CPU) -> ReturnResult {
 /// Return from a subroutine if the carry flag is set.
 pub fn ret_c(cpu: &mut CPU) -> ReturnResult {
     if cpu.registers.get_carry_flag() {
-        ret(cpu);
-        ReturnResult::Returned
+        ret_taken(cpu)
     } else {
         ReturnResult::DidNotReturn
     }
 }
 
+/// Push a value onto the stack, upper byte first. Every push is preceded
+/// by an internal cycle, as SP is decremented.
 pub fn stack_push(cpu: &mut CPU, value: u16) {
-    let sp = cpu.registers.read_sixteen(SixteenBitRegister::SP).sub(2);
-    cpu.mmu.write_u16(sp, value);
-    cpu.registers.write_sixteen(SixteenBitRegister::SP, sp);
+    let [low, high] = value.to_le_bytes();
+    let sp = cpu.registers.read_sixteen(SixteenBitRegister::SP);
+
+    cpu.tick();
+    cpu.write_u8(sp.wrapping_sub(1), high);
+    cpu.write_u8(sp.wrapping_sub(2), low);
+    cpu.registers
+        .write_sixteen(SixteenBitRegister::SP, sp.wrapping_sub(2));
 }
 
 pub fn stack_pop(cpu: &mut CPU) -> u16 {
     let sp = cpu.registers.read_sixteen(SixteenBitRegister::SP);
-    let value = cpu.mmu.read_u16(sp);
+    let value = cpu.read_u16(sp);
 
     cpu.registers
-        .update_sixteen(SixteenBitRegister::SP, |sp| sp + 2);
+        .update_sixteen(SixteenBitRegister::SP, |sp| sp.wrapping_add(2));
 
     value
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
-
-    use crate::cartridge::NoMBC;
-    use crate::mmu::ppu::PPU;
-    use crate::mmu::TestRenderer;
-    use crate::Joypad;
+    use crate::cpu::tests::mock_cpu;
 
     use super::*;
 
@@ -141,6 +145,20 @@ mod tests {
         assert_eq!(stack_pop(&mut cpu), 0x1234);
     }
 
+    #[test]
+    fn stack_push_and_pop_cycles() {
+        let mut cpu = mock_cpu();
+        cpu.registers.write_sixteen(SixteenBitRegister::SP, 0xFFFE);
+        stack_push(&mut cpu, 0x1234);
+        assert_eq!(cpu.ticks, 3);
+        assert_eq!(cpu.mmu.read_u8(0xFFFD), 0x12);
+        assert_eq!(cpu.mmu.read_u8(0xFFFC), 0x34);
+
+        cpu.ticks = 0;
+        stack_pop(&mut cpu);
+        assert_eq!(cpu.ticks, 2);
+    }
+
     #[test]
     fn call_and_return() {
         let mut cpu = mock_cpu();
@@ -241,13 +259,4 @@ mod tests {
         assert_eq!(result, ReturnResult::Returned);
         assert_eq!(cpu.registers.read_sixteen(SixteenBitRegister::PC), 0x5678);
     }
-
-    fn mock_cpu() -> CPU {
-        let cartridge = Box::new(NoMBC::new(vec![0; 0x8000]));
-        let ppu = PPU::new(Arc::new(TestRenderer));
-        let joypad = Arc::new(Joypad::new());
-        let mmu = MMU::new(ppu, cartridge, joypad);
-
-        CPU::new(mmu)
-    }
 }
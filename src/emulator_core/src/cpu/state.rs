@@ -0,0 +1,246 @@
+use super::*;
+
+/// The flags held in the upper nibble of F. The lower nibble always reads
+/// 0.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Flags {
+    pub zero: bool,
+    pub subtract: bool,
+    pub half_carry: bool,
+    pub carry: bool,
+}
+
+impl Flags {
+    pub fn from_bits(f: u8) -> Flags {
+        Flags {
+            zero: f & 0x80 != 0,
+            subtract: f & 0x40 != 0,
+            half_carry: f & 0x20 != 0,
+            carry: f & 0x10 != 0,
+        }
+    }
+
+    pub fn bits(&self) -> u8 {
+        (self.zero as u8) << 7
+            | (self.subtract as u8) << 6
+            | (self.half_carry as u8) << 5
+            | (self.carry as u8) << 4
+    }
+}
+
+/// A copy of everything the CPU keeps track of, taken with [CPU::state] and
+/// put back with [CPU::set_state].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuState {
+    pub a: u8,
+    pub f: Flags,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub sp: u16,
+    pub pc: u16,
+    /// Interrupt master enable.
+    pub ime: bool,
+    /// EI has run, and IME will be set after the next instruction.
+    pub ime_scheduled: bool,
+    pub halted: bool,
+    /// HALT ran with IME off and an interrupt pending, so the next opcode
+    /// fetch won't increment PC.
+    pub halt_bug: bool,
+    pub stopped: bool,
+    /// An illegal opcode has wedged the CPU until it's reset.
+    pub locked_up: bool,
+    /// M-cycles run since the CPU was created, wrapping around.
+    pub clock: u32,
+}
+
+impl CpuState {
+    pub fn af(&self) -> u16 {
+        u16::from_be_bytes([self.a, self.f.bits()])
+    }
+
+    pub fn b(&self) -> u8 {
+        self.bc.to_be_bytes()[0]
+    }
+
+    pub fn c(&self) -> u8 {
+        self.bc.to_be_bytes()[1]
+    }
+
+    pub fn d(&self) -> u8 {
+        self.de.to_be_bytes()[0]
+    }
+
+    pub fn e(&self) -> u8 {
+        self.de.to_be_bytes()[1]
+    }
+
+    pub fn h(&self) -> u8 {
+        self.hl.to_be_bytes()[0]
+    }
+
+    pub fn l(&self) -> u8 {
+        self.hl.to_be_bytes()[1]
+    }
+}
+
+impl CPU {
+    /// Take a copy of the CPU's registers and state.
+    pub fn state(&self) -> CpuState {
+        CpuState {
+            a: self.registers.read_eight(EightBitRegister::A),
+            f: Flags::from_bits(self.registers.read_eight(EightBitRegister::F)),
+            bc: self.registers.read_sixteen(SixteenBitRegister::BC),
+            de: self.registers.read_sixteen(SixteenBitRegister::DE),
+            hl: self.registers.read_sixteen(SixteenBitRegister::HL),
+            sp: self.registers.read_sixteen(SixteenBitRegister::SP),
+            pc: self.registers.read_sixteen(SixteenBitRegister::PC),
+            ime: self.ime,
+            ime_scheduled: self.ime_scheduled,
+            halted: self.halted,
+            halt_bug: self.halt_bug,
+            stopped: self.stopped,
+            locked_up: self.locked_up,
+            clock: self.clock,
+        }
+    }
+
+    /// Overwrite the CPU's registers and state, taking effect from the next
+    /// step. Best done between steps of [CPU::step], not from inside one.
+    pub fn set_state(&mut self, state: CpuState) {
+        self.registers
+            .write_sixteen(SixteenBitRegister::AF, state.af());
+        self.registers
+            .write_sixteen(SixteenBitRegister::BC, state.bc);
+        self.registers
+            .write_sixteen(SixteenBitRegister::DE, state.de);
+        self.registers
+            .write_sixteen(SixteenBitRegister::HL, state.hl);
+        self.registers
+            .write_sixteen(SixteenBitRegister::SP, state.sp);
+        self.registers
+            .write_sixteen(SixteenBitRegister::PC, state.pc);
+        self.ime = state.ime;
+        self.ime_scheduled = state.ime_scheduled;
+        self.halted = state.halted;
+        self.halt_bug = state.halt_bug;
+        self.stopped = state.stopped;
+        self.locked_up = state.locked_up;
+        self.clock = state.clock;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::tests::cpu_running;
+
+    use super::*;
+
+    #[test]
+    fn flags_round_trip() {
+        let flags = Flags::from_bits(0xBF);
+        assert!(flags.zero);
+        assert!(!flags.subtract);
+        assert!(flags.half_carry);
+        assert!(flags.carry);
+        assert_eq!(flags.bits(), 0xB0);
+    }
+
+    #[test]
+    fn state_after_boot() {
+        let state = cpu_running(&[]).state();
+
+        assert_eq!(state.af(), 0x01B0);
+        assert_eq!((state.b(), state.c()), (0x00, 0x13));
+        assert_eq!((state.d(), state.e()), (0x00, 0xD8));
+        assert_eq!((state.h(), state.l()), (0x01, 0x4D));
+        assert_eq!(state.sp, 0xFFFE);
+        assert_eq!(state.pc, 0x0100);
+        assert!(!state.ime && !state.ime_scheduled);
+        assert!(!state.halted && !state.halt_bug);
+        assert!(!state.stopped && !state.locked_up);
+        assert_eq!(state.clock, 0);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn registers_still_dumps_the_state() {
+        let dump = cpu_running(&[]).registers();
+
+        assert_eq!((dump.b, dump.c), (0x00, 0x13));
+        assert_eq!((dump.d, dump.e), (0x00, 0xD8));
+        assert_eq!((dump.h, dump.l), (0x01, 0x4D));
+    }
+
+    #[test]
+    fn state_follows_steps() {
+        // EI, INC A
+        let mut cpu = cpu_running(&[0xFB, 0x3C]);
+
+        cpu.step().unwrap();
+        let state = cpu.state();
+        assert!(state.ime_scheduled);
+        assert!(!state.ime);
+        assert_eq!(state.clock, 1);
+
+        cpu.step().unwrap();
+        let state = cpu.state();
+        assert!(state.ime);
+        assert_eq!(state.a, 0x02);
+        assert_eq!(state.f, Flags::from_bits(0x10));
+        assert_eq!(state.pc, 0x0102);
+    }
+
+    #[test]
+    fn set_state() {
+        // INC A
+        let mut cpu = cpu_running(&[0x3C]);
+        let mut state = cpu.state();
+        state.a = 0xFF;
+        state.f = Flags::default();
+        state.bc = 0x1234;
+        state.clock = 100;
+        cpu.set_state(state);
+        assert_eq!(cpu.state(), state);
+
+        cpu.step().unwrap();
+        let state = cpu.state();
+        assert_eq!(state.a, 0x00);
+        assert!(state.f.zero && state.f.half_carry);
+        assert_eq!(state.bc, 0x1234);
+        assert_eq!(state.clock, 101);
+    }
+
+    #[test]
+    fn set_state_unwedges_a_locked_up_cpu() {
+        // Illegal opcode, INC A
+        let mut cpu = cpu_running(&[0xD3, 0x3C]);
+        cpu.step().unwrap();
+        let mut state = cpu.state();
+        assert!(state.locked_up);
+
+        state.locked_up = false;
+        state.pc = 0x0101;
+        cpu.set_state(state);
+        cpu.step().unwrap();
+        assert_eq!(cpu.state().a, 0x02);
+    }
+
+    #[test]
+    fn set_state_restores_the_halt_bug() {
+        // INC A, INC A
+        let mut cpu = cpu_running(&[0x3C, 0x3C]);
+        let mut state = cpu.state();
+        state.halt_bug = true;
+        cpu.set_state(state);
+        assert!(cpu.state().halt_bug);
+
+        // PC isn't incremented, so INC A runs twice.
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        let state = cpu.state();
+        assert!(!state.halt_bug);
+        assert_eq!(state.a, 0x03);
+        assert_eq!(state.pc, 0x0101);
+    }
+}
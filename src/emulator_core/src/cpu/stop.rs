@@ -0,0 +1,132 @@
+use super::*;
+
+impl CPU {
+    /// STOP, which stops the system clock until a button is pressed, halting
+    /// the CPU, LCD and timer. DIV is reset. STOP is usually followed by a
+    /// padding byte, which is skipped unless an interrupt is pending.
+    ///
+    /// If a button is already held, STOP isn't entered. Instead the CPU
+    /// halts, or carries straight on if an interrupt is pending.
+    ///
+    /// On a CGB an armed KEY1 turns STOP into a speed switch. Only the DMG
+    /// and SGB are emulated, where KEY1 reads $FF and can't be armed, so
+    /// STOP always stops.
+    pub(super) fn stop(&mut self) -> u8 {
+        let button_held = self.mmu.joypad_input();
+        let interrupt_pending = self.mmu.interrupts.has_interrupt();
+
+        if !interrupt_pending {
+            self.registers
+                .update_sixteen(SixteenBitRegister::PC, |pc| pc.wrapping_add(1));
+        }
+
+        if !button_held {
+            self.mmu.write_u8(0xFF04, 0);
+            self.stopped = true;
+        } else if !interrupt_pending {
+            self.halted = true;
+        }
+
+        1
+    }
+
+    /// While stopped only the cartridge's clock keeps running, until a
+    /// button is pressed on a selected joypad line.
+    pub(super) fn stopped_step(&mut self) -> u8 {
+        self.mmu.stopped_step(1);
+
+        if self.mmu.joypad_input() {
+            self.stopped = false;
+        }
+
+        self.clock += 1;
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::cpu::tests::{cpu_with_joypad, pc};
+    use crate::mmu::Button;
+    use crate::Joypad;
+
+    use super::*;
+
+    /// A CPU about to run STOP, then INC A, after a few hundred NOPs.
+    fn cpu_at_stop() -> (CPU, Arc<Joypad>) {
+        let mut code = vec![0; 0x200];
+        code.extend_from_slice(&[0x10, 0x00, 0x3C]);
+        let (mut cpu, joypad) = cpu_with_joypad(&code);
+        cpu.mmu.write_u8(0xFF00, 0x10);
+        cpu.mmu.write_u8(0xFFFF, 0x00);
+
+        while pc(&cpu) != 0x300 {
+            cpu.step().unwrap();
+        }
+
+        (cpu, joypad)
+    }
+
+    #[test]
+    fn stops_until_button_pressed() {
+        let (mut cpu, joypad) = cpu_at_stop();
+        assert_ne!(cpu.mmu.read_u8(0xFF04), 0);
+
+        cpu.step().unwrap();
+        assert!(cpu.stopped);
+        assert_eq!(pc(&cpu), 0x302);
+        assert_eq!(cpu.mmu.read_u8(0xFF04), 0);
+        let ly = cpu.mmu.read_u8(0xFF44);
+
+        for _ in 0..10000 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(pc(&cpu), 0x302);
+        assert_eq!(cpu.mmu.read_u8(0xFF04), 0);
+        assert_eq!(cpu.mmu.read_u8(0xFF44), ly);
+
+        joypad.button_down(Button::A);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert!(!cpu.stopped);
+        assert_eq!(pc(&cpu), 0x303);
+    }
+
+    #[test]
+    fn key1_cannot_arm_a_speed_switch() {
+        let (mut cpu, _) = cpu_at_stop();
+        cpu.mmu.write_u8(0xFF4D, 0x01);
+        assert_eq!(cpu.mmu.read_u8(0xFF4D), 0xFF);
+
+        cpu.step().unwrap();
+        assert!(cpu.stopped);
+        assert_eq!(cpu.mmu.read_u8(0xFF4D), 0xFF);
+    }
+
+    #[test]
+    fn halts_if_button_held() {
+        let (mut cpu, joypad) = cpu_at_stop();
+        joypad.button_down(Button::A);
+        cpu.mmu.write_u8(0xFF0F, 0x00);
+
+        cpu.step().unwrap();
+        assert!(!cpu.stopped);
+        assert!(cpu.halted);
+        assert_eq!(pc(&cpu), 0x302);
+    }
+
+    #[test]
+    fn one_byte_if_interrupt_pending() {
+        let (mut cpu, joypad) = cpu_at_stop();
+        joypad.button_down(Button::A);
+        cpu.mmu.write_u8(0xFFFF, 0x10);
+        cpu.mmu.write_u8(0xFF0F, 0x10);
+
+        cpu.step().unwrap();
+        assert!(!cpu.stopped);
+        assert!(!cpu.halted);
+        assert_eq!(pc(&cpu), 0x301);
+    }
+}
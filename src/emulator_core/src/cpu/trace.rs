@@ -0,0 +1,258 @@
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+
+use super::*;
+use crate::disasm;
+use crate::mmu::Interrupt;
+
+/// Which instructions are traced, and what's written alongside them. The
+/// defaults trace every instruction in the plain gameboy-doctor format.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TraceOptions {
+    /// Only trace instructions at addresses in this range.
+    pub pc_range: Option<RangeInclusive<u16>>,
+    /// Only trace instructions in this ROM bank, see [MMU::rom_bank].
+    /// Instructions run from outside ROM are left out.
+    pub bank: Option<usize>,
+    /// Follow each line with the disassembled instruction.
+    pub disassembly: bool,
+    /// Add a line whenever an interrupt is dispatched.
+    pub interrupts: bool,
+}
+
+/// A trace in progress, see [CPU::start_trace].
+pub(super) struct Trace {
+    options: TraceOptions,
+    writer: BufWriter<Box<dyn Write + Send>>,
+    /// First error hit while writing, returned from [CPU::stop_trace].
+    /// Nothing more is written once there's been an error.
+    error: Option<io::Error>,
+}
+
+impl Trace {
+    fn write(&mut self, line: std::fmt::Arguments) {
+        if self.error.is_none() {
+            self.error = self.writer.write_fmt(line).err();
+        }
+    }
+
+    fn traces(&self, pc: u16, mmu: &MMU) -> bool {
+        let in_range = match &self.options.pc_range {
+            Some(range) => range.contains(&pc),
+            None => true,
+        };
+        let in_bank = match self.options.bank {
+            Some(bank) => mmu.rom_bank(pc) == Some(bank),
+            None => true,
+        };
+
+        in_range && in_bank
+    }
+}
+
+impl CPU {
+    /// Write a line to `writer` before each instruction runs, in the format
+    /// compared by gameboy-doctor:
+    ///
+    /// `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`
+    ///
+    /// Nothing is written while the CPU is halted, stopped or locked up.
+    /// Output is buffered, and any trace already running is stopped first.
+    pub fn start_trace(
+        &mut self,
+        options: TraceOptions,
+        writer: impl Write + Send + 'static,
+    ) -> io::Result<()> {
+        let result = self.stop_trace();
+
+        let writer: Box<dyn Write + Send> = Box::new(writer);
+        self.trace = Some(Box::new(Trace {
+            options,
+            writer: BufWriter::new(writer),
+            error: None,
+        }));
+
+        result
+    }
+
+    /// Stop tracing, flushing the output. Returns the first error hit while
+    /// tracing, if there was one.
+    pub fn stop_trace(&mut self) -> io::Result<()> {
+        match self.trace.take() {
+            Some(mut trace) => match trace.error.take() {
+                Some(err) => Err(err),
+                None => trace.writer.flush(),
+            },
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.trace.is_some()
+    }
+
+    /// Trace the instruction about to run.
+    #[cold]
+    pub(super) fn trace_instruction(&mut self) {
+        let Some(trace) = self.trace.as_mut() else {
+            return;
+        };
+
+        let pc = self.registers.read_sixteen(SixteenBitRegister::PC);
+        if !trace.traces(pc, &self.mmu) {
+            return;
+        }
+
+        let read = |offset: u16| self.mmu.read_u8(pc.wrapping_add(offset));
+        trace.write(format_args!(
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            self.registers.read_eight(EightBitRegister::A),
+            self.registers.read_eight(EightBitRegister::F),
+            self.registers.read_eight(EightBitRegister::B),
+            self.registers.read_eight(EightBitRegister::C),
+            self.registers.read_eight(EightBitRegister::D),
+            self.registers.read_eight(EightBitRegister::E),
+            self.registers.read_eight(EightBitRegister::H),
+            self.registers.read_eight(EightBitRegister::L),
+            self.registers.read_sixteen(SixteenBitRegister::SP),
+            pc,
+            read(0),
+            read(1),
+            read(2),
+            read(3),
+        ));
+
+        if trace.options.disassembly {
+            let instruction = disasm::decode(pc, |addr| self.mmu.read_u8(addr));
+            trace.write(format_args!(" ; {instruction}"));
+        }
+
+        trace.write(format_args!("\n"));
+    }
+
+    /// Trace an interrupt being dispatched, or None if the dispatch was
+    /// cancelled and jumped to 0x0000.
+    #[cold]
+    pub(super) fn trace_interrupt(&mut self, interrupt: Option<Interrupt>) {
+        let Some(trace) = self.trace.as_mut() else {
+            return;
+        };
+
+        if !trace.options.interrupts {
+            return;
+        }
+
+        let pc = self.registers.read_sixteen(SixteenBitRegister::PC);
+        match interrupt {
+            Some(interrupt) => trace.write(format_args!(
+                "-- {interrupt:?} interrupt, jumping to {pc:04X}\n"
+            )),
+            None => trace.write(format_args!(
+                "-- Interrupt cancelled, jumping to {pc:04X}\n"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::tests::cpu_running;
+    use crate::test_writers::{FailingWriter, SharedBuffer};
+
+    use super::*;
+
+    fn trace(cpu: &mut CPU, options: TraceOptions, steps: usize) -> Vec<String> {
+        let buffer = SharedBuffer::default();
+        cpu.start_trace(options, buffer.clone()).unwrap();
+        for _ in 0..steps {
+            cpu.step().unwrap();
+        }
+        cpu.stop_trace().unwrap();
+
+        buffer.lines()
+    }
+
+    #[test]
+    fn traces_in_gameboy_doctor_format() {
+        // INC A, JP $0100
+        let mut cpu = cpu_running(&[0x3C, 0xC3, 0x00, 0x01]);
+        let lines = trace(&mut cpu, TraceOptions::default(), 3);
+
+        assert_eq!(
+            lines,
+            [
+                "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:3C,C3,00,01",
+                "A:02 F:10 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0101 PCMEM:C3,00,01,00",
+                "A:02 F:10 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:3C,C3,00,01",
+            ]
+        );
+    }
+
+    #[test]
+    fn nothing_is_written_once_stopped() {
+        let mut cpu = cpu_running(&[]);
+        let buffer = SharedBuffer::default();
+        cpu.start_trace(TraceOptions::default(), buffer.clone())
+            .unwrap();
+        cpu.step().unwrap();
+        cpu.stop_trace().unwrap();
+        cpu.step().unwrap();
+
+        assert_eq!(buffer.lines().len(), 1);
+        assert!(!cpu.is_tracing());
+    }
+
+    #[test]
+    fn filters_by_pc_and_bank() {
+        // INC A, INC B, JP $0100
+        let code = [0x3C, 0x04, 0xC3, 0x00, 0x01];
+        let options = TraceOptions {
+            pc_range: Some(0x0101..=0x0102),
+            ..TraceOptions::default()
+        };
+        let lines = trace(&mut cpu_running(&code), options, 6);
+        let pcs: Vec<_> = lines
+            .iter()
+            .map(|line| line.split(' ').nth(9).unwrap())
+            .collect();
+        assert_eq!(pcs, ["PC:0101", "PC:0102", "PC:0101", "PC:0102"]);
+
+        let options = TraceOptions {
+            bank: Some(1),
+            ..TraceOptions::default()
+        };
+        assert!(trace(&mut cpu_running(&code), options, 6).is_empty());
+    }
+
+    #[test]
+    fn annotates_disassembly_and_interrupts() {
+        // EI, NOP, with a VBlank interrupt pending.
+        let mut cpu = cpu_running(&[0xFB, 0x00]);
+        cpu.mmu.write_u8(0xFFFF, 0x01);
+        cpu.mmu.write_u8(0xFF0F, 0x01);
+        let options = TraceOptions {
+            disassembly: true,
+            interrupts: true,
+            ..TraceOptions::default()
+        };
+        let lines = trace(&mut cpu, options, 2);
+
+        assert!(lines[0].ends_with("PCMEM:FB,00,00,00 ; EI"));
+        assert!(lines[1].ends_with("PCMEM:00,00,00,00 ; NOP"));
+        assert_eq!(lines[2], "-- VBlank interrupt, jumping to 0040");
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn returns_write_errors() {
+        let mut cpu = cpu_running(&[]);
+        cpu.start_trace(TraceOptions::default(), FailingWriter)
+            .unwrap();
+        for _ in 0..1000 {
+            cpu.step().unwrap();
+        }
+
+        assert!(cpu.stop_trace().is_err());
+        assert!(cpu.stop_trace().is_ok());
+    }
+}
@@ -0,0 +1,761 @@
+//! Disassembler for the SM83, the Game Boy's CPU. Every opcode is described
+//! by an entry in [OPCODES] or, after the 0xCB prefix, [CB_OPCODES].
+//! Instructions are written in RGBDS syntax.
+
+use std::fmt;
+
+/// What an instruction does to one of the flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlagEffect {
+    Unchanged,
+    Reset,
+    Set,
+    /// Set or reset depending on the result.
+    Changed,
+}
+
+/// What an instruction does to each of the flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlagEffects {
+    pub zero: FlagEffect,
+    pub subtract: FlagEffect,
+    pub half_carry: FlagEffect,
+    pub carry: FlagEffect,
+}
+
+/// The format of an operand, and how it's read from the instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// A register, or register pair.
+    Register(&'static str),
+    /// Memory pointed to by a register pair. C points into 0xFF00-0xFFFF,
+    /// HL+ and HL- step HL after the access.
+    Indirect(&'static str),
+    /// Condition of a jump, call or return.
+    Condition(&'static str),
+    /// An 8-bit immediate.
+    Imm8,
+    /// A 16-bit immediate, also used for jump and call targets.
+    Imm16,
+    /// Memory at a 16-bit immediate address.
+    Address,
+    /// Memory at 0xFF00 plus an 8-bit immediate.
+    HighAddress,
+    /// Signed 8-bit offset from the next instruction, shown as the target.
+    Relative,
+    /// A signed 8-bit immediate.
+    Signed,
+    /// SP plus a signed 8-bit immediate.
+    StackOffset,
+    /// Address called by RST.
+    Vector(u8),
+    /// Bit number of BIT, RES and SET.
+    Bit(u8),
+}
+
+/// Everything known about an opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpcodeInfo {
+    pub mnemonic: &'static str,
+    pub operands: &'static [Operand],
+    /// Length in bytes, including the opcode and any 0xCB prefix.
+    pub length: u8,
+    /// M-cycles taken, or taken when the branch is taken for conditional
+    /// instructions.
+    pub cycles: u8,
+    /// M-cycles taken by conditional instructions when the branch isn't
+    /// taken.
+    pub cycles_not_taken: Option<u8>,
+    pub flags: FlagEffects,
+}
+
+const A: Operand = Operand::Register("A");
+const B: Operand = Operand::Register("B");
+const C: Operand = Operand::Register("C");
+const D: Operand = Operand::Register("D");
+const E: Operand = Operand::Register("E");
+const H: Operand = Operand::Register("H");
+const L: Operand = Operand::Register("L");
+const AF: Operand = Operand::Register("AF");
+const BC: Operand = Operand::Register("BC");
+const DE: Operand = Operand::Register("DE");
+const HL: Operand = Operand::Register("HL");
+const SP: Operand = Operand::Register("SP");
+const M_BC: Operand = Operand::Indirect("BC");
+const M_DE: Operand = Operand::Indirect("DE");
+const M_HL: Operand = Operand::Indirect("HL");
+const M_HLI: Operand = Operand::Indirect("HL+");
+const M_HLD: Operand = Operand::Indirect("HL-");
+const M_C: Operand = Operand::Indirect("C");
+const COND_NZ: Operand = Operand::Condition("NZ");
+const COND_Z: Operand = Operand::Condition("Z");
+const COND_NC: Operand = Operand::Condition("NC");
+const COND_C: Operand = Operand::Condition("C");
+const N8: Operand = Operand::Imm8;
+const N16: Operand = Operand::Imm16;
+const A16: Operand = Operand::Address;
+const A8: Operand = Operand::HighAddress;
+const E8: Operand = Operand::Relative;
+const S8: Operand = Operand::Signed;
+const SP_E8: Operand = Operand::StackOffset;
+
+/// Flags written as Z, N, H and C in that order: the flag's letter if it's
+/// changed, 0 or 1 if it's reset or set and - if it's left alone.
+const fn flags(effects: &str) -> FlagEffects {
+    const fn effect(c: u8) -> FlagEffect {
+        match c {
+            b'-' => FlagEffect::Unchanged,
+            b'0' => FlagEffect::Reset,
+            b'1' => FlagEffect::Set,
+            _ => FlagEffect::Changed,
+        }
+    }
+
+    let effects = effects.as_bytes();
+    FlagEffects {
+        zero: effect(effects[0]),
+        subtract: effect(effects[1]),
+        half_carry: effect(effects[2]),
+        carry: effect(effects[3]),
+    }
+}
+
+const fn op(
+    mnemonic: &'static str,
+    operands: &'static [Operand],
+    length: u8,
+    cycles: u8,
+    effects: &str,
+) -> OpcodeInfo {
+    OpcodeInfo {
+        mnemonic,
+        operands,
+        length,
+        cycles,
+        cycles_not_taken: None,
+        flags: flags(effects),
+    }
+}
+
+const fn branch(
+    mnemonic: &'static str,
+    operands: &'static [Operand],
+    length: u8,
+    cycles: u8,
+    cycles_not_taken: u8,
+    effects: &str,
+) -> OpcodeInfo {
+    OpcodeInfo {
+        cycles_not_taken: Some(cycles_not_taken),
+        ..op(mnemonic, operands, length, cycles, effects)
+    }
+}
+
+/// Every unprefixed opcode, with None for the 11 illegal opcodes.
+pub static OPCODES: [Option<OpcodeInfo>; 256] = [
+    Some(op("NOP", &[], 1, 1, "----")),                      // 0x00
+    Some(op("LD", &[BC, N16], 3, 3, "----")),                // 0x01
+    Some(op("LD", &[M_BC, A], 1, 2, "----")),                // 0x02
+    Some(op("INC", &[BC], 1, 2, "----")),                    // 0x03
+    Some(op("INC", &[B], 1, 1, "Z0H-")),                     // 0x04
+    Some(op("DEC", &[B], 1, 1, "Z1H-")),                     // 0x05
+    Some(op("LD", &[B, N8], 2, 2, "----")),                  // 0x06
+    Some(op("RLCA", &[], 1, 1, "000C")),                     // 0x07
+    Some(op("LD", &[A16, SP], 3, 5, "----")),                // 0x08
+    Some(op("ADD", &[HL, BC], 1, 2, "-0HC")),                // 0x09
+    Some(op("LD", &[A, M_BC], 1, 2, "----")),                // 0x0A
+    Some(op("DEC", &[BC], 1, 2, "----")),                    // 0x0B
+    Some(op("INC", &[C], 1, 1, "Z0H-")),                     // 0x0C
+    Some(op("DEC", &[C], 1, 1, "Z1H-")),                     // 0x0D
+    Some(op("LD", &[C, N8], 2, 2, "----")),                  // 0x0E
+    Some(op("RRCA", &[], 1, 1, "000C")),                     // 0x0F
+    Some(op("STOP", &[N8], 2, 1, "----")),                   // 0x10
+    Some(op("LD", &[DE, N16], 3, 3, "----")),                // 0x11
+    Some(op("LD", &[M_DE, A], 1, 2, "----")),                // 0x12
+    Some(op("INC", &[DE], 1, 2, "----")),                    // 0x13
+    Some(op("INC", &[D], 1, 1, "Z0H-")),                     // 0x14
+    Some(op("DEC", &[D], 1, 1, "Z1H-")),                     // 0x15
+    Some(op("LD", &[D, N8], 2, 2, "----")),                  // 0x16
+    Some(op("RLA", &[], 1, 1, "000C")),                      // 0x17
+    Some(op("JR", &[E8], 2, 3, "----")),                     // 0x18
+    Some(op("ADD", &[HL, DE], 1, 2, "-0HC")),                // 0x19
+    Some(op("LD", &[A, M_DE], 1, 2, "----")),                // 0x1A
+    Some(op("DEC", &[DE], 1, 2, "----")),                    // 0x1B
+    Some(op("INC", &[E], 1, 1, "Z0H-")),                     // 0x1C
+    Some(op("DEC", &[E], 1, 1, "Z1H-")),                     // 0x1D
+    Some(op("LD", &[E, N8], 2, 2, "----")),                  // 0x1E
+    Some(op("RRA", &[], 1, 1, "000C")),                      // 0x1F
+    Some(branch("JR", &[COND_NZ, E8], 2, 3, 2, "----")),     // 0x20
+    Some(op("LD", &[HL, N16], 3, 3, "----")),                // 0x21
+    Some(op("LD", &[M_HLI, A], 1, 2, "----")),               // 0x22
+    Some(op("INC", &[HL], 1, 2, "----")),                    // 0x23
+    Some(op("INC", &[H], 1, 1, "Z0H-")),                     // 0x24
+    Some(op("DEC", &[H], 1, 1, "Z1H-")),                     // 0x25
+    Some(op("LD", &[H, N8], 2, 2, "----")),                  // 0x26
+    Some(op("DAA", &[], 1, 1, "Z-0C")),                      // 0x27
+    Some(branch("JR", &[COND_Z, E8], 2, 3, 2, "----")),      // 0x28
+    Some(op("ADD", &[HL, HL], 1, 2, "-0HC")),                // 0x29
+    Some(op("LD", &[A, M_HLI], 1, 2, "----")),               // 0x2A
+    Some(op("DEC", &[HL], 1, 2, "----")),                    // 0x2B
+    Some(op("INC", &[L], 1, 1, "Z0H-")),                     // 0x2C
+    Some(op("DEC", &[L], 1, 1, "Z1H-")),                     // 0x2D
+    Some(op("LD", &[L, N8], 2, 2, "----")),                  // 0x2E
+    Some(op("CPL", &[], 1, 1, "-11-")),                      // 0x2F
+    Some(branch("JR", &[COND_NC, E8], 2, 3, 2, "----")),     // 0x30
+    Some(op("LD", &[SP, N16], 3, 3, "----")),                // 0x31
+    Some(op("LD", &[M_HLD, A], 1, 2, "----")),               // 0x32
+    Some(op("INC", &[SP], 1, 2, "----")),                    // 0x33
+    Some(op("INC", &[M_HL], 1, 3, "Z0H-")),                  // 0x34
+    Some(op("DEC", &[M_HL], 1, 3, "Z1H-")),                  // 0x35
+    Some(op("LD", &[M_HL, N8], 2, 3, "----")),               // 0x36
+    Some(op("SCF", &[], 1, 1, "-001")),                      // 0x37
+    Some(branch("JR", &[COND_C, E8], 2, 3, 2, "----")),      // 0x38
+    Some(op("ADD", &[HL, SP], 1, 2, "-0HC")),                // 0x39
+    Some(op("LD", &[A, M_HLD], 1, 2, "----")),               // 0x3A
+    Some(op("DEC", &[SP], 1, 2, "----")),                    // 0x3B
+    Some(op("INC", &[A], 1, 1, "Z0H-")),                     // 0x3C
+    Some(op("DEC", &[A], 1, 1, "Z1H-")),                     // 0x3D
+    Some(op("LD", &[A, N8], 2, 2, "----")),                  // 0x3E
+    Some(op("CCF", &[], 1, 1, "-00C")),                      // 0x3F
+    Some(op("LD", &[B, B], 1, 1, "----")),                   // 0x40
+    Some(op("LD", &[B, C], 1, 1, "----")),                   // 0x41
+    Some(op("LD", &[B, D], 1, 1, "----")),                   // 0x42
+    Some(op("LD", &[B, E], 1, 1, "----")),                   // 0x43
+    Some(op("LD", &[B, H], 1, 1, "----")),                   // 0x44
+    Some(op("LD", &[B, L], 1, 1, "----")),                   // 0x45
+    Some(op("LD", &[B, M_HL], 1, 2, "----")),                // 0x46
+    Some(op("LD", &[B, A], 1, 1, "----")),                   // 0x47
+    Some(op("LD", &[C, B], 1, 1, "----")),                   // 0x48
+    Some(op("LD", &[C, C], 1, 1, "----")),                   // 0x49
+    Some(op("LD", &[C, D], 1, 1, "----")),                   // 0x4A
+    Some(op("LD", &[C, E], 1, 1, "----")),                   // 0x4B
+    Some(op("LD", &[C, H], 1, 1, "----")),                   // 0x4C
+    Some(op("LD", &[C, L], 1, 1, "----")),                   // 0x4D
+    Some(op("LD", &[C, M_HL], 1, 2, "----")),                // 0x4E
+    Some(op("LD", &[C, A], 1, 1, "----")),                   // 0x4F
+    Some(op("LD", &[D, B], 1, 1, "----")),                   // 0x50
+    Some(op("LD", &[D, C], 1, 1, "----")),                   // 0x51
+    Some(op("LD", &[D, D], 1, 1, "----")),                   // 0x52
+    Some(op("LD", &[D, E], 1, 1, "----")),                   // 0x53
+    Some(op("LD", &[D, H], 1, 1, "----")),                   // 0x54
+    Some(op("LD", &[D, L], 1, 1, "----")),                   // 0x55
+    Some(op("LD", &[D, M_HL], 1, 2, "----")),                // 0x56
+    Some(op("LD", &[D, A], 1, 1, "----")),                   // 0x57
+    Some(op("LD", &[E, B], 1, 1, "----")),                   // 0x58
+    Some(op("LD", &[E, C], 1, 1, "----")),                   // 0x59
+    Some(op("LD", &[E, D], 1, 1, "----")),                   // 0x5A
+    Some(op("LD", &[E, E], 1, 1, "----")),                   // 0x5B
+    Some(op("LD", &[E, H], 1, 1, "----")),                   // 0x5C
+    Some(op("LD", &[E, L], 1, 1, "----")),                   // 0x5D
+    Some(op("LD", &[E, M_HL], 1, 2, "----")),                // 0x5E
+    Some(op("LD", &[E, A], 1, 1, "----")),                   // 0x5F
+    Some(op("LD", &[H, B], 1, 1, "----")),                   // 0x60
+    Some(op("LD", &[H, C], 1, 1, "----")),                   // 0x61
+    Some(op("LD", &[H, D], 1, 1, "----")),                   // 0x62
+    Some(op("LD", &[H, E], 1, 1, "----")),                   // 0x63
+    Some(op("LD", &[H, H], 1, 1, "----")),                   // 0x64
+    Some(op("LD", &[H, L], 1, 1, "----")),                   // 0x65
+    Some(op("LD", &[H, M_HL], 1, 2, "----")),                // 0x66
+    Some(op("LD", &[H, A], 1, 1, "----")),                   // 0x67
+    Some(op("LD", &[L, B], 1, 1, "----")),                   // 0x68
+    Some(op("LD", &[L, C], 1, 1, "----")),                   // 0x69
+    Some(op("LD", &[L, D], 1, 1, "----")),                   // 0x6A
+    Some(op("LD", &[L, E], 1, 1, "----")),                   // 0x6B
+    Some(op("LD", &[L, H], 1, 1, "----")),                   // 0x6C
+    Some(op("LD", &[L, L], 1, 1, "----")),                   // 0x6D
+    Some(op("LD", &[L, M_HL], 1, 2, "----")),                // 0x6E
+    Some(op("LD", &[L, A], 1, 1, "----")),                   // 0x6F
+    Some(op("LD", &[M_HL, B], 1, 2, "----")),                // 0x70
+    Some(op("LD", &[M_HL, C], 1, 2, "----")),                // 0x71
+    Some(op("LD", &[M_HL, D], 1, 2, "----")),                // 0x72
+    Some(op("LD", &[M_HL, E], 1, 2, "----")),                // 0x73
+    Some(op("LD", &[M_HL, H], 1, 2, "----")),                // 0x74
+    Some(op("LD", &[M_HL, L], 1, 2, "----")),                // 0x75
+    Some(op("HALT", &[], 1, 1, "----")),                     // 0x76
+    Some(op("LD", &[M_HL, A], 1, 2, "----")),                // 0x77
+    Some(op("LD", &[A, B], 1, 1, "----")),                   // 0x78
+    Some(op("LD", &[A, C], 1, 1, "----")),                   // 0x79
+    Some(op("LD", &[A, D], 1, 1, "----")),                   // 0x7A
+    Some(op("LD", &[A, E], 1, 1, "----")),                   // 0x7B
+    Some(op("LD", &[A, H], 1, 1, "----")),                   // 0x7C
+    Some(op("LD", &[A, L], 1, 1, "----")),                   // 0x7D
+    Some(op("LD", &[A, M_HL], 1, 2, "----")),                // 0x7E
+    Some(op("LD", &[A, A], 1, 1, "----")),                   // 0x7F
+    Some(op("ADD", &[A, B], 1, 1, "Z0HC")),                  // 0x80
+    Some(op("ADD", &[A, C], 1, 1, "Z0HC")),                  // 0x81
+    Some(op("ADD", &[A, D], 1, 1, "Z0HC")),                  // 0x82
+    Some(op("ADD", &[A, E], 1, 1, "Z0HC")),                  // 0x83
+    Some(op("ADD", &[A, H], 1, 1, "Z0HC")),                  // 0x84
+    Some(op("ADD", &[A, L], 1, 1, "Z0HC")),                  // 0x85
+    Some(op("ADD", &[A, M_HL], 1, 2, "Z0HC")),               // 0x86
+    Some(op("ADD", &[A, A], 1, 1, "Z0HC")),                  // 0x87
+    Some(op("ADC", &[A, B], 1, 1, "Z0HC")),                  // 0x88
+    Some(op("ADC", &[A, C], 1, 1, "Z0HC")),                  // 0x89
+    Some(op("ADC", &[A, D], 1, 1, "Z0HC")),                  // 0x8A
+    Some(op("ADC", &[A, E], 1, 1, "Z0HC")),                  // 0x8B
+    Some(op("ADC", &[A, H], 1, 1, "Z0HC")),                  // 0x8C
+    Some(op("ADC", &[A, L], 1, 1, "Z0HC")),                  // 0x8D
+    Some(op("ADC", &[A, M_HL], 1, 2, "Z0HC")),               // 0x8E
+    Some(op("ADC", &[A, A], 1, 1, "Z0HC")),                  // 0x8F
+    Some(op("SUB", &[A, B], 1, 1, "Z1HC")),                  // 0x90
+    Some(op("SUB", &[A, C], 1, 1, "Z1HC")),                  // 0x91
+    Some(op("SUB", &[A, D], 1, 1, "Z1HC")),                  // 0x92
+    Some(op("SUB", &[A, E], 1, 1, "Z1HC")),                  // 0x93
+    Some(op("SUB", &[A, H], 1, 1, "Z1HC")),                  // 0x94
+    Some(op("SUB", &[A, L], 1, 1, "Z1HC")),                  // 0x95
+    Some(op("SUB", &[A, M_HL], 1, 2, "Z1HC")),               // 0x96
+    Some(op("SUB", &[A, A], 1, 1, "Z1HC")),                  // 0x97
+    Some(op("SBC", &[A, B], 1, 1, "Z1HC")),                  // 0x98
+    Some(op("SBC", &[A, C], 1, 1, "Z1HC")),                  // 0x99
+    Some(op("SBC", &[A, D], 1, 1, "Z1HC")),                  // 0x9A
+    Some(op("SBC", &[A, E], 1, 1, "Z1HC")),                  // 0x9B
+    Some(op("SBC", &[A, H], 1, 1, "Z1HC")),                  // 0x9C
+    Some(op("SBC", &[A, L], 1, 1, "Z1HC")),                  // 0x9D
+    Some(op("SBC", &[A, M_HL], 1, 2, "Z1HC")),               // 0x9E
+    Some(op("SBC", &[A, A], 1, 1, "Z1HC")),                  // 0x9F
+    Some(op("AND", &[A, B], 1, 1, "Z010")),                  // 0xA0
+    Some(op("AND", &[A, C], 1, 1, "Z010")),                  // 0xA1
+    Some(op("AND", &[A, D], 1, 1, "Z010")),                  // 0xA2
+    Some(op("AND", &[A, E], 1, 1, "Z010")),                  // 0xA3
+    Some(op("AND", &[A, H], 1, 1, "Z010")),                  // 0xA4
+    Some(op("AND", &[A, L], 1, 1, "Z010")),                  // 0xA5
+    Some(op("AND", &[A, M_HL], 1, 2, "Z010")),               // 0xA6
+    Some(op("AND", &[A, A], 1, 1, "Z010")),                  // 0xA7
+    Some(op("XOR", &[A, B], 1, 1, "Z000")),                  // 0xA8
+    Some(op("XOR", &[A, C], 1, 1, "Z000")),                  // 0xA9
+    Some(op("XOR", &[A, D], 1, 1, "Z000")),                  // 0xAA
+    Some(op("XOR", &[A, E], 1, 1, "Z000")),                  // 0xAB
+    Some(op("XOR", &[A, H], 1, 1, "Z000")),                  // 0xAC
+    Some(op("XOR", &[A, L], 1, 1, "Z000")),                  // 0xAD
+    Some(op("XOR", &[A, M_HL], 1, 2, "Z000")),               // 0xAE
+    Some(op("XOR", &[A, A], 1, 1, "Z000")),                  // 0xAF
+    Some(op("OR", &[A, B], 1, 1, "Z000")),                   // 0xB0
+    Some(op("OR", &[A, C], 1, 1, "Z000")),                   // 0xB1
+    Some(op("OR", &[A, D], 1, 1, "Z000")),                   // 0xB2
+    Some(op("OR", &[A, E], 1, 1, "Z000")),                   // 0xB3
+    Some(op("OR", &[A, H], 1, 1, "Z000")),                   // 0xB4
+    Some(op("OR", &[A, L], 1, 1, "Z000")),                   // 0xB5
+    Some(op("OR", &[A, M_HL], 1, 2, "Z000")),                // 0xB6
+    Some(op("OR", &[A, A], 1, 1, "Z000")),                   // 0xB7
+    Some(op("CP", &[A, B], 1, 1, "Z1HC")),                   // 0xB8
+    Some(op("CP", &[A, C], 1, 1, "Z1HC")),                   // 0xB9
+    Some(op("CP", &[A, D], 1, 1, "Z1HC")),                   // 0xBA
+    Some(op("CP", &[A, E], 1, 1, "Z1HC")),                   // 0xBB
+    Some(op("CP", &[A, H], 1, 1, "Z1HC")),                   // 0xBC
+    Some(op("CP", &[A, L], 1, 1, "Z1HC")),                   // 0xBD
+    Some(op("CP", &[A, M_HL], 1, 2, "Z1HC")),                // 0xBE
+    Some(op("CP", &[A, A], 1, 1, "Z1HC")),                   // 0xBF
+    Some(branch("RET", &[COND_NZ], 1, 5, 2, "----")),        // 0xC0
+    Some(op("POP", &[BC], 1, 3, "----")),                    // 0xC1
+    Some(branch("JP", &[COND_NZ, N16], 3, 4, 3, "----")),    // 0xC2
+    Some(op("JP", &[N16], 3, 4, "----")),                    // 0xC3
+    Some(branch("CALL", &[COND_NZ, N16], 3, 6, 3, "----")),  // 0xC4
+    Some(op("PUSH", &[BC], 1, 4, "----")),                   // 0xC5
+    Some(op("ADD", &[A, N8], 2, 2, "Z0HC")),                 // 0xC6
+    Some(op("RST", &[Operand::Vector(0x00)], 1, 4, "----")), // 0xC7
+    Some(branch("RET", &[COND_Z], 1, 5, 2, "----")),         // 0xC8
+    Some(op("RET", &[], 1, 4, "----")),                      // 0xC9
+    Some(branch("JP", &[COND_Z, N16], 3, 4, 3, "----")),     // 0xCA
+    Some(op("PREFIX", &[], 1, 1, "----")),                   // 0xCB
+    Some(branch("CALL", &[COND_Z, N16], 3, 6, 3, "----")),   // 0xCC
+    Some(op("CALL", &[N16], 3, 6, "----")),                  // 0xCD
+    Some(op("ADC", &[A, N8], 2, 2, "Z0HC")),                 // 0xCE
+    Some(op("RST", &[Operand::Vector(0x08)], 1, 4, "----")), // 0xCF
+    Some(branch("RET", &[COND_NC], 1, 5, 2, "----")),        // 0xD0
+    Some(op("POP", &[DE], 1, 3, "----")),                    // 0xD1
+    Some(branch("JP", &[COND_NC, N16], 3, 4, 3, "----")),    // 0xD2
+    None,                                                    // 0xD3
+    Some(branch("CALL", &[COND_NC, N16], 3, 6, 3, "----")),  // 0xD4
+    Some(op("PUSH", &[DE], 1, 4, "----")),                   // 0xD5
+    Some(op("SUB", &[A, N8], 2, 2, "Z1HC")),                 // 0xD6
+    Some(op("RST", &[Operand::Vector(0x10)], 1, 4, "----")), // 0xD7
+    Some(branch("RET", &[COND_C], 1, 5, 2, "----")),         // 0xD8
+    Some(op("RETI", &[], 1, 4, "----")),                     // 0xD9
+    Some(branch("JP", &[COND_C, N16], 3, 4, 3, "----")),     // 0xDA
+    None,                                                    // 0xDB
+    Some(branch("CALL", &[COND_C, N16], 3, 6, 3, "----")),   // 0xDC
+    None,                                                    // 0xDD
+    Some(op("SBC", &[A, N8], 2, 2, "Z1HC")),                 // 0xDE
+    Some(op("RST", &[Operand::Vector(0x18)], 1, 4, "----")), // 0xDF
+    Some(op("LDH", &[A8, A], 2, 3, "----")),                 // 0xE0
+    Some(op("POP", &[HL], 1, 3, "----")),                    // 0xE1
+    Some(op("LDH", &[M_C, A], 1, 2, "----")),                // 0xE2
+    None,                                                    // 0xE3
+    None,                                                    // 0xE4
+    Some(op("PUSH", &[HL], 1, 4, "----")),                   // 0xE5
+    Some(op("AND", &[A, N8], 2, 2, "Z010")),                 // 0xE6
+    Some(op("RST", &[Operand::Vector(0x20)], 1, 4, "----")), // 0xE7
+    Some(op("ADD", &[SP, S8], 2, 4, "00HC")),                // 0xE8
+    Some(op("JP", &[HL], 1, 1, "----")),                     // 0xE9
+    Some(op("LD", &[A16, A], 3, 4, "----")),                 // 0xEA
+    None,                                                    // 0xEB
+    None,                                                    // 0xEC
+    None,                                                    // 0xED
+    Some(op("XOR", &[A, N8], 2, 2, "Z000")),                 // 0xEE
+    Some(op("RST", &[Operand::Vector(0x28)], 1, 4, "----")), // 0xEF
+    Some(op("LDH", &[A, A8], 2, 3, "----")),                 // 0xF0
+    Some(op("POP", &[AF], 1, 3, "ZNHC")),                    // 0xF1
+    Some(op("LDH", &[A, M_C], 1, 2, "----")),                // 0xF2
+    Some(op("DI", &[], 1, 1, "----")),                       // 0xF3
+    None,                                                    // 0xF4
+    Some(op("PUSH", &[AF], 1, 4, "----")),                   // 0xF5
+    Some(op("OR", &[A, N8], 2, 2, "Z000")),                  // 0xF6
+    Some(op("RST", &[Operand::Vector(0x30)], 1, 4, "----")), // 0xF7
+    Some(op("LD", &[HL, SP_E8], 2, 3, "00HC")),              // 0xF8
+    Some(op("LD", &[SP, HL], 1, 2, "----")),                 // 0xF9
+    Some(op("LD", &[A, A16], 3, 4, "----")),                 // 0xFA
+    Some(op("EI", &[], 1, 1, "----")),                       // 0xFB
+    None,                                                    // 0xFC
+    None,                                                    // 0xFD
+    Some(op("CP", &[A, N8], 2, 2, "Z1HC")),                  // 0xFE
+    Some(op("RST", &[Operand::Vector(0x38)], 1, 4, "----")), // 0xFF
+];
+
+/// Every opcode following the 0xCB prefix. These follow a regular pattern:
+/// the lowest 3 bits pick the register, the rest the operation.
+pub static CB_OPCODES: [OpcodeInfo; 256] = cb_opcodes();
+
+const fn cb_opcodes() -> [OpcodeInfo; 256] {
+    const REGISTERS: [&[Operand]; 8] = [&[B], &[C], &[D], &[E], &[H], &[L], &[M_HL], &[A]];
+    const SHIFTS: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+    const BITS: [[&[Operand]; 8]; 8] = [
+        bit_operands::<0>(),
+        bit_operands::<1>(),
+        bit_operands::<2>(),
+        bit_operands::<3>(),
+        bit_operands::<4>(),
+        bit_operands::<5>(),
+        bit_operands::<6>(),
+        bit_operands::<7>(),
+    ];
+
+    let mut table = [op("", &[], 2, 2, "----"); 256];
+    let mut opcode = 0;
+    while opcode < 256 {
+        let register = opcode & 7;
+        let hl = register == 6;
+        let bit = (opcode >> 3) & 7;
+
+        table[opcode] = match opcode >> 6 {
+            0 if bit == 6 => op("SWAP", REGISTERS[register], 2, 2, "Z000"),
+            0 => op(SHIFTS[bit], REGISTERS[register], 2, 2, "Z00C"),
+            1 => op("BIT", BITS[bit][register], 2, 2, "Z01-"),
+            2 => op("RES", BITS[bit][register], 2, 2, "----"),
+            _ => op("SET", BITS[bit][register], 2, 2, "----"),
+        };
+
+        // (HL) takes an extra cycle to read, and BIT aside one more to
+        // write back.
+        if hl {
+            table[opcode].cycles = if opcode >> 6 == 1 { 3 } else { 4 };
+        }
+
+        opcode += 1;
+    }
+
+    table
+}
+
+const fn bit_operands<const BIT: u8>() -> [&'static [Operand]; 8] {
+    [
+        &[Operand::Bit(BIT), B],
+        &[Operand::Bit(BIT), C],
+        &[Operand::Bit(BIT), D],
+        &[Operand::Bit(BIT), E],
+        &[Operand::Bit(BIT), H],
+        &[Operand::Bit(BIT), L],
+        &[Operand::Bit(BIT), M_HL],
+        &[Operand::Bit(BIT), A],
+    ]
+}
+
+/// A decoded instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: u16,
+    /// The bytes of the instruction, including any 0xCB prefix.
+    pub bytes: Vec<u8>,
+    /// None for an illegal opcode, which is a single byte.
+    pub info: Option<&'static OpcodeInfo>,
+}
+
+impl Instruction {
+    /// Address of the instruction which follows this one.
+    pub fn next_address(&self) -> u16 {
+        self.address.wrapping_add(self.bytes.len() as u16)
+    }
+
+    fn imm8(&self) -> u8 {
+        self.bytes[self.bytes.len() - 1]
+    }
+
+    fn imm16(&self) -> u16 {
+        u16::from_le_bytes([self.bytes[1], self.bytes[2]])
+    }
+
+    fn format_operand(&self, f: &mut fmt::Formatter<'_>, operand: Operand) -> fmt::Result {
+        match operand {
+            Operand::Register(name) | Operand::Condition(name) => write!(f, "{name}"),
+            Operand::Indirect(name) => write!(f, "[{name}]"),
+            Operand::Imm8 => write!(f, "${:02X}", self.imm8()),
+            Operand::Imm16 => write!(f, "${:04X}", self.imm16()),
+            Operand::Address => write!(f, "[${:04X}]", self.imm16()),
+            Operand::HighAddress => write!(f, "[$FF{:02X}]", self.imm8()),
+            Operand::Relative => {
+                let offset = self.imm8() as i8 as u16;
+                write!(f, "${:04X}", self.next_address().wrapping_add(offset))
+            }
+            Operand::Signed => write!(f, "{}", self.imm8() as i8),
+            Operand::StackOffset => write!(f, "SP{:+}", self.imm8() as i8),
+            Operand::Vector(address) => write!(f, "${address:02X}"),
+            Operand::Bit(bit) => write!(f, "{bit}"),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(info) = self.info else {
+            return write!(f, "DB ${:02X}", self.bytes[0]);
+        };
+
+        write!(f, "{}", info.mnemonic)?;
+        for (i, operand) in info.operands.iter().enumerate() {
+            f.write_str(if i == 0 { " " } else { ", " })?;
+            self.format_operand(f, *operand)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Look up an opcode, following the 0xCB prefix to the second byte.
+pub fn opcode_info(opcode: u8, next: u8) -> Option<&'static OpcodeInfo> {
+    match opcode {
+        0xCB => Some(&CB_OPCODES[next as usize]),
+        _ => OPCODES[opcode as usize].as_ref(),
+    }
+}
+
+/// Decode the instruction at an address, reading its bytes with `read`.
+pub fn decode(address: u16, read: impl Fn(u16) -> u8) -> Instruction {
+    let opcode = read(address);
+    let info = opcode_info(opcode, read(address.wrapping_add(1)));
+    let length = info.map_or(1, |info| info.length);
+    let bytes = (0..length as u16)
+        .map(|offset| read(address.wrapping_add(offset)))
+        .collect();
+
+    Instruction {
+        address,
+        bytes,
+        info,
+    }
+}
+
+/// Decode every instruction starting from `start`, up to but not including
+/// `end`.
+pub fn disassemble(start: u16, end: u16, read: impl Fn(u16) -> u8) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut address = start as u32;
+
+    while address < end as u32 {
+        let instruction = decode(address as u16, &read);
+        address += instruction.bytes.len() as u32;
+        instructions.push(instruction);
+    }
+
+    instructions
+}
+
+/// Disassemble straight from a ROM image, with `bank` mapped at
+/// 0x4000-0x7FFF as an MBC would. Addresses are those the CPU would see,
+/// so `start` and `end` should be within 0x0000-0x7FFF. Bytes past the end
+/// of the ROM read as 0xFF.
+pub fn disassemble_rom(rom: &[u8], bank: usize, start: u16, end: u16) -> Vec<Instruction> {
+    disassemble(start, end, |address| {
+        let offset = match address {
+            0..=0x3FFF => address as usize,
+            _ => bank * 0x4000 + (address as usize & 0x3FFF),
+        };
+
+        rom.get(offset).copied().unwrap_or(0xFF)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::tests::cpu_running;
+
+    use super::*;
+
+    fn text(bytes: &[u8]) -> String {
+        let mut rom = vec![0; 0x8000];
+        rom[0x150..0x150 + bytes.len()].copy_from_slice(bytes);
+
+        let instructions = disassemble_rom(&rom, 1, 0x150, 0x150 + bytes.len() as u16);
+        assert_eq!(instructions.len(), 1);
+        instructions[0].to_string()
+    }
+
+    #[test]
+    fn formats_operands() {
+        assert_eq!(text(&[0x00]), "NOP");
+        assert_eq!(text(&[0x01, 0x34, 0x12]), "LD BC, $1234");
+        assert_eq!(text(&[0x22]), "LD [HL+], A");
+        assert_eq!(text(&[0x36, 0x42]), "LD [HL], $42");
+        assert_eq!(text(&[0x18, 0xFE]), "JR $0150");
+        assert_eq!(text(&[0x20, 0x05]), "JR NZ, $0157");
+        assert_eq!(text(&[0xC3, 0x50, 0x01]), "JP $0150");
+        assert_eq!(text(&[0xDF]), "RST $18");
+        assert_eq!(text(&[0xE0, 0x40]), "LDH [$FF40], A");
+        assert_eq!(text(&[0xF2]), "LDH A, [C]");
+        assert_eq!(text(&[0xE8, 0xFE]), "ADD SP, -2");
+        assert_eq!(text(&[0xF8, 0x02]), "LD HL, SP+2");
+        assert_eq!(text(&[0xFA, 0x00, 0xC0]), "LD A, [$C000]");
+        assert_eq!(text(&[0xCB, 0x7E]), "BIT 7, [HL]");
+        assert_eq!(text(&[0xCB, 0x37]), "SWAP A");
+        assert_eq!(text(&[0xD3]), "DB $D3");
+    }
+
+    #[test]
+    fn reads_the_selected_bank() {
+        let mut rom = vec![0; 0x10000];
+        rom[0x0100] = 0x3C;
+        rom[0x4100] = 0x04;
+        rom[0xC100] = 0x0C;
+
+        assert_eq!(
+            disassemble_rom(&rom, 3, 0x100, 0x101)[0].to_string(),
+            "INC A"
+        );
+        assert_eq!(
+            disassemble_rom(&rom, 1, 0x4100, 0x4101)[0].to_string(),
+            "INC B"
+        );
+        assert_eq!(
+            disassemble_rom(&rom, 3, 0x4100, 0x4101)[0].to_string(),
+            "INC C"
+        );
+        assert_eq!(
+            disassemble_rom(&rom, 7, 0x4100, 0x4101)[0].to_string(),
+            "RST $38"
+        );
+    }
+
+    #[test]
+    fn disassembles_ranges() {
+        // LD A, $01; INC A; JP $0150, split by the end of the range.
+        let mut rom = vec![0; 0x8000];
+        rom[0x150..0x156].copy_from_slice(&[0x3E, 0x01, 0x3C, 0xC3, 0x50, 0x01]);
+        let instructions = disassemble_rom(&rom, 1, 0x150, 0x154);
+
+        let addresses: Vec<_> = instructions.iter().map(|i| i.address).collect();
+        assert_eq!(addresses, [0x150, 0x152, 0x153]);
+        assert_eq!(instructions[2].bytes, [0xC3, 0x50, 0x01]);
+        assert_eq!(instructions[2].next_address(), 0x156);
+    }
+
+    #[test]
+    fn disassembles_the_live_memory_map() {
+        let mut cpu = cpu_running(&[]);
+        cpu.mmu.write_u8(0xC000, 0xAF);
+
+        let instructions = cpu.mmu.disassemble(0xC000, 0xC001);
+        assert_eq!(instructions[0].to_string(), "XOR A, A");
+    }
+
+    #[test]
+    fn illegal_opcodes() {
+        let illegal: Vec<_> = (0..=0xFF)
+            .filter(|&opcode| OPCODES[opcode as usize].is_none())
+            .collect();
+
+        assert_eq!(
+            illegal,
+            [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD]
+        );
+    }
+
+    fn operand_bytes(info: &OpcodeInfo) -> u8 {
+        info.operands
+            .iter()
+            .map(|operand| match operand {
+                Operand::Imm8
+                | Operand::HighAddress
+                | Operand::Relative
+                | Operand::Signed
+                | Operand::StackOffset => 1,
+                Operand::Imm16 | Operand::Address => 2,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn lengths_match_the_operands() {
+        for info in OPCODES.iter().flatten() {
+            assert_eq!(info.length, 1 + operand_bytes(info), "{}", info.mnemonic);
+        }
+
+        for info in CB_OPCODES.iter() {
+            assert_eq!(info.length, 2, "{}", info.mnemonic);
+            assert_eq!(operand_bytes(info), 0, "{}", info.mnemonic);
+        }
+    }
+
+    /// Run a single instruction, with the stack and HL pointing into WRAM
+    /// and flags set so that any condition is or isn't met.
+    fn cycles_taken(code: &[u8], condition: Option<&str>, met: bool) -> u8 {
+        let mut cpu = cpu_running(code);
+        let mut state = cpu.state();
+        state.sp = 0xDFF0;
+        state.hl = 0xC000;
+        match condition {
+            Some("NZ") => state.f.zero = !met,
+            Some("Z") => state.f.zero = met,
+            Some("NC") => state.f.carry = !met,
+            Some("C") => state.f.carry = met,
+            _ => {}
+        }
+        cpu.set_state(state);
+
+        cpu.step().expect("Should step")
+    }
+
+    fn check_cycles(code: &[u8], info: &OpcodeInfo) {
+        let condition = info.operands.iter().find_map(|operand| match operand {
+            Operand::Condition(condition) => Some(*condition),
+            _ => None,
+        });
+
+        assert_eq!(
+            cycles_taken(code, condition, true),
+            info.cycles,
+            "{code:02X?} {}",
+            info.mnemonic
+        );
+        if let Some(cycles) = info.cycles_not_taken {
+            assert_eq!(
+                cycles_taken(code, condition, false),
+                cycles,
+                "{code:02X?} {} not taken",
+                info.mnemonic
+            );
+        }
+    }
+
+    #[test]
+    fn cycles_match_the_cpu() {
+        for (opcode, info) in OPCODES.iter().enumerate() {
+            match info {
+                Some(info) if opcode != 0xCB => check_cycles(&[opcode as u8, 0x00, 0xC0], info),
+                _ => {}
+            }
+        }
+
+        for (opcode, info) in CB_OPCODES.iter().enumerate() {
+            check_cycles(&[0xCB, opcode as u8], info);
+        }
+    }
+}
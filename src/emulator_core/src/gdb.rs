@@ -0,0 +1,767 @@
+//! A server for the GDB remote serial protocol, so that gdb (with the
+//! target description sent by the server) or other clients such as IDA can
+//! debug the running emulator over TCP.
+//!
+//! Registers are sent as the 16-bit pairs AF, BC, DE, HL, SP and PC, each
+//! little endian. Breakpoints (Z0 and Z1) and write, read and access
+//! watchpoints (Z2, Z3 and Z4) map onto the [Debugger](crate::Debugger).
+use std::collections::HashMap;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::{
+    Access, BreakReason, Breakpoint, BreakpointId, CpuState, EmulatorHandle, Flags, RunMode,
+    Watchpoint, WatchpointId,
+};
+
+/// How long reads wait before checking whether the server should stop, and
+/// how often a running emulator checks for the client interrupting it.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Describes the registers, so gdb doesn't assume the layout of another
+/// architecture.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gnu.gdb.sm83.core">
+    <reg name="af" bitsize="16" type="uint16" regnum="0"/>
+    <reg name="bc" bitsize="16" type="uint16"/>
+    <reg name="de" bitsize="16" type="uint16"/>
+    <reg name="hl" bitsize="16" type="uint16"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+/// Accepts debuggers on a TCP listener, one at a time. The emulator is
+/// paused while a debugger is attached and stopped, and carries on running
+/// when it detaches.
+pub struct GdbServer {
+    listener: TcpListener,
+}
+
+impl GdbServer {
+    pub fn new(listener: TcpListener) -> io::Result<GdbServer> {
+        listener.set_nonblocking(true)?;
+
+        Ok(GdbServer { listener })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serve debuggers until `stop` is set, or the emulator thread is no
+    /// longer running. A debugger which drops its connection is detached,
+    /// leaving the server waiting for the next one.
+    pub fn run(&self, handle: &EmulatorHandle, stop: &AtomicBool) -> io::Result<()> {
+        while !stop.load(Ordering::Relaxed) && handle.is_running() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    let mut session = Session::new(handle, stream, stop)?;
+                    // Errors only end the session, the next debugger can
+                    // still connect.
+                    let _ = session.run();
+                    session.detach();
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(POLL_INTERVAL)
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// What to do after replying to a packet.
+enum Next {
+    Reply(String),
+    Resume(RunMode),
+    /// End the session, replying first unless the client has killed it.
+    Detach {
+        reply: bool,
+    },
+}
+
+/// A connection to a single debugger.
+struct Session<'a> {
+    handle: &'a EmulatorHandle,
+    stop: &'a AtomicBool,
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    /// Packets aren't acknowledged once the client has asked for
+    /// QStartNoAckMode.
+    no_ack: bool,
+    /// The client understands the swbreak stop reason.
+    swbreak: bool,
+    /// The reply to `?`, describing why the emulator last stopped.
+    last_stop: String,
+    breakpoints: HashMap<u16, BreakpointId>,
+    /// Watchpoints by their Z packet type, address and length. Access
+    /// watchpoints (Z4) are a read and a write watchpoint.
+    watchpoints: HashMap<(u8, u16, u16), Vec<WatchpointId>>,
+}
+
+impl<'a> Session<'a> {
+    fn new(
+        handle: &'a EmulatorHandle,
+        stream: TcpStream,
+        stop: &'a AtomicBool,
+    ) -> io::Result<Self> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        stream.set_nodelay(true)?;
+
+        Ok(Session {
+            handle,
+            stop,
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            no_ack: false,
+            swbreak: false,
+            last_stop: "S05".to_string(),
+            breakpoints: HashMap::new(),
+            watchpoints: HashMap::new(),
+        })
+    }
+
+    fn run(&mut self) -> io::Result<()> {
+        // Stop the emulator, and forget any breaks from before the debugger
+        // attached.
+        self.handle.pause();
+        self.handle.state().ok_or_else(emulator_stopped)?;
+        while self.handle.wait_for_break(Some(Duration::ZERO)).is_some() {}
+
+        while let Some(packet) = self.read_packet()? {
+            let reply = match self.handle_packet(&packet) {
+                Next::Reply(reply) => reply,
+                Next::Resume(mode) => self.resume(mode)?,
+                Next::Detach { reply } => {
+                    if reply {
+                        self.send_packet("OK")?;
+                    }
+                    return Ok(());
+                }
+            };
+            self.send_packet(&reply)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove everything added by the debugger, and carry on running.
+    fn detach(&mut self) {
+        for (_, id) in self.breakpoints.drain() {
+            self.handle.remove_breakpoint(id);
+        }
+        for (_, ids) in self.watchpoints.drain() {
+            for id in ids {
+                self.handle.remove_watchpoint(id);
+            }
+        }
+
+        self.handle.resume();
+    }
+
+    fn handle_packet(&mut self, packet: &str) -> Next {
+        // Split after the first character rather than byte, as invalid
+        // UTF-8 in the packet has been replaced with a multi-byte character.
+        let command_len = packet.chars().next().map_or(0, char::len_utf8);
+        let (command, args) = packet.split_at(command_len);
+
+        let reply = match command {
+            "?" => Some(self.last_stop.clone()),
+            "g" => self.read_registers(),
+            "G" => self.write_registers(args),
+            "p" => self.read_register(args),
+            "P" => self.write_register(args),
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "Z" => self.insert(args),
+            "z" => self.remove(args),
+            "c" | "C" | "s" | "S" => {
+                // c and s can give an address to carry on from, C and S a
+                // signal to ignore.
+                let address = args.split(';').next().unwrap_or_default();
+                if matches!(command, "c" | "s") && !address.is_empty() {
+                    match u16::from_str_radix(address, 16) {
+                        Ok(address) => self.set_state(move |state| state.pc = address),
+                        Err(_) => return Next::Reply("E01".to_string()),
+                    };
+                }
+
+                return Next::Resume(match command {
+                    "c" | "C" => RunMode::Continue,
+                    _ => RunMode::StepInto,
+                });
+            }
+            "D" => return Next::Detach { reply: true },
+            "k" => return Next::Detach { reply: false },
+            "H" | "T" => Some("OK".to_string()),
+            _ => return Next::Reply(self.query(packet)),
+        };
+
+        Next::Reply(reply.unwrap_or_else(|| "E01".to_string()))
+    }
+
+    fn query(&mut self, packet: &str) -> String {
+        if let Some(features) = packet.strip_prefix("qSupported") {
+            self.swbreak = features.contains("swbreak+");
+            return "PacketSize=1000;qXfer:features:read+;QStartNoAckMode+;swbreak+".to_string();
+        }
+
+        if let Some(args) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            return xfer(TARGET_XML, args).unwrap_or_else(|| "E01".to_string());
+        }
+
+        match packet {
+            "QStartNoAckMode" => {
+                self.no_ack = true;
+                "OK".to_string()
+            }
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            // Anything else is unsupported.
+            _ => String::new(),
+        }
+    }
+
+    fn read_registers(&self) -> Option<String> {
+        let state = self.handle.state()?;
+
+        Some(
+            registers(&state)
+                .iter()
+                .map(|&value| hex_u16(value))
+                .collect(),
+        )
+    }
+
+    fn write_registers(&self, args: &str) -> Option<String> {
+        let bytes = from_hex(args)?;
+        if bytes.len() != 12 {
+            return None;
+        }
+
+        self.set_state(move |state| {
+            for (number, pair) in bytes.chunks(2).enumerate() {
+                set_register(state, number, u16::from_le_bytes([pair[0], pair[1]]));
+            }
+        })
+    }
+
+    fn read_register(&self, args: &str) -> Option<String> {
+        let number = usize::from_str_radix(args, 16).ok()?;
+        let state = self.handle.state()?;
+
+        registers(&state).get(number).map(|&value| hex_u16(value))
+    }
+
+    fn write_register(&self, args: &str) -> Option<String> {
+        let (number, value) = args.split_once('=')?;
+        let number = usize::from_str_radix(number, 16).ok()?;
+        let bytes = from_hex(value)?;
+        if number >= 6 || bytes.len() != 2 {
+            return None;
+        }
+
+        self.set_state(move |state| {
+            set_register(state, number, u16::from_le_bytes([bytes[0], bytes[1]]))
+        })
+    }
+
+    fn set_state(&self, f: impl FnOnce(&mut CpuState) + Send + 'static) -> Option<String> {
+        self.handle.with_cpu(move |cpu| {
+            let mut state = cpu.state();
+            f(&mut state);
+            cpu.set_state(state);
+        })?;
+
+        Some("OK".to_string())
+    }
+
+    fn read_memory(&self, args: &str) -> Option<String> {
+        let (address, length) = address_and_length(args)?;
+        let bytes = self.handle.read_memory(address, length as usize)?;
+
+        Some(to_hex(&bytes))
+    }
+
+    fn write_memory(&self, args: &str) -> Option<String> {
+        let (range, data) = args.split_once(':')?;
+        let (address, length) = address_and_length(range)?;
+        let bytes = from_hex(data)?;
+        if bytes.len() != length as usize {
+            return None;
+        }
+
+        self.handle.write_memory(address, bytes)?;
+        Some("OK".to_string())
+    }
+
+    fn insert(&mut self, args: &str) -> Option<String> {
+        let (kind, address, length) = breakpoint_args(args)?;
+
+        match kind {
+            // Software and hardware breakpoints are the same here.
+            0 | 1 => {
+                if !self.breakpoints.contains_key(&address) {
+                    let id = self.handle.add_breakpoint(Breakpoint::new(address))?;
+                    self.breakpoints.insert(address, id);
+                }
+            }
+            2..=4 => {
+                let range = address..=address.saturating_add(length.max(1) - 1);
+                let accesses: &[Access] = match kind {
+                    2 => &[Access::Write],
+                    3 => &[Access::Read],
+                    _ => &[Access::Read, Access::Write],
+                };
+                let mut ids = Vec::new();
+                for &access in accesses {
+                    let watchpoint = Watchpoint {
+                        range: range.clone(),
+                        access,
+                    };
+                    ids.push(self.handle.add_watchpoint(watchpoint)?);
+                }
+                self.watchpoints
+                    .entry((kind, address, length))
+                    .or_default()
+                    .extend(ids);
+            }
+            _ => return Some(String::new()),
+        }
+
+        Some("OK".to_string())
+    }
+
+    fn remove(&mut self, args: &str) -> Option<String> {
+        let (kind, address, length) = breakpoint_args(args)?;
+
+        match kind {
+            0 | 1 => {
+                if let Some(id) = self.breakpoints.remove(&address) {
+                    self.handle.remove_breakpoint(id)?;
+                }
+            }
+            2..=4 => {
+                for id in self
+                    .watchpoints
+                    .remove(&(kind, address, length))
+                    .unwrap_or_default()
+                {
+                    self.handle.remove_watchpoint(id)?;
+                }
+            }
+            _ => return Some(String::new()),
+        }
+
+        Some("OK".to_string())
+    }
+
+    /// Let the emulator run until it stops by itself, or the client
+    /// interrupts it, returning the stop reply.
+    fn resume(&mut self, mode: RunMode) -> io::Result<String> {
+        self.handle.run(mode);
+
+        loop {
+            if let Some((_, reason)) = self.handle.wait_for_break(Some(POLL_INTERVAL)) {
+                self.last_stop = self.stop_reply(reason);
+                return Ok(self.last_stop.clone());
+            }
+
+            if !self.handle.is_running() {
+                return Ok("W00".to_string());
+            }
+
+            // Ctrl-C from the client. Anything else while running is
+            // ignored.
+            if self.poll_byte()? == Some(0x03) {
+                self.handle.pause();
+            }
+        }
+    }
+
+    fn stop_reply(&self, reason: BreakReason) -> String {
+        match reason {
+            BreakReason::Breakpoint(_) if self.swbreak => "T05swbreak:;".to_string(),
+            BreakReason::Watchpoint {
+                id,
+                address,
+                access,
+                ..
+            } => {
+                let access_watchpoint = self
+                    .watchpoints
+                    .iter()
+                    .any(|((kind, _, _), ids)| *kind == 4 && ids.contains(&id));
+                let kind = match access {
+                    _ if access_watchpoint => "awatch",
+                    Access::Write => "watch",
+                    Access::Read => "rwatch",
+                    Access::Execute => return "S05".to_string(),
+                };
+                format!("T05{kind}:{address:x};")
+            }
+            BreakReason::IllegalOpcode(_) => "S04".to_string(),
+            BreakReason::Paused => "S02".to_string(),
+            _ => "S05".to_string(),
+        }
+    }
+
+    /// Read the next packet, acknowledging it. Returns None once the client
+    /// has disconnected, or the server is stopping.
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            // Skip acknowledgements, and Ctrl-C while already stopped.
+            loop {
+                match self.read_byte()? {
+                    Some(b'$') => break,
+                    Some(_) => {}
+                    None => return Ok(None),
+                }
+            }
+
+            let mut data = Vec::new();
+            let mut escaped = false;
+            let mut sum: u8 = 0;
+            loop {
+                let Some(byte) = self.read_byte()? else {
+                    return Ok(None);
+                };
+                if byte == b'#' && !escaped {
+                    break;
+                }
+                sum = sum.wrapping_add(byte);
+
+                match byte {
+                    b'}' if !escaped => escaped = true,
+                    _ if escaped => {
+                        data.push(byte ^ 0x20);
+                        escaped = false;
+                    }
+                    _ => data.push(byte),
+                }
+            }
+
+            let (Some(high), Some(low)) = (self.read_byte()?, self.read_byte()?) else {
+                return Ok(None);
+            };
+            let checksum = std::str::from_utf8(&[high, low])
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+
+            if self.no_ack {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+            if checksum == Some(sum) {
+                self.writer.write_all(b"+")?;
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+            self.writer.write_all(b"-")?;
+        }
+    }
+
+    /// Send a packet, resending it until the client acknowledges it.
+    fn send_packet(&mut self, data: &str) -> io::Result<()> {
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.push(b'$');
+        for &byte in data.as_bytes() {
+            match byte {
+                b'$' | b'#' | b'}' | b'*' => packet.extend([b'}', byte ^ 0x20]),
+                _ => packet.push(byte),
+            }
+        }
+        let sum = packet[1..]
+            .iter()
+            .fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        packet.extend(format!("#{sum:02x}").bytes());
+
+        loop {
+            self.writer.write_all(&packet)?;
+            if self.no_ack {
+                return Ok(());
+            }
+
+            match self.read_byte()? {
+                Some(b'+') => return Ok(()),
+                Some(_) => {}
+                None => return Err(io::ErrorKind::UnexpectedEof.into()),
+            }
+        }
+    }
+
+    /// Read a byte, waiting for one to arrive. Returns None once the client
+    /// has disconnected, or the server is stopping.
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        loop {
+            match self.poll_byte() {
+                Ok(Some(byte)) => return Ok(Some(byte)),
+                Ok(None) if !self.stop.load(Ordering::Relaxed) => {}
+                Ok(None) => return Ok(None),
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Read a byte if one arrives within the poll interval. Fails with
+    /// UnexpectedEof once the client has disconnected.
+    fn poll_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+
+        match self.reader.read(&mut byte) {
+            Ok(0) => Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(_) => Ok(Some(byte[0])),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock
+                        | io::ErrorKind::TimedOut
+                        | io::ErrorKind::Interrupted
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn emulator_stopped() -> io::Error {
+    io::Error::other("The emulator is no longer running")
+}
+
+fn registers(state: &CpuState) -> [u16; 6] {
+    [state.af(), state.bc, state.de, state.hl, state.sp, state.pc]
+}
+
+fn set_register(state: &mut CpuState, number: usize, value: u16) {
+    match number {
+        0 => {
+            let [a, f] = value.to_be_bytes();
+            state.a = a;
+            state.f = Flags::from_bits(f);
+        }
+        1 => state.bc = value,
+        2 => state.de = value,
+        3 => state.hl = value,
+        4 => state.sp = value,
+        5 => state.pc = value,
+        _ => {}
+    }
+}
+
+/// Parse `type,address,kind` from a Z or z packet, ignoring any
+/// conditions which follow.
+fn breakpoint_args(args: &str) -> Option<(u8, u16, u16)> {
+    let args = args.split(';').next()?;
+    let mut parts = args.split(',');
+    let kind = parts.next()?.parse().ok()?;
+    let address = u16::from_str_radix(parts.next()?, 16).ok()?;
+    let length = u16::from_str_radix(parts.next()?, 16).ok()?;
+
+    Some((kind, address, length))
+}
+
+fn address_and_length(args: &str) -> Option<(u16, u16)> {
+    let (address, length) = args.split_once(',')?;
+
+    Some((
+        u16::from_str_radix(address, 16).ok()?,
+        u16::from_str_radix(length, 16).ok()?,
+    ))
+}
+
+/// Reply to a qXfer read of `offset,length` from `document`.
+fn xfer(document: &str, args: &str) -> Option<String> {
+    let (offset, length) = args.split_once(',')?;
+    let offset = usize::from_str_radix(offset, 16).ok()?;
+    let length = usize::from_str_radix(length, 16).ok()?;
+
+    let rest = document.get(offset.min(document.len())..)?;
+    if rest.len() <= length {
+        Some(format!("l{rest}"))
+    } else {
+        Some(format!("m{}", &rest[..length]))
+    }
+}
+
+fn hex_u16(value: u16) -> String {
+    to_hex(&value.to_le_bytes())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::tests::cpu_running;
+    use crate::Emulator;
+
+    use super::*;
+
+    /// A client speaking the protocol, with acknowledgements.
+    struct Client {
+        stream: TcpStream,
+    }
+
+    impl Client {
+        fn send(&mut self, data: &str) {
+            self.send_bytes(data.as_bytes());
+        }
+
+        fn send_bytes(&mut self, data: &[u8]) {
+            let sum = data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+            self.stream.write_all(b"$").unwrap();
+            self.stream.write_all(data).unwrap();
+            write!(self.stream, "#{sum:02x}").unwrap();
+            assert_eq!(self.read_byte(), b'+');
+        }
+
+        fn receive(&mut self) -> String {
+            while self.read_byte() != b'$' {}
+
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte() {
+                    b'#' => break,
+                    byte => data.push(byte),
+                }
+            }
+            self.read_byte();
+            self.read_byte();
+            self.stream.write_all(b"+").unwrap();
+
+            String::from_utf8(data).unwrap()
+        }
+
+        fn exchange(&mut self, data: &str) -> String {
+            self.send(data);
+            self.receive()
+        }
+
+        fn read_byte(&mut self) -> u8 {
+            let mut byte = [0];
+            self.stream.read_exact(&mut byte).unwrap();
+            byte[0]
+        }
+    }
+
+    /// Serve a debugger for an emulator running a loop of INC A,
+    /// LD [$C000], A, JR -6 at 0x0100, and connect a client to it.
+    fn with_client(test: impl FnOnce(&mut Client)) {
+        let cpu = cpu_running(&[0x3C, 0xEA, 0x00, 0xC0, 0x18, 0xFA]);
+        let handle = Emulator::new(cpu).spawn();
+
+        let server = GdbServer::new(TcpListener::bind("127.0.0.1:0").unwrap()).unwrap();
+        let address = server.local_addr().unwrap();
+        let stop = AtomicBool::new(false);
+
+        std::thread::scope(|scope| {
+            let server = scope.spawn(|| server.run(&handle, &stop));
+
+            let stream = TcpStream::connect(address).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(10)))
+                .unwrap();
+            test(&mut Client { stream });
+
+            stop.store(true, Ordering::Relaxed);
+            server.join().unwrap().unwrap();
+        });
+
+        assert_eq!(handle.shutdown(), Ok(()));
+    }
+
+    #[test]
+    fn reads_and_writes_registers_and_memory() {
+        with_client(|client| {
+            assert!(client.exchange("qSupported:swbreak+").contains("swbreak+"));
+            assert!(client
+                .exchange("qXfer:features:read:target.xml:0,1000")
+                .starts_with("l<?xml"));
+            assert_eq!(client.exchange("?"), "S05");
+
+            assert_eq!(client.exchange("P5=0001"), "OK");
+            assert_eq!(client.exchange("p5"), "0001");
+            assert_eq!(client.exchange("P0=b012"), "OK");
+            let registers = client.exchange("g");
+            assert_eq!(registers.len(), 24);
+            assert!(registers.starts_with("b012"));
+            assert!(registers.ends_with("0001"));
+
+            assert_eq!(client.exchange("Mc001,2:abcd"), "OK");
+            assert_eq!(client.exchange("mc001,2"), "abcd");
+            assert_eq!(client.exchange("m100,3"), "3cea00");
+            assert_eq!(client.exchange("mzz,1"), "E01");
+
+            assert_eq!(client.exchange("D"), "OK");
+        });
+    }
+
+    #[test]
+    fn stops_at_breakpoints_and_watchpoints() {
+        with_client(|client| {
+            client.exchange("qSupported:swbreak+");
+            client.exchange("P5=0001");
+
+            assert_eq!(client.exchange("Z0,101,1"), "OK");
+            assert_eq!(client.exchange("c"), "T05swbreak:;");
+            assert_eq!(client.exchange("p5"), "0101");
+            assert_eq!(client.exchange("z0,101,1"), "OK");
+
+            assert_eq!(client.exchange("s"), "S05");
+            assert_eq!(client.exchange("p5"), "0401");
+
+            assert_eq!(client.exchange("Z2,c000,1"), "OK");
+            assert_eq!(client.exchange("c"), "T05watch:c000;");
+            assert_eq!(client.exchange("z2,c000,1"), "OK");
+            assert_eq!(client.exchange("Z3,c000,1"), "OK");
+            assert_eq!(client.exchange("Z4,c000,1"), "OK");
+            assert_eq!(client.exchange("c"), "T05awatch:c000;");
+        });
+    }
+
+    #[test]
+    fn ignores_unknown_packets() {
+        with_client(|client| {
+            assert_eq!(client.exchange("qUnknown"), "");
+            // A non-ASCII command byte.
+            client.send_bytes(&[0xFF, b'1']);
+            assert_eq!(client.receive(), "");
+            assert_eq!(client.exchange("?"), "S05");
+        });
+    }
+
+    #[test]
+    fn interrupts_a_running_emulator() {
+        with_client(|client| {
+            client.send("c");
+            client.stream.write_all(&[0x03]).unwrap();
+
+            assert_eq!(client.receive(), "S02");
+            assert_eq!(client.exchange("?"), "S02");
+        });
+    }
+}
@@ -1,19 +1,86 @@
 mod cartridge;
 mod cpu;
+mod disasm;
+mod gdb;
 mod mmu;
+mod png;
+mod post_processing;
+mod recorder;
 mod registers;
+mod scaler;
+#[cfg(test)]
+mod test_writers;
 
 pub use cartridge::create_cartridge;
 pub use cartridge::Cartridge;
 pub use cartridge::CartridgePersistence;
+pub use cartridge::Header;
+pub use cpu::Access;
+pub use cpu::BreakReason;
+pub use cpu::Breakpoint;
+pub use cpu::BreakpointId;
+pub use cpu::Comparison;
+pub use cpu::Condition;
+pub use cpu::CpuState;
+pub use cpu::Debugger;
+pub use cpu::Flags;
+pub use cpu::IllegalOpcodePolicy;
+pub use cpu::MemoryTiming;
+pub use cpu::Register;
+pub use cpu::RunMode;
+pub use cpu::StepError;
+pub use cpu::TraceOptions;
+pub use cpu::Watchpoint;
+pub use cpu::WatchpointId;
 pub use cpu::CPU;
+pub use disasm::decode;
+pub use disasm::disassemble;
+pub use disasm::disassemble_rom;
+pub use disasm::opcode_info;
+pub use disasm::FlagEffect;
+pub use disasm::FlagEffects;
+pub use disasm::Instruction;
+pub use disasm::OpcodeInfo;
+pub use disasm::Operand;
+pub use disasm::CB_OPCODES;
+pub use disasm::OPCODES;
+pub use gdb::GdbServer;
+pub use mmu::BGMapSelection;
 pub use mmu::Button;
 pub use mmu::Color;
+pub use mmu::DebugImage;
+pub use mmu::DebugViews;
+pub use mmu::Frame;
+pub use mmu::FrameBuffer;
+pub use mmu::IndexedPixel;
+pub use mmu::Interrupt;
 pub use mmu::Joypad;
+pub use mmu::Layer;
+pub use mmu::OamEntry;
+pub use mmu::Overlay;
+pub use mmu::Palette;
+pub use mmu::PaletteNumber;
+pub use mmu::Palettes;
+pub use mmu::Pixel;
+pub use mmu::PixelSource;
 pub use mmu::Renderer;
+pub use mmu::Screenshot;
+pub use mmu::SpriteFlags;
 pub use mmu::MMU;
 pub use mmu::PPU;
+pub use mmu::SGB_HEIGHT;
+pub use mmu::SGB_WIDTH;
+pub use post_processing::Ghosting;
+pub use post_processing::PixelGrid;
+pub use post_processing::ToneCurve;
+pub use recorder::Recorder;
+pub use recorder::RecordingFormat;
+pub use scaler::Filter;
+pub use scaler::Scaler;
 
+use std::io;
+use std::sync::mpsc;
+use std::sync::Mutex;
 use std::time::Duration;
 use std::time::Instant;
 
@@ -30,16 +97,195 @@ pub struct Emulator {
 /// Should always be used, if the program is aborted while the emulator thread
 /// is still running, RAM data will not be saved and you will lose your saves.
 pub struct EmulatorHandle {
-    shutdown_sender: std::sync::mpsc::Sender<()>,
-    join_handle: std::thread::JoinHandle<()>,
+    command_sender: mpsc::Sender<Command>,
+    /// Where and why the emulator thread stopped, each time it's paused.
+    breaks: Mutex<mpsc::Receiver<(u16, BreakReason)>>,
+    join_handle: std::thread::JoinHandle<Result<(), StepError>>,
+}
+
+/// Messages sent from the [EmulatorHandle] to the emulator thread. Handled
+/// between CPU steps.
+enum Command {
+    Shutdown,
+    Pause,
+    Run(RunMode),
+    WithCpu(Box<dyn FnOnce(&mut CPU) + Send>),
+    DebugViews(mpsc::Sender<DebugViews>),
+    Screenshot(mpsc::Sender<Screenshot>),
+    SetLayer(Layer, bool),
+    SetOverlay(Overlay, bool),
+    SetPalette(Palette),
+    StartTrace(TraceOptions, Box<dyn io::Write + Send>),
+    StopTrace(mpsc::Sender<io::Result<()>>),
 }
 
 impl EmulatorHandle {
     /// Signal shutdown of the emulator thread. Calls join handle and waits
-    /// for the thread to gracefully terminate.
-    pub fn shutdown(self) {
-        let _ = self.shutdown_sender.send(());
-        let _ = self.join_handle.join();
+    /// for the thread to gracefully terminate. Returns the error which
+    /// stopped the emulator, if it had already stopped on its own. If the
+    /// emulator thread panicked, the panic carries on in this thread.
+    pub fn shutdown(self) -> Result<(), StepError> {
+        let _ = self.command_sender.send(Command::Shutdown);
+
+        match self.join_handle.join() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    /// Carry on running after the emulator has stopped at a break, see
+    /// [IllegalOpcodePolicy::Break] and [Debugger].
+    pub fn resume(&self) {
+        self.run(RunMode::Continue);
+    }
+
+    /// Carry on running until the emulator stops as chosen by `mode`, or at
+    /// a breakpoint or watchpoint.
+    pub fn run(&self, mode: RunMode) {
+        let _ = self.command_sender.send(Command::Run(mode));
+    }
+
+    /// Stop the emulator between instructions, as if it had stopped at a
+    /// break, until it's resumed. Does nothing if it's already stopped.
+    pub fn pause(&self) {
+        let _ = self.command_sender.send(Command::Pause);
+    }
+
+    /// Wait for the emulator to stop at a break, or to be paused, returning
+    /// the address it stopped at and why. Waits forever without a
+    /// `timeout`. Returns None if it timed out, or if the emulator thread is
+    /// no longer running.
+    pub fn wait_for_break(&self, timeout: Option<Duration>) -> Option<(u16, BreakReason)> {
+        let breaks = self.breaks.lock().expect("Should lock breaks");
+
+        match timeout {
+            Some(timeout) => breaks.recv_timeout(timeout).ok(),
+            None => breaks.recv().ok(),
+        }
+    }
+
+    /// Is the emulator thread still running, whether or not it's paused.
+    pub fn is_running(&self) -> bool {
+        !self.join_handle.is_finished()
+    }
+
+    /// Run `f` on the emulator thread between instructions, returning what
+    /// it returns. Blocks until the emulator thread has handled the
+    /// request. Returns None if the emulator thread is no longer running.
+    pub fn with_cpu<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut CPU) -> T + Send + 'static,
+    ) -> Option<T> {
+        let (sender, receiver) = mpsc::channel();
+        let command = Command::WithCpu(Box::new(move |cpu| {
+            let _ = sender.send(f(cpu));
+        }));
+
+        self.command_sender.send(command).ok()?;
+
+        receiver.recv().ok()
+    }
+
+    /// Take a copy of the CPU's registers and state, see [CPU::state].
+    pub fn state(&self) -> Option<CpuState> {
+        self.with_cpu(|cpu| cpu.state())
+    }
+
+    /// Read `len` bytes of memory from `start`, wrapping around at the
+    /// end, see [CPU::read_memory].
+    pub fn read_memory(&self, start: u16, len: usize) -> Option<Vec<u8>> {
+        self.with_cpu(move |cpu| {
+            (0..len)
+                .map(|offset| cpu.read_memory(start.wrapping_add(offset as u16)))
+                .collect()
+        })
+    }
+
+    /// Write `bytes` to memory from `start`, see [CPU::write_memory].
+    pub fn write_memory(&self, start: u16, bytes: Vec<u8>) -> Option<()> {
+        self.with_cpu(move |cpu| {
+            for (offset, value) in bytes.into_iter().enumerate() {
+                cpu.write_memory(start.wrapping_add(offset as u16), value);
+            }
+        })
+    }
+
+    pub fn add_breakpoint(&self, breakpoint: Breakpoint) -> Option<BreakpointId> {
+        self.with_cpu(|cpu| cpu.debugger_mut().add_breakpoint(breakpoint))
+    }
+
+    pub fn remove_breakpoint(&self, id: BreakpointId) -> Option<bool> {
+        self.with_cpu(move |cpu| cpu.debugger_mut().remove_breakpoint(id))
+    }
+
+    pub fn add_watchpoint(&self, watchpoint: Watchpoint) -> Option<WatchpointId> {
+        self.with_cpu(|cpu| cpu.debugger_mut().add_watchpoint(watchpoint))
+    }
+
+    pub fn remove_watchpoint(&self, id: WatchpointId) -> Option<bool> {
+        self.with_cpu(move |cpu| cpu.debugger_mut().remove_watchpoint(id))
+    }
+
+    /// Capture the PPU debugging views (tile data, background maps, OAM and
+    /// palettes) from the running emulator. Blocks until the emulator thread
+    /// has handled the request. Returns None if the emulator thread is no
+    /// longer running.
+    pub fn debug_views(&self) -> Option<DebugViews> {
+        let (sender, receiver) = mpsc::channel();
+
+        self.command_sender.send(Command::DebugViews(sender)).ok()?;
+
+        receiver.recv().ok()
+    }
+
+    /// Capture the last frame completed by the PPU. Blocks until the
+    /// emulator thread has handled the request. Returns None if the
+    /// emulator thread is no longer running.
+    pub fn screenshot(&self) -> Option<Screenshot> {
+        let (sender, receiver) = mpsc::channel();
+
+        self.command_sender.send(Command::Screenshot(sender)).ok()?;
+
+        receiver.recv().ok()
+    }
+
+    /// Switch drawing of a PPU layer on or off, from the next scanline
+    /// onwards. Only affects the output, not the emulated hardware.
+    pub fn set_layer_enabled(&self, layer: Layer, enabled: bool) {
+        let _ = self.command_sender.send(Command::SetLayer(layer, enabled));
+    }
+
+    /// Switch drawing of a debugging overlay on or off, from the next frame
+    /// onwards.
+    pub fn set_overlay_enabled(&self, overlay: Overlay, enabled: bool) {
+        let _ = self
+            .command_sender
+            .send(Command::SetOverlay(overlay, enabled));
+    }
+
+    /// Change the colours the frame is drawn with, from the next pixel
+    /// drawn.
+    pub fn set_palette(&self, palette: Palette) {
+        let _ = self.command_sender.send(Command::SetPalette(palette));
+    }
+
+    /// Start tracing instructions to `writer`, see [CPU::start_trace]. Any
+    /// trace already running is stopped, without reporting its errors.
+    pub fn start_trace(&self, options: TraceOptions, writer: impl io::Write + Send + 'static) {
+        let _ = self
+            .command_sender
+            .send(Command::StartTrace(options, Box::new(writer)));
+    }
+
+    /// Stop tracing, flushing the output. Blocks until the emulator thread
+    /// has handled the request. Returns None if the emulator thread is no
+    /// longer running.
+    pub fn stop_trace(&self) -> Option<io::Result<()>> {
+        let (sender, receiver) = mpsc::channel();
+
+        self.command_sender.send(Command::StopTrace(sender)).ok()?;
+
+        receiver.recv().ok()
     }
 }
 
@@ -52,29 +298,110 @@ impl Emulator {
     /// Spawns a new thread that runs the emulator. Returns handle which
     /// can be used to shutdown the emulator.
     pub fn spawn(mut self) -> EmulatorHandle {
-        let (shutdown_sender, shutdown_receiver) = std::sync::mpsc::channel::<()>();
+        let (command_sender, command_receiver) = mpsc::channel::<Command>();
+        let (break_sender, breaks) = mpsc::channel();
 
         let join_handle = std::thread::spawn(move || {
-            while shutdown_receiver.try_recv().is_err() {
-                self.step();
+            // Stopped at a break, waiting to be resumed.
+            let mut paused = false;
+
+            loop {
+                let command = if paused {
+                    match command_receiver.recv() {
+                        Ok(command) => Some(command),
+                        // Nothing is left to resume the emulator.
+                        Err(_) => break,
+                    }
+                } else {
+                    command_receiver.try_recv().ok()
+                };
+
+                match command {
+                    Some(Command::Shutdown) => break,
+                    Some(Command::Pause) if !paused => {
+                        paused = true;
+                        let pc = self.cpu.state().pc;
+                        let _ = break_sender.send((pc, BreakReason::Paused));
+                    }
+                    Some(Command::Pause) => {}
+                    Some(Command::Run(mode)) => {
+                        self.cpu.set_run_mode(mode);
+                        paused = false;
+                    }
+                    Some(Command::WithCpu(f)) => f(&mut self.cpu),
+                    Some(Command::DebugViews(reply)) => {
+                        let _ = reply.send(self.cpu.mmu.ppu.debug_views());
+                    }
+                    Some(Command::Screenshot(reply)) => {
+                        let _ = reply.send(self.cpu.mmu.ppu.screenshot());
+                    }
+                    Some(Command::SetLayer(layer, enabled)) => {
+                        self.cpu.mmu.ppu.set_layer_enabled(layer, enabled)
+                    }
+                    Some(Command::SetOverlay(overlay, enabled)) => {
+                        self.cpu.mmu.ppu.set_overlay_enabled(overlay, enabled)
+                    }
+                    Some(Command::SetPalette(palette)) => self.cpu.mmu.ppu.set_palette(palette),
+                    Some(Command::StartTrace(options, writer)) => {
+                        let _ = self.cpu.start_trace(options, writer);
+                    }
+                    Some(Command::StopTrace(reply)) => {
+                        let _ = reply.send(self.cpu.stop_trace());
+                    }
+                    None => {}
+                }
+
+                if paused {
+                    continue;
+                }
+
+                match self.step() {
+                    Ok(()) => {}
+                    Err(StepError::Break { address, reason }) => {
+                        paused = true;
+                        let _ = break_sender.send((address, reason));
+                    }
+                    Err(err) => return Err(err),
+                }
             }
 
-            // Before exiting, save state.
-            self.save();
+            Ok(())
         });
 
         EmulatorHandle {
-            shutdown_sender,
+            command_sender,
+            breaks: Mutex::new(breaks),
             join_handle,
         }
     }
 
-    fn step(&mut self) {
-        let cycles = self.cpu.step();
+    /// Runs the emulator on the current thread as fast as possible, without
+    /// limiting, for the given number of frames worth of cycles. Saves
+    /// cartridge RAM at the end, as when the emulator thread shuts down.
+    /// There is no debugger to stop at, so breaks are returned as errors.
+    pub fn run_frames(mut self, frames: u64) -> Result<(), StepError> {
+        let target = frames * LCD_FRAME_CYCLES;
+        let mut cycles: u64 = 0;
+
+        while cycles < target {
+            cycles += self.cpu.step()? as u64;
+        }
+
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<(), StepError> {
+        let cycles = self.cpu.step()?;
         self.limiter.step(cycles);
+
+        Ok(())
     }
+}
 
-    fn save(&mut self) {
+/// Cartridge RAM is saved whenever the emulator is dropped, however it
+/// stops: shut down, on an error or by panicking.
+impl Drop for Emulator {
+    fn drop(&mut self) {
         self.cpu.mmu.save();
     }
 }
@@ -93,6 +420,8 @@ struct Limiter {
 const FPS: u64 = 60;
 const CYCLES_PER_SECOND: u64 = 4194304 / 4; // Hz
 const CYCLES_PER_FRAME: u64 = CYCLES_PER_SECOND / FPS;
+/// M-cycles the PPU takes to draw a frame, 154 lines of 114 M-cycles.
+const LCD_FRAME_CYCLES: u64 = 154 * 114;
 const TARGET_FRAME_DURATION: Duration = Duration::from_millis(1000 / FPS);
 
 impl Limiter {
@@ -120,3 +449,130 @@ impl Limiter {
         self.next_frame = now + TARGET_FRAME_DURATION;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cartridge::MockCartridge;
+    use crate::cpu::tests::cpu_with_cartridge;
+
+    use super::*;
+
+    /// An emulator running a cartridge which executes an illegal opcode
+    /// straight away, and expects to be saved once.
+    fn emulator_at_illegal_opcode(policy: IllegalOpcodePolicy) -> Emulator {
+        let mut cartridge = MockCartridge::new();
+        cartridge
+            .expect_read_rom()
+            .returning(|address| if address == 0x100 { 0xD3 } else { 0 });
+        cartridge.expect_step().return_const(());
+        cartridge.expect_save().times(1).return_const(());
+
+        let mut cpu = cpu_with_cartridge(Box::new(cartridge));
+        cpu.set_illegal_opcode_policy(policy);
+
+        Emulator::new(cpu)
+    }
+
+    /// Wait for the emulator thread to stop by itself.
+    fn wait_for_exit(handle: &EmulatorHandle) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while handle.is_running() {
+            assert!(Instant::now() < deadline, "Emulator thread should exit");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn saves_when_stopped_by_an_error() {
+        let handle = emulator_at_illegal_opcode(IllegalOpcodePolicy::Error).spawn();
+        wait_for_exit(&handle);
+
+        assert_eq!(
+            handle.shutdown(),
+            Err(StepError::IllegalOpcode {
+                opcode: 0xD3,
+                address: 0x100
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cartridge is broken")]
+    fn shutdown_passes_on_panics() {
+        let mut cartridge = MockCartridge::new();
+        cartridge
+            .expect_read_rom()
+            .returning(|_| panic!("Cartridge is broken"));
+        cartridge.expect_save().return_const(());
+
+        let handle = Emulator::new(cpu_with_cartridge(Box::new(cartridge))).spawn();
+        wait_for_exit(&handle);
+
+        let _ = handle.shutdown();
+    }
+
+    #[test]
+    fn pauses_at_a_break() {
+        let handle = emulator_at_illegal_opcode(IllegalOpcodePolicy::Break).spawn();
+        handle.resume();
+
+        assert!(handle.screenshot().is_some());
+        assert_eq!(handle.shutdown(), Ok(()));
+    }
+
+    #[test]
+    fn traces_from_the_emulator_thread() {
+        let handle = emulator_at_illegal_opcode(IllegalOpcodePolicy::Break).spawn();
+        handle.start_trace(TraceOptions::default(), io::sink());
+
+        assert!(matches!(handle.stop_trace(), Some(Ok(()))));
+        assert_eq!(handle.shutdown(), Ok(()));
+    }
+
+    #[test]
+    fn debugs_the_emulator_thread() {
+        let handle = emulator_at_illegal_opcode(IllegalOpcodePolicy::Break).spawn();
+        let illegal_opcode = Some((0x100, BreakReason::IllegalOpcode(0xD3)));
+        assert_eq!(handle.wait_for_break(None), illegal_opcode);
+
+        // Jump over the illegal opcode into a loop of NOPs.
+        let mut state = handle.state().unwrap();
+        state.pc = 0xC000;
+        handle.with_cpu(move |cpu| cpu.set_state(state));
+        handle.write_memory(0xC000, vec![0x00, 0x00, 0x18, 0xFC]);
+        assert_eq!(handle.read_memory(0xC002, 2), Some(vec![0x18, 0xFC]));
+
+        let id = handle.add_breakpoint(Breakpoint::new(0xC002)).unwrap();
+        handle.resume();
+        let breakpoint = Some((0xC002, BreakReason::Breakpoint(id)));
+        assert_eq!(handle.wait_for_break(None), breakpoint);
+
+        handle.run(RunMode::StepInto);
+        assert_eq!(
+            handle.wait_for_break(None),
+            Some((0xC000, BreakReason::Step))
+        );
+
+        assert_eq!(handle.remove_breakpoint(id), Some(true));
+        handle.resume();
+        handle.pause();
+        assert!(matches!(
+            handle.wait_for_break(None),
+            Some((0xC000..=0xC002, BreakReason::Paused))
+        ));
+        handle.pause();
+        assert_eq!(handle.wait_for_break(Some(Duration::from_millis(10))), None);
+
+        assert_eq!(handle.shutdown(), Ok(()));
+    }
+
+    #[test]
+    fn run_frames_returns_errors() {
+        let emulator = emulator_at_illegal_opcode(IllegalOpcodePolicy::Break);
+
+        assert!(matches!(
+            emulator.run_frames(1),
+            Err(StepError::Break { address: 0x100, .. })
+        ));
+    }
+}
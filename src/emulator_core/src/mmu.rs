@@ -1,3 +1,4 @@
+mod dma;
 mod interrupts;
 mod joypad;
 pub mod ppu;
@@ -5,15 +6,34 @@ mod timer;
 
 use std::sync::Arc;
 
-use ppu::BGMapSelection;
 use ppu::SpritePaletteSelection;
 use ppu::ViewportRegister;
 use ppu::WindowPositionRegister;
 
 use crate::cartridge::Cartridge;
+use crate::disasm;
+use crate::disasm::Instruction;
+pub use crate::mmu::ppu::BGMapSelection;
 pub use crate::mmu::ppu::Color;
+pub use crate::mmu::ppu::DebugImage;
+pub use crate::mmu::ppu::DebugViews;
+pub use crate::mmu::ppu::Frame;
+pub use crate::mmu::ppu::FrameBuffer;
+pub use crate::mmu::ppu::IndexedPixel;
+pub use crate::mmu::ppu::Layer;
+pub use crate::mmu::ppu::OamEntry;
+pub use crate::mmu::ppu::Overlay;
+pub use crate::mmu::ppu::Palette;
+pub use crate::mmu::ppu::PaletteNumber;
+pub use crate::mmu::ppu::Palettes;
+pub use crate::mmu::ppu::Pixel;
+pub use crate::mmu::ppu::PixelSource;
 pub use crate::mmu::ppu::Renderer;
+pub use crate::mmu::ppu::Screenshot;
+pub use crate::mmu::ppu::SpriteFlags;
 pub use crate::mmu::ppu::PPU;
+pub use crate::mmu::ppu::SGB_HEIGHT;
+pub use crate::mmu::ppu::SGB_WIDTH;
 pub use interrupts::Interrupt;
 pub use interrupts::Interrupts;
 pub use joypad::Button;
@@ -24,6 +44,7 @@ pub use crate::mmu::ppu::TestRenderer;
 
 pub struct MMU {
     cartridge: Box<dyn Cartridge>,
+    dma: dma::OamDma,
     empty: [u8; 0x60],
     hram: [u8; 0x80],
     io: [u8; 0x80],
@@ -39,6 +60,7 @@ impl MMU {
     pub fn new(ppu: PPU, cartridge: Box<dyn Cartridge>, joypad: Arc<Joypad>) -> MMU {
         let mut mmu = MMU {
             cartridge,
+            dma: dma::OamDma::new(),
             empty: [0; 0x60],
             hram: [0; 0x80],
             io: [0; 0x80],
@@ -83,6 +105,15 @@ impl MMU {
 
     pub(crate) fn read_u8(&self, addr: u16) -> u8 {
         match addr {
+            _ if self.dma.is_blocking(addr) => 0xFF,
+            _ => self.read_bus(addr),
+        }
+    }
+
+    fn read_bus(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0x9FFF if !self.ppu.vram_readable() => 0xFF,
+            0xFE00..=0xFE9F if !self.ppu.oam_readable() => 0xFF,
             0..=0x7FFF => self.cartridge.read_rom(addr),
             0x8000..=0x97FF => self.ppu.read_tiledata(addr - 0x8000),
             0x9800..=0x9BFF => self.ppu.read_bg_map(BGMapSelection::Map0, addr - 0x9800),
@@ -110,7 +141,7 @@ impl MMU {
             0xFF43 => self.ppu.read_background_viewport(ViewportRegister::Scx),
             0xFF44 => self.ppu.read_ly(),
             0xFF45 => self.ppu.read_lyc(),
-            0xFF46 => 0, // DMA transfer
+            0xFF46 => self.dma.read(),
             0xFF47 => self.ppu.read_background_palette(),
             0xFF48 => self
                 .ppu
@@ -120,12 +151,17 @@ impl MMU {
                 .read_sprite_palette(SpritePaletteSelection::Palette1),
             0xFF4A => self.ppu.read_window_position(WindowPositionRegister::WY),
             0xFF4B => self.ppu.read_window_position(WindowPositionRegister::WX),
-            0xFF4C..=0xFF7F => 0, // Nothing
+            0xFF4C => 0, // Nothing
+            // KEY1, the CGB speed switch. Reads all ones on a DMG, which is
+            // how games tell they can't switch speed.
+            0xFF4D => 0xFF,
+            0xFF4E..=0xFF7F => 0, // Nothing
             0xFF80..=0xFFFE => self.hram[(addr - 0xFF80) as usize],
             0xFFFF => self.interrupts.read_interrupt_enabled(),
         }
     }
 
+    #[cfg(test)]
     pub(crate) fn read_u16(&self, addr: u16) -> u16 {
         let low = self.read_u8(addr) as u16;
         let high = self.read_u8(addr + 1) as u16;
@@ -135,6 +171,9 @@ impl MMU {
 
     pub(crate) fn write_u8(&mut self, addr: u16, value: u8) {
         match addr {
+            _ if self.dma.is_blocking(addr) => {}
+            0x8000..=0x9FFF if !self.ppu.vram_writable() => {}
+            0xFE00..=0xFE9F if !self.ppu.oam_writable() => {}
             0..=0x7FFF => self.cartridge.write_rom(addr, value),
             0x8000..=0x97FF => self.ppu.write_tiledata(addr - 0x8000, value),
             0x9800..=0x9BFF => self
@@ -148,7 +187,12 @@ impl MMU {
             0xE000..=0xFDFF => self.wrams[(addr - 0xE000) as usize] = value,
             0xFE00..=0xFE9F => self.ppu.write_oam(addr - 0xFE00, value),
             0xFEA0..=0xFEFF => self.empty[(addr - 0xFEA0) as usize] = value,
-            0xFF00 => self.joypad.write(value),
+            0xFF00 => {
+                self.joypad.write(value);
+                while let Some(packet) = self.joypad.take_sgb_packet() {
+                    self.ppu.receive_sgb_packet(packet);
+                }
+            }
             0xFF01 => self.serial.push(value as char),
             0xFF02 => self.io[(addr - 0xFF00) as usize] = value,
             0xFF03 => {} // Nothing
@@ -171,7 +215,7 @@ impl MMU {
                 .write_background_viewport(ViewportRegister::Scx, value),
             0xFF44 => {} // LY is read-only
             0xFF45 => self.ppu.write_lyc(value),
-            0xFF46 => self.dma_transfer(value),
+            0xFF46 => self.dma.write(value),
             0xFF47 => self.ppu.write_background_palette(value),
             0xFF48 => self
                 .ppu
@@ -191,19 +235,17 @@ impl MMU {
         }
     }
 
-    pub(crate) fn write_u16(&mut self, addr: u16, value: u16) {
-        let low = value as u8;
-        let high = (value >> 8) as u8;
-
-        self.write_u8(addr, low);
-        self.write_u8(addr + 1, high);
-    }
-
     pub(crate) fn step(&mut self, m_cycles: u8) {
         self.timer.step(m_cycles);
         self.ppu.step(m_cycles);
         self.cartridge.step(m_cycles);
 
+        for _ in 0..m_cycles {
+            if let Some((source, offset)) = self.dma.step() {
+                self.ppu.write_oam(offset, self.read_bus(source));
+            }
+        }
+
         if self.timer.interrupt_request {
             self.interrupts.request_interrupt(Interrupt::Timer);
             self.timer.interrupt_request = false;
@@ -225,19 +267,46 @@ impl MMU {
         }
     }
 
+    /// Behave as a Super Game Boy, listening for command packets on the
+    /// joypad register. See [PPU::set_sgb_enabled].
+    pub fn set_sgb_enabled(&mut self, enabled: bool) {
+        self.joypad.set_sgb_enabled(enabled);
+        self.ppu.set_sgb_enabled(enabled);
+    }
+
+    /// Advance only what keeps running while the CPU is in STOP mode: the
+    /// system clock is stopped, but the cartridge's real time clock has its
+    /// own.
+    pub(crate) fn stopped_step(&mut self, m_cycles: u8) {
+        self.cartridge.step(m_cycles);
+    }
+
+    /// Is a button pressed on one of the joypad lines the game has
+    /// selected, which wakes the CPU from STOP.
+    pub(crate) fn joypad_input(&self) -> bool {
+        self.joypad.input()
+    }
+
+    /// The ROM bank an address reads from: bank 0 for 0x0000-0x3FFF, and
+    /// whichever bank is switched in for 0x4000-0x7FFF. None outside ROM.
+    pub fn rom_bank(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0..=0x3FFF => Some(0),
+            0x4000..=0x7FFF => Some(self.cartridge.rom_bank()),
+            _ => None,
+        }
+    }
+
+    /// Disassemble the instructions from `start` up to `end`, as the CPU
+    /// currently sees them through the memory map.
+    pub fn disassemble(&self, start: u16, end: u16) -> Vec<Instruction> {
+        disasm::disassemble(start, end, |addr| self.read_u8(addr))
+    }
+
     /// Calls the Cartridge persister interface to save the current state of RAM. Can
     /// be called manually, but is generally handled by the emulation context
     /// automatically on shutdown.
     pub fn save(&mut self) {
         self.cartridge.save();
     }
-
-    fn dma_transfer(&mut self, value: u8) {
-        let start_address: u16 = (value as u16) << 8;
-
-        for offset in 0..160 {
-            self.ppu
-                .write_oam(offset, self.read_u8(start_address + offset))
-        }
-    }
 }
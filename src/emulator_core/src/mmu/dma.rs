@@ -0,0 +1,139 @@
+/// OAM DMA, copying 160 bytes into OAM at one byte per M-cycle. While a
+/// transfer is running the CPU can't reach OAM, or the bus the transfer is
+/// reading from, so games run their wait loop from HRAM.
+#[derive(Debug, Default)]
+pub struct OamDma {
+    /// DMA: the last value written, the upper byte of the source address.
+    register: u8,
+    /// The transfer currently copying, as its source address and the
+    /// number of bytes copied so far.
+    transfer: Option<(u16, u16)>,
+    /// A transfer which has been requested but not yet started, as its
+    /// source address and the cycles left until it starts. Restarting a
+    /// transfer leaves the old one running until the new one takes over.
+    starting: Option<(u16, u8)>,
+}
+
+const OAM_SIZE: u16 = 160;
+
+fn is_video_bus(addr: u16) -> bool {
+    (0x8000..=0x9FFF).contains(&addr)
+}
+
+impl OamDma {
+    pub fn new() -> OamDma {
+        OamDma::default()
+    }
+
+    /// Read the DMA register.
+    /// Should be available at 0xFF46.
+    pub fn read(&self) -> u8 {
+        self.register
+    }
+
+    /// Request a transfer from `value << 8`. It starts after the cycle of
+    /// the write and one more.
+    /// Should be available at 0xFF46.
+    pub fn write(&mut self, value: u8) {
+        self.register = value;
+        // Sources above 0xDFFF read from echo RAM.
+        let source = match value {
+            0xE0..=0xFF => (value as u16 - 0x20) << 8,
+            _ => (value as u16) << 8,
+        };
+        self.starting = Some((source, 2));
+    }
+
+    /// Is the CPU cut off from an address by the running transfer. VRAM is
+    /// on its own bus, everything else below OAM shares the external bus.
+    pub fn is_blocking(&self, addr: u16) -> bool {
+        let Some((source, _)) = self.transfer else {
+            return false;
+        };
+
+        match addr {
+            0xFE00..=0xFEFF => true,
+            0xFF00..=0xFFFF => false,
+            _ => is_video_bus(addr) == is_video_bus(source),
+        }
+    }
+
+    /// Advance by one M-cycle, returning the source address of the byte to
+    /// copy and its offset in OAM if a byte is copied in this cycle.
+    pub fn step(&mut self) -> Option<(u16, u16)> {
+        let copy = self.transfer.map(|(source, index)| (source + index, index));
+        self.transfer = match self.transfer {
+            Some((source, index)) if index + 1 < OAM_SIZE => Some((source, index + 1)),
+            _ => None,
+        };
+
+        if let Some((source, delay)) = self.starting {
+            if delay <= 1 {
+                self.starting = None;
+                self.transfer = Some((source, 0));
+            } else {
+                self.starting = Some((source, delay - 1));
+            }
+        }
+
+        copy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_one_byte_per_cycle_after_a_delay() {
+        let mut dma = OamDma::new();
+        dma.write(0xC1);
+        assert_eq!(dma.read(), 0xC1);
+
+        assert_eq!(dma.step(), None);
+        assert!(!dma.is_blocking(0xFE00));
+        assert_eq!(dma.step(), None);
+        assert!(dma.is_blocking(0xFE00));
+
+        let copies: Vec<_> = std::iter::from_fn(|| dma.step()).collect();
+        assert_eq!(copies.len(), 160);
+        assert_eq!(copies[0], (0xC100, 0));
+        assert_eq!(copies[159], (0xC19F, 159));
+        assert!(!dma.is_blocking(0xFE00));
+    }
+
+    #[test]
+    fn blocks_oam_and_the_source_bus() {
+        let mut dma = OamDma::new();
+        dma.write(0x80);
+        dma.step();
+        dma.step();
+
+        assert!(dma.is_blocking(0xFE00));
+        assert!(dma.is_blocking(0x9800));
+        assert!(!dma.is_blocking(0xC000));
+        assert!(!dma.is_blocking(0xFF80));
+
+        dma.write(0xC0);
+        dma.step();
+        dma.step();
+
+        assert!(dma.is_blocking(0x0150));
+        assert!(dma.is_blocking(0xFDFF));
+        assert!(!dma.is_blocking(0x8000));
+    }
+
+    #[test]
+    fn restart_keeps_the_old_transfer_until_the_new_one_starts() {
+        let mut dma = OamDma::new();
+        dma.write(0xC0);
+        dma.step();
+        dma.step();
+        assert_eq!(dma.step(), Some((0xC000, 0)));
+
+        dma.write(0xE1);
+        assert_eq!(dma.step(), Some((0xC001, 1)));
+        assert_eq!(dma.step(), Some((0xC002, 2)));
+        assert_eq!(dma.step(), Some((0xC100, 0)));
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::VecDeque;
 use std::sync::Mutex;
 
 /// Gameboy Joypad register, Directly accessible to the "user"
@@ -18,6 +19,74 @@ struct State {
     dpad_signal: u8,
     // Mask set by MMU which controls which signal set to access
     mask: u8,
+    // Super Game Boy command packets and multiplayer, see [Sgb]
+    sgb: Option<Sgb>,
+}
+
+/// A Super Game Boy listens to the P14/P15 lines for command packets. Each
+/// packet starts with a reset pulse (both lines low), followed by 128 bits,
+/// least significant first, and a stop bit. A '0' bit pulls P14 low and a '1'
+/// bit pulls P15 low, with both lines going high again between bits.
+///
+/// Once multiplayer is enabled with MLT_REQ, the lower nibble reads the
+/// selected player while both lines are high, and the player advances each
+/// time P15 goes high again.
+#[derive(Default)]
+struct Sgb {
+    // Value of P14/P15 after the previous write
+    lines: u8,
+    // Number of bits received of the current packet, if one is in progress
+    bit: Option<u8>,
+    data: [u8; 16],
+    packets: VecDeque<[u8; 16]>,
+    players: u8,
+    player: u8,
+}
+
+/// Command code of MLT_REQ, the only packet handled by the joypad itself.
+const MLT_REQ: u8 = 0x11;
+
+impl Sgb {
+    fn write(&mut self, lines: u8) {
+        let previous = std::mem::replace(&mut self.lines, lines);
+
+        match (self.bit, lines) {
+            (_, 0x00) => {
+                self.bit = Some(0);
+                self.data = [0; 16];
+            }
+            (Some(128), 0x10 | 0x20) if previous == 0x30 => {
+                // Only a '0' is a valid stop bit.
+                self.bit = None;
+                if lines == 0x20 {
+                    self.receive_packet();
+                }
+            }
+            (Some(bit), 0x10 | 0x20) if previous == 0x30 => {
+                if lines == 0x10 {
+                    self.data[bit as usize / 8] |= 1 << (bit % 8);
+                }
+                self.bit = Some(bit + 1);
+            }
+            (None, 0x30) if previous == 0x10 && self.players > 1 => {
+                self.player = (self.player + 1) % self.players;
+            }
+            _ => {}
+        }
+    }
+
+    fn receive_packet(&mut self) {
+        if self.data[0] >> 3 == MLT_REQ {
+            self.players = match self.data[1] & 0x3 {
+                1 => 2,
+                3 => 4,
+                _ => 1,
+            };
+            self.player = 0;
+        }
+
+        self.packets.push_back(self.data);
+    }
 }
 
 impl State {
@@ -27,6 +96,7 @@ impl State {
             button_signal: 0xF,
             dpad_signal: 0xF,
             mask: 0,
+            sgb: None,
         }
     }
 }
@@ -99,14 +169,46 @@ impl Joypad {
             return guard.button_signal | guard.mask;
         }
 
-        guard.mask | 0xF
+        match &guard.sgb {
+            Some(sgb) if sgb.players > 1 => guard.mask | (0xF - sgb.player),
+            _ => guard.mask | 0xF,
+        }
     }
 
     // Should be available at 0xFF00. Writes the state of the joypad register.
     pub(super) fn write(&self, value: u8) {
         let mut guard = self.state.lock().expect("Should aqcuire mutex");
 
-        guard.mask = value & 0xF0
+        guard.mask = value & 0xF0;
+
+        if let Some(sgb) = guard.sgb.as_mut() {
+            sgb.write(value & 0x30);
+        }
+    }
+
+    /// Listen for Super Game Boy command packets, and answer MLT_REQ.
+    pub(super) fn set_sgb_enabled(&self, enabled: bool) {
+        let mut guard = self.state.lock().expect("Should aqcuire mutex");
+
+        guard.sgb = enabled.then(|| Sgb {
+            lines: 0x30,
+            ..Sgb::default()
+        });
+    }
+
+    /// The oldest Super Game Boy packet which hasn't been handled yet.
+    pub(super) fn take_sgb_packet(&self) -> Option<[u8; 16]> {
+        let mut guard = self.state.lock().expect("Should aqcuire mutex");
+
+        guard.sgb.as_mut()?.packets.pop_front()
+    }
+
+    /// Is any selected line pulled low by a pressed button.
+    pub(super) fn input(&self) -> bool {
+        let guard = self.state.lock().expect("Should aqcuire mutex");
+
+        (guard.mask & 0x10 == 0 && guard.dpad_signal != 0xF)
+            || (guard.mask & 0x20 == 0 && guard.button_signal != 0xF)
     }
 
     pub(super) fn interrupt_requested(&self) -> bool {
@@ -256,6 +358,72 @@ mod tests {
         assert_eq!(joypad.read(), 0x10 | 0b1110);
     }
 
+    fn send_packet(joypad: &Joypad, packet: [u8; 16]) {
+        joypad.write(0x00);
+        joypad.write(0x30);
+        for bit in 0..128 {
+            let one = packet[bit / 8] & (1 << (bit % 8)) != 0;
+            joypad.write(if one { 0x10 } else { 0x20 });
+            joypad.write(0x30);
+        }
+        joypad.write(0x20);
+        joypad.write(0x30);
+    }
+
+    #[test]
+    fn receives_sgb_packets() {
+        let joypad = Joypad::new();
+        let packet = [
+            0x01, 0xFF, 0x7F, 0x00, 0x80, 0x55, 0xAA, 0, 0, 0, 0, 0, 0, 0, 0x12, 0x34,
+        ];
+
+        send_packet(&joypad, packet);
+        assert_eq!(joypad.take_sgb_packet(), None);
+
+        joypad.set_sgb_enabled(true);
+        send_packet(&joypad, packet);
+        send_packet(&joypad, [0x89; 16]);
+
+        assert_eq!(joypad.take_sgb_packet(), Some(packet));
+        assert_eq!(joypad.take_sgb_packet(), Some([0x89; 16]));
+        assert_eq!(joypad.take_sgb_packet(), None);
+    }
+
+    #[test]
+    fn sgb_multiplayer() {
+        let joypad = Joypad::new();
+        joypad.set_sgb_enabled(true);
+
+        let mut mlt_req = [0; 16];
+        mlt_req[0] = MLT_REQ << 3 | 1;
+        mlt_req[1] = 0x01;
+        send_packet(&joypad, mlt_req);
+        assert_eq!(joypad.read(), 0x3F);
+
+        // Reading the buttons moves on to the next player.
+        joypad.write(0x10);
+        joypad.write(0x30);
+        assert_eq!(joypad.read(), 0x3E);
+
+        joypad.write(0x20);
+        joypad.write(0x10);
+        joypad.write(0x30);
+        assert_eq!(joypad.read(), 0x3F);
+    }
+
+    #[test]
+    fn input_only_on_selected_lines() {
+        let joypad = Joypad::new();
+        joypad.button_down(Button::Start);
+
+        joypad.write(0x30);
+        assert!(!joypad.input());
+        joypad.write(0x20);
+        assert!(!joypad.input());
+        joypad.write(0x10);
+        assert!(joypad.input());
+    }
+
     #[test]
     fn interrupt_requested_when_button_pressed() {
         let joypad = Joypad::new();
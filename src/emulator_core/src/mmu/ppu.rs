@@ -2,29 +2,38 @@ mod background_map;
 mod background_palette;
 mod background_tile;
 mod background_viewport;
+mod compatibility_palettes;
+mod debug_overlay;
+mod debug_views;
+mod frame_buffer;
+mod indexed_frame;
 mod lcd_control;
 pub mod lcdc_status;
 mod oam;
+mod palette;
 mod pixel;
 mod renderer;
 mod rendering;
+mod screenshot;
+mod sgb;
 mod sprite_palette;
 mod sprite_tile;
 mod tiledata;
 mod window_position;
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use background_map::BackgroundMap;
 use background_palette::BackgroundPalette;
 use background_tile::BackgroundTile;
 use background_viewport::BackgroundViewport;
+use debug_overlay::DebugOptions;
 use lcd_control::LCDControl;
 use lcdc_status::LCDStatus;
-use oam::SpriteFlags;
-use oam::SpriteSize;
 use oam::Oam;
-use pixel::Pixel;
+use oam::SpriteSize;
+use sgb::Sgb;
 use sprite_palette::SpritePalette;
 use sprite_tile::SpriteTile;
 use tiledata::TileData;
@@ -32,8 +41,24 @@ use window_position::WindowPosition;
 
 pub use background_map::BGMapSelection;
 pub use background_viewport::ViewportRegister;
+pub use debug_overlay::Layer;
+pub use debug_overlay::Overlay;
+pub use debug_views::DebugImage;
+pub use debug_views::DebugViews;
+pub use debug_views::OamEntry;
+pub use debug_views::Palettes;
+pub use frame_buffer::FrameBuffer;
+pub use indexed_frame::IndexedPixel;
+pub use indexed_frame::PixelSource;
+pub use oam::PaletteNumber;
+pub use oam::SpriteFlags;
+pub use palette::Palette;
+pub use pixel::Pixel;
 pub use renderer::Color;
+pub use renderer::Frame;
 pub use renderer::Renderer;
+pub use screenshot::Screenshot;
+pub use sgb::{SGB_HEIGHT, SGB_WIDTH};
 pub use sprite_palette::SpritePaletteSelection;
 pub use tiledata::TileAddressingMethod;
 pub use window_position::WindowPositionRegister;
@@ -53,17 +78,31 @@ pub struct PPU {
     bg_priority: [Pixel; WIDTH * HEIGHT],
     bg_map0: BackgroundMap,
     bg_map1: BackgroundMap,
+    blank_frame: bool,
     clock: u32,
+    cycles: u64,
+    debug_options: DebugOptions,
+    drawing_dots: u32,
+    frame_number: u64,
+    indexed: Option<Box<[IndexedPixel; WIDTH * HEIGHT]>>,
+    last_frame: Screenshot,
     lcd_stat: LCDStatus,
     lcdc: LCDControl,
+    lcd_on_line: bool,
+    line_sprites: Vec<oam::Sprite>,
     ly: u8,
     lyc: u8,
     oam: Oam,
+    palette: Palette,
     sprite_palette_0: SpritePalette,
     sprite_palette_1: SpritePalette,
+    stat_line: bool,
     tiledata: TileData,
+    window_line: u8,
     window_position: WindowPosition,
+    window_triggered: bool,
     renderer: Arc<dyn Renderer>,
+    sgb: Option<Box<Sgb>>,
 }
 
 /// TODO: this can be a more compact type
@@ -82,19 +121,39 @@ impl PPU {
             bg_priority: [Pixel::Color0; WIDTH * HEIGHT],
             bg_map0: BackgroundMap::new(),
             bg_map1: BackgroundMap::new(),
-            buffer: [renderer.palette(Color::Black); WIDTH * HEIGHT],
+            blank_frame: false,
+            buffer: [Palette::GREY.background[3]; WIDTH * HEIGHT],
             clock: 0,
+            cycles: 0,
+            debug_options: DebugOptions::default(),
+            drawing_dots: 0,
+            frame_number: 0,
+            indexed: None,
+            last_frame: Screenshot {
+                width: WIDTH,
+                height: HEIGHT,
+                pixels: vec![Palette::GREY.background[0]; WIDTH * HEIGHT],
+                number: 0,
+                timestamp: Duration::ZERO,
+            },
             interrupt_request: InterruptRequests::default(),
             lcd_stat: LCDStatus::new(),
             lcdc: LCDControl::new(),
+            lcd_on_line: false,
+            line_sprites: Vec::with_capacity(10),
             ly: 0,
             lyc: 0,
             oam: Oam::new(),
+            palette: Palette::default(),
             sprite_palette_0: SpritePalette::new(),
             sprite_palette_1: SpritePalette::new(),
+            stat_line: false,
             tiledata: TileData::new(),
+            window_line: 0,
             window_position: WindowPosition::default(),
+            window_triggered: false,
             renderer,
+            sgb: None,
         }
     }
 
@@ -146,14 +205,20 @@ impl PPU {
         }
     }
 
-    /// Read from the LCD stat register
+    /// Read from the LCD stat register. Bit 7 is unused and always reads 1.
     pub(crate) fn read_lcd_stat(&self) -> u8 {
-        self.lcd_stat.read()
+        self.lcd_stat.read() | 0x80
     }
 
-    /// Write to the LCD stat register.
+    /// Write to the LCD stat register. Only the interrupt enable bits are
+    /// writable, the mode and coincidence flag are controlled by the PPU.
     pub(crate) fn write_lcd_stat(&mut self, value: u8) {
-        self.lcd_stat.write(value)
+        let read_only = self.lcd_stat.read() & 0b0000_0111;
+        self.lcd_stat.write((value & 0b0111_1000) | read_only);
+
+        if self.lcdc.lcd_enable() {
+            self.update_stat_line();
+        }
     }
 
     /// Read from the LCD control register
@@ -161,18 +226,79 @@ impl PPU {
         self.lcdc.read()
     }
 
-    /// Read from the LCD control register
+    /// Write to the LCD control register
     pub(crate) fn write_lcdc(&mut self, value: u8) {
         if self.lcdc.is_lcd_enable_toggled_off(value) {
-            self.update_ly(0);
-            self.lcd_stat.set_ppu_mode(lcdc_status::PPUMode::HBlank);
-            self.clock = 0;
-            self.reset_buffer();
+            self.lcd_off();
+        }
+
+        if self.lcdc.is_lcd_enable_toggled_on(value) {
+            self.lcd_on();
         }
 
         self.lcdc.write(value)
     }
 
+    /// Can the CPU currently read VRAM. VRAM is locked while the PPU is
+    /// drawing (mode 3), from an M-cycle before STAT reads mode 3. Reads
+    /// return $FF.
+    pub(crate) fn vram_readable(&self) -> bool {
+        !self.lcdc.lcd_enable()
+            || (self.lcd_stat.ppu_mode() != lcdc_status::PPUMode::Drawing
+                && !self.entering_drawing())
+    }
+
+    /// Can the CPU currently write VRAM. Writes are ignored while the PPU
+    /// is drawing (mode 3), the lock only applies once STAT reads mode 3.
+    pub(crate) fn vram_writable(&self) -> bool {
+        !self.lcdc.lcd_enable() || self.lcd_stat.ppu_mode() != lcdc_status::PPUMode::Drawing
+    }
+
+    /// Can the CPU currently read OAM. OAM is locked while the PPU is
+    /// scanning it (mode 2) and drawing (mode 3), from an M-cycle before
+    /// STAT reads mode 2. Reads return $FF.
+    pub(crate) fn oam_readable(&self) -> bool {
+        !self.lcdc.lcd_enable()
+            || (matches!(
+                self.lcd_stat.ppu_mode(),
+                lcdc_status::PPUMode::HBlank | lcdc_status::PPUMode::VBlank
+            ) && !self.entering_oam())
+    }
+
+    /// Can the CPU currently write OAM. Writes are ignored during modes 2
+    /// and 3, once STAT reads them, except for the M-cycle between the two.
+    pub(crate) fn oam_writable(&self) -> bool {
+        !self.lcdc.lcd_enable()
+            || matches!(
+                self.lcd_stat.ppu_mode(),
+                lcdc_status::PPUMode::HBlank | lcdc_status::PPUMode::VBlank
+            )
+            || self.entering_drawing()
+    }
+
+    /// Switching the LCD off stops the PPU immediately, resetting LY and
+    /// putting it in mode 0. The screen is blanked while the LCD is off.
+    fn lcd_off(&mut self) {
+        self.ly = 0;
+        self.lcd_stat.set_ppu_mode(lcdc_status::PPUMode::HBlank);
+        self.clock = 0;
+        self.stat_line = false;
+        self.start_frame();
+
+        self.blank_buffer();
+        self.present_frame();
+    }
+
+    /// Switching the LCD on starts the PPU from the beginning of line 0.
+    /// That line skips the OAM scan, and the first frame isn't displayed.
+    fn lcd_on(&mut self) {
+        self.clock = 0;
+        self.lcd_on_line = true;
+        self.blank_frame = true;
+        self.update_ly(0);
+        self.lcd_stat.set_ppu_mode(lcdc_status::PPUMode::HBlank);
+    }
+
     /// Read from one of the 2 available sprite palettes
     pub(crate) fn read_sprite_palette(&self, palette: SpritePaletteSelection) -> u8 {
         match palette {
@@ -226,6 +352,10 @@ impl PPU {
     pub(crate) fn write_lyc(&mut self, value: u8) {
         self.lyc = value;
         self.ly_compare();
+
+        if self.lcdc.lcd_enable() {
+            self.update_stat_line();
+        }
     }
 
     /// Read from the LY register
@@ -233,6 +363,11 @@ impl PPU {
         self.ly
     }
 
+    /// How many frames have been sent to the renderer.
+    pub(crate) fn frame_number(&self) -> u64 {
+        self.frame_number
+    }
+
     /// Write to one of the window position registers (WX, WY)
     pub(crate) fn write_window_position(&mut self, register: WindowPositionRegister, value: u8) {
         match register {
@@ -241,14 +376,11 @@ impl PPU {
         }
     }
 
-    /// Compare Ly and Lyc register, setting coincidence flag and requesting
-    /// STAT interrupt if enabled
+    /// Compare Ly and Lyc register, setting the coincidence flag. The
+    /// STAT interrupt itself is handled by the STAT line, see
+    /// [PPU::update_stat_line].
     pub(super) fn ly_compare(&mut self) {
         self.lcd_stat.set_lyc_eq_ly(self.ly == self.lyc);
-
-        if self.lcd_stat.lyc_ly_stat_ie() && self.lyc == self.ly {
-            self.request_stat_interrupt()
-        }
     }
 
     pub(super) fn request_stat_interrupt(&mut self) {
@@ -260,6 +392,10 @@ impl PPU {
     }
 
     fn reset_buffer(&mut self) {
-        self.buffer = [self.renderer.palette(Color::Black); 160 * 144];
+        self.buffer = [self.palette.color(PixelSource::Background, Color::Black); 160 * 144];
+
+        if let Some(sgb) = self.sgb.as_mut() {
+            sgb.fill_shade(Color::Black);
+        }
     }
 }
@@ -0,0 +1,340 @@
+//! The colour palettes the CGB boot ROM gives to DMG games. Games published
+//! by Nintendo are looked up by title checksum, with the 4th letter of the
+//! title telling apart games with the same checksum. Other games get the
+//! default palette, unless another is picked by holding a button combination
+//! while the logo scrolls.
+use std::borrow::Cow;
+
+use super::*;
+use crate::mmu::Button;
+use palette::rgb555;
+
+/// Colours from the boot ROM, in the CGB's 15 bit BGR format. Mostly
+/// groups of four, although some palette combinations start part of the way
+/// through a group.
+const COLORS: [u16; 120] = [
+    0x7FFF, 0x32BF, 0x00D0, 0x0000, //  0
+    0x639F, 0x4279, 0x15B0, 0x04CB, //  1
+    0x7FFF, 0x6E31, 0x454A, 0x0000, //  2
+    0x7FFF, 0x1BEF, 0x0200, 0x0000, //  3
+    0x7FFF, 0x421F, 0x1CF2, 0x0000, //  4
+    0x7FFF, 0x5294, 0x294A, 0x0000, //  5
+    0x7FFF, 0x03FF, 0x012F, 0x0000, //  6
+    0x7FFF, 0x03EF, 0x01D6, 0x0000, //  7
+    0x7FFF, 0x42B5, 0x3DC8, 0x0000, //  8
+    0x7E74, 0x03FF, 0x0180, 0x0000, //  9
+    0x67FF, 0x77AC, 0x1A13, 0x2D6B, // 10
+    0x7ED6, 0x4BFF, 0x2175, 0x0000, // 11
+    0x53FF, 0x4A5F, 0x7E52, 0x0000, // 12
+    0x4FFF, 0x7ED2, 0x3A4C, 0x1CE0, // 13
+    0x03ED, 0x7FFF, 0x255F, 0x0000, // 14
+    0x036A, 0x021F, 0x03FF, 0x7FFF, // 15
+    0x7FFF, 0x01DF, 0x0112, 0x0000, // 16
+    0x231F, 0x035F, 0x00F2, 0x0009, // 17
+    0x7FFF, 0x03EA, 0x011F, 0x0000, // 18
+    0x299F, 0x001A, 0x000C, 0x0000, // 19
+    0x7FFF, 0x027F, 0x001F, 0x0000, // 20
+    0x7FFF, 0x03E0, 0x0206, 0x0120, // 21
+    0x7FFF, 0x7EEB, 0x001F, 0x7C00, // 22
+    0x7FFF, 0x3FFF, 0x7E00, 0x001F, // 23
+    0x7FFF, 0x03FF, 0x001F, 0x0000, // 24
+    0x03FF, 0x001F, 0x000C, 0x0000, // 25
+    0x7FFF, 0x033F, 0x0193, 0x0000, // 26
+    0x0000, 0x4200, 0x037F, 0x7FFF, // 27
+    0x7FFF, 0x7E8C, 0x7C00, 0x0000, // 28
+    0x7FFF, 0x1BEF, 0x6180, 0x0000, // 29
+];
+
+/// Offsets into [COLORS] of the four colours used for OBJ0, OBJ1 and BG.
+#[derive(Debug, Clone, Copy)]
+struct Combination {
+    sprite_0: usize,
+    sprite_1: usize,
+    background: usize,
+}
+
+/// A combination made from whole groups of four colours.
+const fn groups(sprite_0: usize, sprite_1: usize, background: usize) -> Combination {
+    offsets(sprite_0 * 4, sprite_1 * 4, background * 4)
+}
+
+const fn offsets(sprite_0: usize, sprite_1: usize, background: usize) -> Combination {
+    Combination {
+        sprite_0,
+        sprite_1,
+        background,
+    }
+}
+
+const COMBINATIONS: [Combination; 51] = [
+    groups(4, 4, 29),
+    groups(18, 18, 18),
+    groups(20, 20, 20),
+    groups(24, 24, 24),
+    groups(9, 9, 9),
+    groups(0, 0, 0),
+    groups(27, 27, 27),
+    groups(5, 5, 5),
+    groups(12, 12, 12),
+    groups(26, 26, 26),
+    groups(16, 8, 8), // 10
+    groups(4, 28, 28),
+    groups(4, 2, 2),
+    groups(3, 4, 4),
+    groups(4, 29, 29),
+    groups(28, 4, 28),
+    groups(2, 17, 2),
+    groups(16, 16, 8),
+    groups(4, 4, 7),
+    groups(4, 4, 18),
+    groups(4, 4, 20), // 20
+    groups(19, 19, 9),
+    offsets(4 * 4 - 1, 4 * 4 - 1, 11 * 4),
+    groups(17, 17, 2),
+    groups(4, 4, 2),
+    groups(4, 4, 3),
+    groups(28, 28, 0),
+    groups(3, 3, 0),
+    groups(0, 0, 1),
+    groups(18, 22, 18),
+    groups(20, 22, 20), // 30
+    groups(24, 22, 24),
+    groups(16, 22, 8),
+    groups(17, 4, 13),
+    offsets(28 * 4 - 1, 0, 14 * 4),
+    offsets(28 * 4 - 1, 4 * 4, 15 * 4),
+    groups(19, 22, 9),
+    groups(16, 28, 10),
+    groups(4, 23, 28),
+    groups(17, 22, 2),
+    groups(4, 0, 2), // 40
+    groups(4, 28, 3),
+    groups(28, 3, 0),
+    groups(3, 28, 4),
+    groups(21, 28, 4),
+    groups(3, 28, 0),
+    groups(25, 3, 28),
+    groups(0, 28, 8),
+    groups(4, 3, 28),
+    groups(28, 3, 6),
+    groups(4, 28, 29), // 50
+];
+
+/// Title checksums of the games with their own palette. Checksums from
+/// [FIRST_SHARED_CHECKSUM] onwards are shared between games, and are told
+/// apart by [FOURTH_LETTERS].
+const TITLE_CHECKSUMS: [u8; 79] = [
+    0x00, 0x88, 0x16, 0x36, 0xD1, 0xDB, 0xF2, 0x3C, 0x8C, 0x92, // 0
+    0x3D, 0x5C, 0x58, 0xC9, 0x3E, 0x70, 0x1D, 0x59, 0x69, 0x19, // 10
+    0x35, 0xA8, 0x14, 0xAA, 0x75, 0x95, 0x99, 0x34, 0x6F, 0x15, // 20
+    0xFF, 0x97, 0x4B, 0x90, 0x17, 0x10, 0x39, 0xF7, 0xF6, 0xA2, // 30
+    0x49, 0x4E, 0x43, 0x68, 0xE0, 0x8B, 0xF0, 0xCE, 0x0C, 0x29, // 40
+    0xE8, 0xB7, 0x86, 0x9A, 0x52, 0x01, 0x9D, 0x71, 0x9C, 0xBD, // 50
+    0x5D, 0x6D, 0x67, 0x3F, 0x6B, 0xB3, 0x46, 0x28, 0xA5, 0xC6, // 60
+    0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4, // 70
+];
+
+const FIRST_SHARED_CHECKSUM: usize = 65;
+
+/// 4th letter of the title for each game with a shared checksum. Each row
+/// of 14 lines up with the shared checksums, a game's index is
+/// [FIRST_SHARED_CHECKSUM] plus the position of its letter.
+const FOURTH_LETTERS: &[u8; 29] = b"BEFAARBEKEK R-URAR INAILICE R";
+
+/// Index into [COMBINATIONS] for each game, by index from the title
+/// checksum and 4th letter.
+const GAME_COMBINATIONS: [u8; 94] = [
+    0, 4, 5, 35, 34, 3, 31, 15, 10, 5, // 0
+    19, 36, 7, 37, 30, 44, 21, 32, 31, 20, // 10
+    5, 33, 13, 14, 5, 29, 5, 18, 9, 3, // 20
+    2, 26, 25, 25, 41, 42, 26, 45, 42, 45, // 30
+    36, 38, 26, 42, 30, 41, 34, 34, 5, 42, // 40
+    6, 5, 33, 25, 42, 42, 40, 2, 16, 25, // 50
+    42, 42, 5, 0, 39, 36, 22, 25, 6, 32, // 60
+    12, 36, 11, 39, 18, 39, 24, 31, 50, 17, // 70
+    46, 6, 27, 0, 47, 41, 41, 0, 0, 19, // 80
+    34, 23, 18, 29, // 90
+];
+
+/// Combinations picked with a direction and optional button, for the
+/// palettes that can be chosen manually.
+const BUTTON_COMBINATIONS: [(Button, Option<Button>, usize); 12] = [
+    (Button::Up, None, 5),
+    (Button::Up, Some(Button::A), 43),
+    (Button::Up, Some(Button::B), 28),
+    (Button::Left, None, 48),
+    (Button::Left, Some(Button::A), 40),
+    (Button::Left, Some(Button::B), 7),
+    (Button::Down, None, 8),
+    (Button::Down, Some(Button::A), 3),
+    (Button::Down, Some(Button::B), 49),
+    (Button::Right, None, 0),
+    (Button::Right, Some(Button::A), 1),
+    (Button::Right, Some(Button::B), 6),
+];
+
+/// Index into [GAME_COMBINATIONS] for a game, or 0 (the default palette)
+/// if the game isn't in the table.
+fn game_index(title_checksum: u8, fourth_letter: u8) -> usize {
+    let Some(index) = TITLE_CHECKSUMS
+        .iter()
+        .position(|&checksum| checksum == title_checksum)
+    else {
+        return 0;
+    };
+
+    if index < FIRST_SHARED_CHECKSUM {
+        return index;
+    }
+
+    (index - FIRST_SHARED_CHECKSUM..FOURTH_LETTERS.len())
+        .step_by(TITLE_CHECKSUMS.len() - FIRST_SHARED_CHECKSUM)
+        .find(|&position| FOURTH_LETTERS[position] == fourth_letter)
+        .map(|position| FIRST_SHARED_CHECKSUM + position)
+        .unwrap_or(0)
+}
+
+fn palette_from(name: Cow<'static, str>, combination: Combination) -> Palette {
+    let colors = |offset: usize| std::array::from_fn(|i| rgb555(COLORS[offset + i]));
+
+    Palette {
+        name,
+        background: colors(combination.background),
+        sprite_0: colors(combination.sprite_0),
+        sprite_1: colors(combination.sprite_1),
+    }
+}
+
+fn button_name(button: Button) -> &'static str {
+    match button {
+        Button::Up => "Up",
+        Button::Down => "Down",
+        Button::Left => "Left",
+        Button::Right => "Right",
+        Button::A => "A",
+        Button::B => "B",
+        Button::Start => "Start",
+        Button::Select => "Select",
+    }
+}
+
+impl Palette {
+    /// The palette the CGB boot ROM picks for a DMG game from the checksum
+    /// and fourth letter of its title, see [Header::cgb_palette]. Games not
+    /// published by Nintendo, and those the boot ROM doesn't know, get the
+    /// same palette as Right on the d-pad.
+    ///
+    /// [Header::cgb_palette]: crate::Header::cgb_palette
+    pub fn cgb_compatibility(title_checksum: u8, fourth_letter: u8, nintendo: bool) -> Palette {
+        let index = if nintendo {
+            game_index(title_checksum, fourth_letter)
+        } else {
+            0
+        };
+
+        let combination = COMBINATIONS[GAME_COMBINATIONS[index] as usize];
+        palette_from(Cow::Borrowed("CGB"), combination)
+    }
+
+    /// One of the 12 palettes that can be picked on the CGB by holding a
+    /// direction, and optionally A or B, while the boot logo is shown.
+    pub fn cgb_button_combo(direction: Button, button: Option<Button>) -> Option<Palette> {
+        BUTTON_COMBINATIONS
+            .iter()
+            .find(|(d, b, _)| *d == direction && *b == button)
+            .map(|&(direction, button, combination)| {
+                let name = match button {
+                    Some(button) => {
+                        format!("CGB {}+{}", button_name(direction), button_name(button))
+                    }
+                    None => format!("CGB {}", button_name(direction)),
+                };
+
+                palette_from(Cow::Owned(name), COMBINATIONS[combination])
+            })
+    }
+
+    /// All 12 palettes which can be picked with a button combination, see
+    /// [Palette::cgb_button_combo].
+    pub fn cgb_button_combos() -> Vec<Palette> {
+        BUTTON_COMBINATIONS
+            .iter()
+            .filter_map(|&(direction, button, _)| Palette::cgb_button_combo(direction, button))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Header;
+
+    use super::*;
+
+    /// The palette picked for a game with the title `text`, published by
+    /// Nintendo or not.
+    fn palette_for(text: &[u8], nintendo: bool) -> Palette {
+        let mut rom = vec![0; 0x150];
+        rom[0x134..0x134 + text.len()].copy_from_slice(text);
+        rom[0x14B] = if nintendo { 0x01 } else { 0x00 };
+
+        Header::new(&rom).cgb_palette()
+    }
+
+    #[test]
+    fn picks_palette_by_title() {
+        let palette = palette_for(b"POKEMON RED", true);
+
+        assert_eq!(palette.background, [0xFFFFFF, 0xFF8484, 0x943A3A, 0x000000]);
+        assert_eq!(palette.sprite_0, [0xFFFFFF, 0x7BFF31, 0x008400, 0x000000]);
+        assert_eq!(palette.sprite_1, palette.background);
+    }
+
+    #[test]
+    fn shared_checksums_use_fourth_letter() {
+        // SUPER MARIOLAND and POKEMON BLUE share their checksums with
+        // other games.
+        assert_eq!(game_index(0x46, b'E'), 66);
+        assert_eq!(game_index(0x61, b'E'), 72);
+        assert_eq!(game_index(0x46, b'R'), 80);
+        assert_eq!(game_index(0xB3, b'R'), 93);
+        // Shared checksum with an unknown 4th letter
+        assert_eq!(game_index(0x46, b'Z'), 0);
+
+        let palette = palette_for(b"SUPER MARIOLAND", true);
+        assert_eq!(palette.background, [0xB5B5FF, 0xFFFF94, 0xAD5A42, 0x000000]);
+        assert_eq!(palette.sprite_0, [0x000000, 0xFFFFFF, 0xFF8484, 0x943A3A]);
+    }
+
+    #[test]
+    fn unknown_and_third_party_games_use_default() {
+        let default = Palette::cgb_button_combo(Button::Right, None).unwrap();
+
+        let unknown = palette_for(b"HELLO WORLD", true);
+        assert_eq!(unknown.background, default.background);
+
+        let third_party = palette_for(b"TETRIS", false);
+        assert_eq!(third_party.background, default.background);
+        assert_eq!(third_party.sprite_0, default.sprite_0);
+
+        let tetris = palette_for(b"TETRIS", true);
+        assert_ne!(tetris.background, default.background);
+    }
+
+    #[test]
+    fn button_combos() {
+        let combos = Palette::cgb_button_combos();
+        assert_eq!(combos.len(), 12);
+        assert_eq!(combos[1].name, "CGB Up+A");
+
+        let grey = Palette::cgb_button_combo(Button::Left, Some(Button::B)).unwrap();
+        assert_eq!(grey.background, [0xFFFFFF, 0xA5A5A5, 0x525252, 0x000000]);
+
+        let inverted = Palette::cgb_button_combo(Button::Right, Some(Button::B)).unwrap();
+        assert_eq!(
+            inverted.background,
+            [0x000000, 0x008484, 0xFFDE00, 0xFFFFFF]
+        );
+
+        assert!(Palette::cgb_button_combo(Button::Start, None).is_none());
+    }
+}
@@ -0,0 +1,308 @@
+use super::rendering::WINDOW_X_OFFSET;
+use super::*;
+
+const SPRITE_BOUNDS_COLOR: u32 = 0x00FF00;
+const SPRITE_INDEX_COLOR: u32 = 0xFF00FF;
+const WINDOW_BOUNDS_COLOR: u32 = 0xFF0000;
+const TILE_GRID_COLOR: u32 = 0x0080FF;
+
+/// 3x5 glyphs for the digits 0-9, used to label sprites with their OAM
+/// index. Each row is 3 bits, most significant bit on the left.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// Layers drawn by the PPU. Each can be switched off at runtime to help
+/// track down graphical glitches. This only changes what is drawn to the
+/// frame, the emulated hardware behaves exactly the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    Background,
+    Window,
+    Sprites,
+}
+
+/// Debugging information which can be drawn on top of the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overlay {
+    /// Outline of every visible sprite, labelled with its OAM index.
+    SpriteBounds,
+    /// Left and top edges of the window.
+    WindowBounds,
+    /// Lines between background tiles, following the scroll registers.
+    TileGrid,
+}
+
+/// Runtime switches for layers and overlays. All layers are drawn and no
+/// overlays are drawn by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugOptions {
+    background: bool,
+    window: bool,
+    sprites: bool,
+    sprite_bounds: bool,
+    window_bounds: bool,
+    tile_grid: bool,
+}
+
+impl Default for DebugOptions {
+    fn default() -> Self {
+        Self {
+            background: true,
+            window: true,
+            sprites: true,
+            sprite_bounds: false,
+            window_bounds: false,
+            tile_grid: false,
+        }
+    }
+}
+
+impl DebugOptions {
+    pub fn layer_enabled(&self, layer: Layer) -> bool {
+        match layer {
+            Layer::Background => self.background,
+            Layer::Window => self.window,
+            Layer::Sprites => self.sprites,
+        }
+    }
+
+    pub fn set_layer_enabled(&mut self, layer: Layer, enabled: bool) {
+        match layer {
+            Layer::Background => self.background = enabled,
+            Layer::Window => self.window = enabled,
+            Layer::Sprites => self.sprites = enabled,
+        }
+    }
+
+    pub fn overlay_enabled(&self, overlay: Overlay) -> bool {
+        match overlay {
+            Overlay::SpriteBounds => self.sprite_bounds,
+            Overlay::WindowBounds => self.window_bounds,
+            Overlay::TileGrid => self.tile_grid,
+        }
+    }
+
+    pub fn set_overlay_enabled(&mut self, overlay: Overlay, enabled: bool) {
+        match overlay {
+            Overlay::SpriteBounds => self.sprite_bounds = enabled,
+            Overlay::WindowBounds => self.window_bounds = enabled,
+            Overlay::TileGrid => self.tile_grid = enabled,
+        }
+    }
+}
+
+impl PPU {
+    /// Is the given layer currently being drawn.
+    pub fn layer_enabled(&self, layer: Layer) -> bool {
+        self.debug_options.layer_enabled(layer)
+    }
+
+    /// Switch drawing of a layer on or off.
+    pub fn set_layer_enabled(&mut self, layer: Layer, enabled: bool) {
+        self.debug_options.set_layer_enabled(layer, enabled)
+    }
+
+    /// Is the given overlay currently being drawn.
+    pub fn overlay_enabled(&self, overlay: Overlay) -> bool {
+        self.debug_options.overlay_enabled(overlay)
+    }
+
+    /// Switch drawing of an overlay on or off.
+    pub fn set_overlay_enabled(&mut self, overlay: Overlay, enabled: bool) {
+        self.debug_options.set_overlay_enabled(overlay, enabled)
+    }
+
+    /// Draw the enabled overlays into the completed frame, using the state of
+    /// OAM and the scroll/window registers at the end of the frame.
+    pub(super) fn draw_overlays(&mut self) {
+        if self.debug_options.tile_grid {
+            self.draw_tile_grid();
+        }
+
+        if self.debug_options.window_bounds {
+            self.draw_window_bounds();
+        }
+
+        if self.debug_options.sprite_bounds {
+            self.draw_sprite_bounds();
+        }
+    }
+
+    fn draw_tile_grid(&mut self) {
+        let scx = self.background_viewport.scx as usize;
+        let scy = self.background_viewport.scy as usize;
+
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                if (x + scx).is_multiple_of(8) || (y + scy).is_multiple_of(8) {
+                    self.overlay_pixel(x as i16, y as i16, TILE_GRID_COLOR);
+                }
+            }
+        }
+    }
+
+    fn draw_window_bounds(&mut self) {
+        if !self.lcdc.window_enabled() {
+            return;
+        }
+
+        let left = self.window_position.wx as i16 - WINDOW_X_OFFSET as i16;
+        let top = self.window_position.wy as i16;
+
+        for y in top..HEIGHT as i16 {
+            self.overlay_pixel(left, y, WINDOW_BOUNDS_COLOR);
+        }
+
+        for x in left..WIDTH as i16 {
+            self.overlay_pixel(x, top, WINDOW_BOUNDS_COLOR);
+        }
+    }
+
+    fn draw_sprite_bounds(&mut self) {
+        let height = self.lcdc.sprite_size().height() as i16;
+
+        for index in 0..40 {
+            let sprite = self.oam.sprite_at(index);
+            let (left, top) = (sprite.x(), sprite.y());
+            let (right, bottom) = (left + 7, top + height - 1);
+
+            if right < 0 || left >= WIDTH as i16 || bottom < 0 || top >= HEIGHT as i16 {
+                continue;
+            }
+
+            for x in left..=right {
+                self.overlay_pixel(x, top, SPRITE_BOUNDS_COLOR);
+                self.overlay_pixel(x, bottom, SPRITE_BOUNDS_COLOR);
+            }
+
+            for y in top..=bottom {
+                self.overlay_pixel(left, y, SPRITE_BOUNDS_COLOR);
+                self.overlay_pixel(right, y, SPRITE_BOUNDS_COLOR);
+            }
+
+            self.draw_number(index, left + 1, top + 1);
+        }
+    }
+
+    /// Draw a number using the 3x5 digit glyphs, with the top left corner of
+    /// the first digit at the given coordinates.
+    fn draw_number(&mut self, number: u8, x: i16, y: i16) {
+        let digits = if number >= 10 {
+            vec![number / 10, number % 10]
+        } else {
+            vec![number]
+        };
+
+        for (position, digit) in digits.into_iter().enumerate() {
+            let glyph = DIGITS[digit as usize];
+            let origin = x + position as i16 * 4;
+
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) != 0 {
+                        self.overlay_pixel(origin + column, y + row as i16, SPRITE_INDEX_COLOR);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Set a pixel in the frame buffer, ignoring anything off screen.
+    fn overlay_pixel(&mut self, x: i16, y: i16, value: u32) {
+        if (0..WIDTH as i16).contains(&x) && (0..HEIGHT as i16).contains(&y) {
+            self.buffer[y as usize * WIDTH + x as usize] = value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_options() {
+        let options = DebugOptions::default();
+
+        assert!(options.layer_enabled(Layer::Background));
+        assert!(options.layer_enabled(Layer::Window));
+        assert!(options.layer_enabled(Layer::Sprites));
+        assert!(!options.overlay_enabled(Overlay::SpriteBounds));
+        assert!(!options.overlay_enabled(Overlay::WindowBounds));
+        assert!(!options.overlay_enabled(Overlay::TileGrid));
+    }
+
+    #[test]
+    fn toggle_options() {
+        let mut options = DebugOptions::default();
+
+        options.set_layer_enabled(Layer::Window, false);
+        options.set_overlay_enabled(Overlay::TileGrid, true);
+
+        assert!(!options.layer_enabled(Layer::Window));
+        assert!(options.layer_enabled(Layer::Background));
+        assert!(options.overlay_enabled(Overlay::TileGrid));
+        assert!(!options.overlay_enabled(Overlay::SpriteBounds));
+    }
+
+    #[test]
+    fn tile_grid_follows_scroll() {
+        let mut ppu = PPU::new(Arc::new(TestRenderer));
+        ppu.write_background_viewport(ViewportRegister::Scx, 3);
+        ppu.set_overlay_enabled(Overlay::TileGrid, true);
+
+        ppu.draw_overlays();
+
+        assert_eq!(ppu.buffer[5], TILE_GRID_COLOR);
+        assert_eq!(ppu.buffer[WIDTH + 13], TILE_GRID_COLOR);
+        assert_ne!(ppu.buffer[WIDTH + 4], TILE_GRID_COLOR);
+        assert_eq!(ppu.buffer[8 * WIDTH + 4], TILE_GRID_COLOR);
+    }
+
+    #[test]
+    fn window_bounds() {
+        let mut ppu = PPU::new(Arc::new(TestRenderer));
+        ppu.write_lcdc(0x20);
+        ppu.write_window_position(WindowPositionRegister::WX, 17);
+        ppu.write_window_position(WindowPositionRegister::WY, 20);
+        ppu.set_overlay_enabled(Overlay::WindowBounds, true);
+
+        ppu.draw_overlays();
+
+        assert_eq!(ppu.buffer[20 * WIDTH + 10], WINDOW_BOUNDS_COLOR);
+        assert_eq!(ppu.buffer[20 * WIDTH + 159], WINDOW_BOUNDS_COLOR);
+        assert_eq!(ppu.buffer[143 * WIDTH + 10], WINDOW_BOUNDS_COLOR);
+        assert_ne!(ppu.buffer[19 * WIDTH + 10], WINDOW_BOUNDS_COLOR);
+        assert_ne!(ppu.buffer[21 * WIDTH + 11], WINDOW_BOUNDS_COLOR);
+    }
+
+    #[test]
+    fn sprite_bounds_with_index() {
+        let mut ppu = PPU::new(Arc::new(TestRenderer));
+        // Sprite 1 at (10, 20), all other sprites are off screen at (-8, -16)
+        ppu.write_oam(4, 36);
+        ppu.write_oam(5, 18);
+        ppu.set_overlay_enabled(Overlay::SpriteBounds, true);
+
+        ppu.draw_overlays();
+
+        let at = |x: usize, y: usize| ppu.buffer[y * WIDTH + x];
+        assert_eq!(at(10, 20), SPRITE_BOUNDS_COLOR);
+        assert_eq!(at(17, 20), SPRITE_BOUNDS_COLOR);
+        assert_eq!(at(10, 27), SPRITE_BOUNDS_COLOR);
+        assert_eq!(at(17, 27), SPRITE_BOUNDS_COLOR);
+        assert_ne!(at(10, 28), SPRITE_BOUNDS_COLOR);
+        // Top of the "1" glyph
+        assert_eq!(at(12, 21), SPRITE_INDEX_COLOR);
+        assert_ne!(at(11, 21), SPRITE_INDEX_COLOR);
+    }
+}
@@ -0,0 +1,442 @@
+use super::*;
+
+/// Tile sheet is laid out 16 tiles wide, 24 tiles tall (384 tiles).
+const TILE_SHEET_COLUMNS: usize = 16;
+const TILE_COUNT: usize = 384;
+const MAP_SIZE: usize = 256;
+/// OAM view is laid out 8 sprites wide, 5 sprites tall (40 sprites). Each
+/// cell is big enough to hold a tall (8x16) sprite.
+const OAM_COLUMNS: usize = 8;
+const OAM_CELL_WIDTH: usize = 8;
+const OAM_CELL_HEIGHT: usize = 16;
+const SWATCH_SIZE: usize = 8;
+/// Color used to outline the viewport on the background map views. Chosen
+/// so it stands out against any 4 shade palette.
+const VIEWPORT_OUTLINE: u32 = 0xFF0000;
+
+/// An image produced by one of the PPU debugging views. Pixels are in the
+/// same format as the frames sent to the [Renderer], i.e. colours from the
+/// current [Palette].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl DebugImage {
+    fn new(width: usize, height: usize, fill: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![fill; width * height],
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, value: u32) {
+        self.pixels[y * self.width + x] = value;
+    }
+
+    /// Pixel value at the given x, y coordinate.
+    pub fn pixel(&self, x: usize, y: usize) -> u32 {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Convert the image to RGBA bytes. Assumes the pixels are encoded as
+    /// 0x00RRGGBB (as they are with every [Palette]), every
+    /// pixel is fully opaque.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|pixel| {
+                let [_, r, g, b] = pixel.to_be_bytes();
+                [r, g, b, 0xFF]
+            })
+            .collect()
+    }
+}
+
+/// Decoded entry of the OAM table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OamEntry {
+    /// Index of the sprite in OAM (0-39)
+    pub index: u8,
+    /// X position on screen, i.e. with the 8 pixel offset removed.
+    pub x: i16,
+    /// Y position on screen, i.e. with the 16 pixel offset removed.
+    pub y: i16,
+    pub tile_number: u8,
+    pub flags: SpriteFlags,
+}
+
+/// The colors currently assigned to each shade by the palette registers.
+/// Index 0 of the sprite palettes is always transparent, it's reported as
+/// the color the register would assign to it anyway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palettes {
+    pub background: [Color; 4],
+    pub sprite_0: [Color; 4],
+    pub sprite_1: [Color; 4],
+}
+
+/// Bundle of all the debugging views, captured at the same point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugViews {
+    pub tile_sheet: DebugImage,
+    pub background_map_0: DebugImage,
+    pub background_map_1: DebugImage,
+    pub oam_table: Vec<OamEntry>,
+    pub oam: DebugImage,
+    pub palettes: Palettes,
+    pub palette_swatches: DebugImage,
+}
+
+impl PPU {
+    /// Capture all of the debugging views.
+    pub fn debug_views(&self) -> DebugViews {
+        DebugViews {
+            tile_sheet: self.tile_sheet(),
+            background_map_0: self.background_map_view(BGMapSelection::Map0),
+            background_map_1: self.background_map_view(BGMapSelection::Map1),
+            oam_table: self.oam_table(),
+            oam: self.oam_view(),
+            palettes: self.palettes(),
+            palette_swatches: self.palette_view(),
+        }
+    }
+
+    /// Render all 384 tiles in tile data as a 128x192 image. Tiles are drawn
+    /// in order, 16 per row, using raw shades rather than a palette so the
+    /// tile data can be seen even if the palettes are all one color.
+    pub fn tile_sheet(&self) -> DebugImage {
+        let rows = TILE_COUNT / TILE_SHEET_COLUMNS;
+        let mut image = DebugImage::new(TILE_SHEET_COLUMNS * 8, rows * 8, 0);
+
+        for index in 0..TILE_COUNT {
+            let tile = self.tiledata.tile_by_index(index);
+            let origin_x = (index % TILE_SHEET_COLUMNS) * 8;
+            let origin_y = (index / TILE_SHEET_COLUMNS) * 8;
+
+            for y in 0..8 {
+                for x in 0..8 {
+                    let color = raw_color(tile.pixel_at(x, y));
+                    image.set(
+                        origin_x + x as usize,
+                        origin_y + y as usize,
+                        self.palette.color(PixelSource::Background, color),
+                    );
+                }
+            }
+        }
+
+        image
+    }
+
+    /// Render one of the full 256x256 background maps using the current
+    /// addressing method and background palette. The 160x144 viewport
+    /// (SCX, SCY) is outlined, wrapping around the edges of the map.
+    pub fn background_map_view(&self, selection: BGMapSelection) -> DebugImage {
+        let map = match selection {
+            BGMapSelection::Map0 => &self.bg_map0,
+            BGMapSelection::Map1 => &self.bg_map1,
+        };
+        let addressing_method = self.lcdc.addressing_method();
+        let mut image = DebugImage::new(MAP_SIZE, MAP_SIZE, 0);
+
+        for y in 0..MAP_SIZE {
+            for x in 0..MAP_SIZE {
+                let tile_number = map.tile_number_at(x as u8, y as u8);
+                let tile = self.tiledata.tile_at(tile_number, addressing_method);
+                let pixel = tile.pixel_at(x as u8 % 8, y as u8 % 8);
+                let color = self.background_palette.color_from_pixel(pixel);
+
+                image.set(
+                    x,
+                    y,
+                    self.palette.color(PixelSource::Background, color.into()),
+                );
+            }
+        }
+
+        let scx = self.background_viewport.scx as usize;
+        let scy = self.background_viewport.scy as usize;
+
+        for x in 0..WIDTH {
+            image.set((scx + x) % MAP_SIZE, scy, VIEWPORT_OUTLINE);
+            image.set(
+                (scx + x) % MAP_SIZE,
+                (scy + HEIGHT - 1) % MAP_SIZE,
+                VIEWPORT_OUTLINE,
+            );
+        }
+
+        for y in 0..HEIGHT {
+            image.set(scx, (scy + y) % MAP_SIZE, VIEWPORT_OUTLINE);
+            image.set(
+                (scx + WIDTH - 1) % MAP_SIZE,
+                (scy + y) % MAP_SIZE,
+                VIEWPORT_OUTLINE,
+            );
+        }
+
+        image
+    }
+
+    /// Decode all 40 entries in OAM.
+    pub fn oam_table(&self) -> Vec<OamEntry> {
+        (0..40)
+            .map(|index| {
+                let sprite = self.oam.sprite_at(index);
+
+                OamEntry {
+                    index,
+                    x: sprite.x(),
+                    y: sprite.y(),
+                    tile_number: sprite.tile_number,
+                    flags: sprite.flags,
+                }
+            })
+            .collect()
+    }
+
+    /// Render every sprite in OAM into a grid, 8 sprites per row, in OAM
+    /// order. Each sprite is drawn with its own flags and palette using the
+    /// current sprite size, transparent pixels are left white.
+    pub fn oam_view(&self) -> DebugImage {
+        let rows = 40 / OAM_COLUMNS;
+        let sprite_size = self.lcdc.sprite_size();
+        let mut image = DebugImage::new(
+            OAM_COLUMNS * OAM_CELL_WIDTH,
+            rows * OAM_CELL_HEIGHT,
+            self.palette.color(PixelSource::Background, Color::White),
+        );
+
+        for index in 0..40 {
+            let sprite = self.oam.sprite_at(index as u8);
+            let tile = self
+                .tiledata
+                .sprite_tile_at(sprite.tile_number, sprite_size);
+            let palette = self.sprite_palette(sprite.flags);
+            let source = PixelSource::Sprite(sprite.flags.palette_number());
+            let origin_x = (index % OAM_COLUMNS) * OAM_CELL_WIDTH;
+            let origin_y = (index / OAM_COLUMNS) * OAM_CELL_HEIGHT;
+
+            for y in 0..sprite_size.height() {
+                for x in 0..8 {
+                    let pixel = tile.pixel_at(x, y, sprite.flags);
+
+                    if pixel == Pixel::Color0 {
+                        continue;
+                    }
+
+                    let color = palette.color_from_pixel(pixel);
+                    image.set(
+                        origin_x + x as usize,
+                        origin_y + y as usize,
+                        self.palette.color(source, color.into()),
+                    );
+                }
+            }
+        }
+
+        image
+    }
+
+    /// The colors currently assigned by BGP, OBP0 and OBP1.
+    pub fn palettes(&self) -> Palettes {
+        let pixels = [Pixel::Color0, Pixel::Color1, Pixel::Color2, Pixel::Color3];
+
+        Palettes {
+            background: pixels.map(|pixel| self.background_palette.color_from_pixel(pixel).into()),
+            sprite_0: pixels.map(|pixel| self.sprite_palette_0.color_at(pixel)),
+            sprite_1: pixels.map(|pixel| self.sprite_palette_1.color_at(pixel)),
+        }
+    }
+
+    /// Render the current palettes as swatches, one row each for BGP, OBP0
+    /// and OBP1, from color 0 to color 3.
+    pub fn palette_view(&self) -> DebugImage {
+        let palettes = self.palettes();
+        let rows = [
+            (PixelSource::Background, palettes.background),
+            (PixelSource::Sprite(PaletteNumber::OBP0), palettes.sprite_0),
+            (PixelSource::Sprite(PaletteNumber::OBP1), palettes.sprite_1),
+        ];
+        let mut image = DebugImage::new(4 * SWATCH_SIZE, rows.len() * SWATCH_SIZE, 0);
+
+        for (row, (source, colors)) in rows.iter().enumerate() {
+            for (column, color) in colors.iter().enumerate() {
+                let value = self.palette.color(*source, *color);
+
+                for y in 0..SWATCH_SIZE {
+                    for x in 0..SWATCH_SIZE {
+                        image.set(column * SWATCH_SIZE + x, row * SWATCH_SIZE + y, value);
+                    }
+                }
+            }
+        }
+
+        image
+    }
+}
+
+/// Map a pixel straight to a shade, without going through a palette.
+fn raw_color(pixel: Pixel) -> Color {
+    match pixel {
+        Pixel::Color0 => Color::White,
+        Pixel::Color1 => Color::LightGray,
+        Pixel::Color2 => Color::DarkGray,
+        Pixel::Color3 => Color::Black,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: u32 = 0xFFFFFF;
+    const LIGHT_GRAY: u32 = 0xA8A8A8;
+    const DARK_GRAY: u32 = 0x454545;
+    const BLACK: u32 = 0;
+
+    fn ppu() -> PPU {
+        PPU::new(Arc::new(TestRenderer))
+    }
+
+    #[test]
+    fn tile_sheet_layout() {
+        let mut ppu = ppu();
+        // First row of tile 17 is color 1, last tile (383) is all color 3.
+        ppu.write_tiledata(17 * 16, 0xFF);
+        for addr in 383 * 16..384 * 16 {
+            ppu.write_tiledata(addr, 0xFF);
+        }
+
+        let sheet = ppu.tile_sheet();
+
+        assert_eq!((sheet.width, sheet.height), (128, 192));
+        assert_eq!(sheet.pixel(8, 8), LIGHT_GRAY);
+        assert_eq!(sheet.pixel(15, 8), LIGHT_GRAY);
+        assert_eq!(sheet.pixel(8, 9), WHITE);
+        assert_eq!(sheet.pixel(127, 191), BLACK);
+        assert_eq!(sheet.pixel(120, 184), BLACK);
+        assert_eq!(sheet.pixel(119, 184), WHITE);
+    }
+
+    #[test]
+    fn background_map_outlines_viewport() {
+        let mut ppu = ppu();
+        ppu.write_background_palette(0b11_10_01_00);
+        ppu.write_background_viewport(ViewportRegister::Scx, 200);
+        ppu.write_background_viewport(ViewportRegister::Scy, 10);
+
+        let map = ppu.background_map_view(BGMapSelection::Map0);
+
+        assert_eq!((map.width, map.height), (256, 256));
+        assert_eq!(map.pixel(200, 10), VIEWPORT_OUTLINE);
+        assert_eq!(map.pixel(255, 10), VIEWPORT_OUTLINE);
+        // Right hand edge wraps around: (200 + 159) % 256
+        assert_eq!(map.pixel(103, 50), VIEWPORT_OUTLINE);
+        assert_eq!(map.pixel(0, 153), VIEWPORT_OUTLINE);
+        assert_eq!(map.pixel(150, 50), WHITE);
+        assert_eq!(map.pixel(210, 50), WHITE);
+    }
+
+    #[test]
+    fn background_map_uses_palette_and_addressing() {
+        let mut ppu = ppu();
+        ppu.write_lcdc(0x10);
+        ppu.write_background_palette(0b00_00_11_00);
+        ppu.write_tiledata(5 * 16, 0xFF);
+        ppu.write_bg_map(BGMapSelection::Map1, 33, 5);
+
+        let map = ppu.background_map_view(BGMapSelection::Map1);
+
+        assert_eq!(map.pixel(8, 8), BLACK);
+        assert_eq!(map.pixel(8, 9), WHITE);
+    }
+
+    #[test]
+    fn oam_table_decodes_sprites() {
+        let mut ppu = ppu();
+        ppu.write_oam(4, 20);
+        ppu.write_oam(5, 10);
+        ppu.write_oam(6, 0x42);
+        ppu.write_oam(7, 0b1011_0000);
+
+        let table = ppu.oam_table();
+
+        assert_eq!(table.len(), 40);
+        let entry = table[1];
+        assert_eq!(entry.index, 1);
+        assert_eq!((entry.x, entry.y), (2, 4));
+        assert_eq!(entry.tile_number, 0x42);
+        assert!(entry.flags.bg_priority());
+        assert!(!entry.flags.y_flip());
+        assert!(entry.flags.x_flip());
+        assert_eq!(entry.flags.palette_number(), PaletteNumber::OBP1);
+    }
+
+    #[test]
+    fn oam_view_draws_sprites_with_their_palette() {
+        let mut ppu = ppu();
+        ppu.write_sprite_palette(SpritePaletteSelection::Palette1, 0b10_00_00_00);
+        ppu.write_tiledata(3 * 16, 0xFF);
+        ppu.write_tiledata(3 * 16 + 1, 0xFF);
+        ppu.write_oam(9 * 4 + 2, 3);
+        ppu.write_oam(9 * 4 + 3, 0b0001_0000);
+
+        let view = ppu.oam_view();
+
+        assert_eq!((view.width, view.height), (64, 80));
+        assert_eq!(view.pixel(8, 16), DARK_GRAY);
+        assert_eq!(view.pixel(8, 17), WHITE);
+    }
+
+    #[test]
+    fn palettes() {
+        let mut ppu = ppu();
+        ppu.write_background_palette(0b11_10_01_00);
+        ppu.write_sprite_palette(SpritePaletteSelection::Palette0, 0b00_01_10_11);
+
+        let palettes = ppu.palettes();
+
+        assert_eq!(
+            palettes.background,
+            [
+                Color::White,
+                Color::LightGray,
+                Color::DarkGray,
+                Color::Black
+            ]
+        );
+        assert_eq!(
+            palettes.sprite_0,
+            [
+                Color::Black,
+                Color::DarkGray,
+                Color::LightGray,
+                Color::White
+            ]
+        );
+
+        let swatches = ppu.palette_view();
+        assert_eq!(swatches.pixel(0, 0), WHITE);
+        assert_eq!(swatches.pixel(31, 7), BLACK);
+        assert_eq!(swatches.pixel(0, 8), BLACK);
+    }
+
+    #[test]
+    fn to_rgba() {
+        let image = DebugImage {
+            width: 2,
+            height: 1,
+            pixels: vec![0x123456, 0xABCDEF],
+        };
+
+        assert_eq!(
+            image.to_rgba(),
+            vec![0x12, 0x34, 0x56, 0xFF, 0xAB, 0xCD, 0xEF, 0xFF]
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::*;
+
+/// A [Renderer] which keeps the most recently completed frame so that a
+/// frontend on another thread can display it.
+///
+/// Frames are triple buffered: the emulator thread copies each frame into a
+/// back buffer, which is then swapped with the pending buffer. Readers swap
+/// the pending buffer with the front buffer when a new frame is available.
+/// Locks are only held for as long as it takes to copy or swap buffers, so
+/// neither side waits on the other and readers never see a partially
+/// written frame.
+pub struct FrameBuffer {
+    back: Mutex<StoredFrame>,
+    pending: Mutex<Pending>,
+    front: Mutex<StoredFrame>,
+}
+
+struct Pending {
+    frame: StoredFrame,
+    fresh: bool,
+}
+
+struct StoredFrame {
+    pixels: Vec<u32>,
+    width: usize,
+    height: usize,
+    indexed: Option<Box<[IndexedPixel; WIDTH * HEIGHT]>>,
+    number: u64,
+    timestamp: Duration,
+}
+
+impl StoredFrame {
+    fn new() -> Self {
+        Self {
+            pixels: vec![0; WIDTH * HEIGHT],
+            width: WIDTH,
+            height: HEIGHT,
+            indexed: None,
+            number: 0,
+            timestamp: Duration::ZERO,
+        }
+    }
+
+    fn frame(&self) -> Frame<'_> {
+        Frame {
+            pixels: &self.pixels,
+            width: self.width,
+            height: self.height,
+            indexed: self.indexed.as_deref(),
+            number: self.number,
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+impl FrameBuffer {
+    pub fn new() -> Self {
+        Self {
+            back: Mutex::new(StoredFrame::new()),
+            pending: Mutex::new(Pending {
+                frame: StoredFrame::new(),
+                fresh: false,
+            }),
+            front: Mutex::new(StoredFrame::new()),
+        }
+    }
+
+    /// Call the given function with the most recently completed frame. Until
+    /// the first frame is rendered this is a black 160x144 frame numbered 0.
+    pub fn with_latest<T>(&self, f: impl FnOnce(Frame) -> T) -> T {
+        let mut front = self.front.lock().expect("Should lock front buffer");
+
+        {
+            let mut pending = self.pending.lock().expect("Should lock pending buffer");
+            if pending.fresh {
+                std::mem::swap(&mut *front, &mut pending.frame);
+                pending.fresh = false;
+            }
+        }
+
+        f(front.frame())
+    }
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer for FrameBuffer {
+    fn render(&self, frame: &Frame) {
+        let mut back = self.back.lock().expect("Should lock back buffer");
+
+        back.pixels.clear();
+        back.pixels.extend_from_slice(frame.pixels);
+        back.width = frame.width;
+        back.height = frame.height;
+        match frame.indexed {
+            Some(indexed) => {
+                let stored = back
+                    .indexed
+                    .get_or_insert_with(|| Box::new([IndexedPixel::BLANK; WIDTH * HEIGHT]));
+                stored.copy_from_slice(indexed);
+            }
+            None => back.indexed = None,
+        }
+        back.number = frame.number;
+        back.timestamp = frame.timestamp;
+
+        let mut pending = self.pending.lock().expect("Should lock pending buffer");
+        std::mem::swap(&mut *back, &mut pending.frame);
+        pending.fresh = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn frame_filled_with(value: u32, number: u64) -> ([u32; WIDTH * HEIGHT], u64) {
+        ([value; WIDTH * HEIGHT], number)
+    }
+
+    fn render(buffer: &FrameBuffer, (pixels, number): ([u32; WIDTH * HEIGHT], u64)) {
+        buffer.render(&Frame {
+            pixels: &pixels,
+            width: WIDTH,
+            height: HEIGHT,
+            indexed: None,
+            number,
+            timestamp: Duration::from_millis(number * 16),
+        });
+    }
+
+    #[test]
+    fn empty_before_first_frame() {
+        let buffer = FrameBuffer::new();
+
+        buffer.with_latest(|frame| {
+            assert_eq!(frame.number, 0);
+            assert!(frame.pixels.iter().all(|&pixel| pixel == 0));
+        });
+    }
+
+    #[test]
+    fn reads_latest_frame() {
+        let buffer = FrameBuffer::new();
+
+        render(&buffer, frame_filled_with(1, 1));
+        render(&buffer, frame_filled_with(2, 2));
+
+        buffer.with_latest(|frame| {
+            assert_eq!(frame.number, 2);
+            assert_eq!(frame.timestamp, Duration::from_millis(32));
+            assert!(frame.pixels.iter().all(|&pixel| pixel == 2));
+        });
+    }
+
+    #[test]
+    fn keeps_frame_until_a_new_one_arrives() {
+        let buffer = FrameBuffer::new();
+
+        render(&buffer, frame_filled_with(1, 1));
+        assert_eq!(buffer.with_latest(|frame| frame.number), 1);
+        assert_eq!(buffer.with_latest(|frame| frame.number), 1);
+
+        render(&buffer, frame_filled_with(3, 3));
+        assert_eq!(buffer.with_latest(|frame| frame.number), 3);
+        assert_eq!(buffer.with_latest(|frame| frame.pixels[0]), 3);
+    }
+
+    #[test]
+    fn keeps_indexed_frame() {
+        let buffer = FrameBuffer::new();
+        let pixels = [0; WIDTH * HEIGHT];
+        let mut indexed = [IndexedPixel::BLANK; WIDTH * HEIGHT];
+        indexed[1].source = PixelSource::Window;
+
+        buffer.render(&Frame {
+            pixels: &pixels,
+            width: WIDTH,
+            height: HEIGHT,
+            indexed: Some(&indexed),
+            number: 1,
+            timestamp: Duration::ZERO,
+        });
+
+        buffer.with_latest(|frame| {
+            let stored = frame.indexed.expect("Should keep indexed frame");
+            assert_eq!(stored[0], IndexedPixel::BLANK);
+            assert_eq!(stored[1].source, PixelSource::Window);
+        });
+
+        render(&buffer, frame_filled_with(2, 2));
+        buffer.with_latest(|frame| assert!(frame.indexed.is_none()));
+    }
+
+    #[test]
+    fn frames_are_never_torn() {
+        let buffer = Arc::new(FrameBuffer::new());
+        let writer = buffer.clone();
+
+        let handle = std::thread::spawn(move || {
+            for number in 1..=500 {
+                render(&writer, frame_filled_with(number as u32, number));
+            }
+        });
+
+        let mut last = 0;
+        while !handle.is_finished() {
+            buffer.with_latest(|frame| {
+                let value = frame.number as u32;
+                assert!(frame.pixels.iter().all(|&pixel| pixel == value));
+                assert!(frame.number >= last);
+                last = frame.number;
+            });
+        }
+
+        handle.join().expect("Should join writer");
+        assert_eq!(buffer.with_latest(|frame| frame.number), 500);
+    }
+}
@@ -0,0 +1,155 @@
+use super::*;
+
+/// Where a pixel in the frame came from.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PixelSource {
+    Background,
+    Window,
+    /// A sprite, drawn with the given sprite palette (OBP0 or OBP1).
+    Sprite(PaletteNumber),
+}
+
+/// A pixel of the frame before it's converted to an output colour with
+/// [Palette::color]. Holds the colour number from the tile data along
+/// with the shade it was mapped to by the palette register at the time it
+/// was drawn.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct IndexedPixel {
+    pub pixel: Pixel,
+    pub color: Color,
+    pub source: PixelSource,
+}
+
+impl IndexedPixel {
+    /// Background colour 0 with a white palette entry, as shown when the
+    /// LCD is blank or the background is disabled.
+    pub const BLANK: IndexedPixel = IndexedPixel {
+        pixel: Pixel::Color0,
+        color: Color::White,
+        source: PixelSource::Background,
+    };
+}
+
+impl PPU {
+    /// Also produce an indexed copy of every frame, passed to the renderer
+    /// as [Frame::indexed]. Off by default, to save the extra work when
+    /// only colour frames are needed.
+    pub fn set_indexed_output(&mut self, enabled: bool) {
+        self.indexed = enabled.then(|| Box::new([IndexedPixel::BLANK; WIDTH * HEIGHT]));
+    }
+
+    /// Is an indexed copy of every frame being produced.
+    pub fn indexed_output_enabled(&self) -> bool {
+        self.indexed.is_some()
+    }
+
+    /// Write a pixel to the frame, and to the indexed frame if enabled.
+    pub(super) fn write_pixel(
+        &mut self,
+        idx: usize,
+        pixel: Pixel,
+        color: Color,
+        source: PixelSource,
+    ) {
+        self.buffer[idx] = self.palette.color(source, color);
+
+        if let Some(sgb) = self.sgb.as_mut() {
+            sgb.write_shade(idx, color);
+        }
+
+        if let Some(indexed) = self.indexed.as_mut() {
+            indexed[idx] = IndexedPixel {
+                pixel,
+                color,
+                source,
+            };
+        }
+    }
+
+    /// Blank the whole frame, and the indexed frame if enabled.
+    pub(super) fn blank_buffer(&mut self) {
+        self.buffer = [self.palette.color(PixelSource::Background, Color::White); WIDTH * HEIGHT];
+
+        if let Some(sgb) = self.sgb.as_mut() {
+            sgb.fill_shade(Color::White);
+        }
+
+        if let Some(indexed) = self.indexed.as_mut() {
+            indexed.fill(IndexedPixel::BLANK);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct IndexedRenderer(Mutex<Vec<IndexedPixel>>);
+
+    impl Renderer for IndexedRenderer {
+        fn render(&self, frame: &Frame) {
+            let indexed = frame.indexed.expect("Should have indexed frame");
+            *self.0.lock().unwrap() = indexed.to_vec();
+        }
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let mut ppu = PPU::new(Arc::new(TestRenderer));
+        assert!(!ppu.indexed_output_enabled());
+
+        ppu.set_indexed_output(true);
+        assert!(ppu.indexed_output_enabled());
+    }
+
+    #[test]
+    fn indexed_frame_records_layers() {
+        let renderer = Arc::new(IndexedRenderer::default());
+        let mut ppu = PPU::new(renderer.clone());
+        ppu.set_indexed_output(true);
+        // LCD, window (map 1), sprites and BG enabled
+        ppu.write_lcdc(0xF3);
+        ppu.write_background_palette(0b11_10_01_00);
+        ppu.write_sprite_palette(SpritePaletteSelection::Palette1, 0b00_01_10_11);
+        ppu.write_window_position(WindowPositionRegister::WX, 7 + 80);
+
+        // Tile 1 is solid colour 1, used by the window and a sprite.
+        for row in 0..8 {
+            ppu.write_tiledata(16 + row * 2, 0xFF);
+        }
+        ppu.write_bg_map(BGMapSelection::Map1, 0, 1);
+
+        // Sprite 0 at (40, 0) using OBP1
+        ppu.write_oam(0, 16);
+        ppu.write_oam(1, 48);
+        ppu.write_oam(2, 1);
+        ppu.write_oam(3, 0x10);
+
+        // The first frame after the LCD is switched on is blank.
+        for _ in 0..(2 * 17556) {
+            ppu.step(1);
+        }
+
+        let indexed = renderer.0.lock().unwrap();
+        assert_eq!(indexed[0], IndexedPixel::BLANK);
+        assert_eq!(
+            indexed[40],
+            IndexedPixel {
+                pixel: Pixel::Color1,
+                color: Color::DarkGray,
+                source: PixelSource::Sprite(PaletteNumber::OBP1),
+            }
+        );
+        assert_eq!(
+            indexed[80],
+            IndexedPixel {
+                pixel: Pixel::Color1,
+                color: Color::LightGray,
+                source: PixelSource::Window,
+            }
+        );
+    }
+}
@@ -90,6 +90,14 @@ impl LCDControl {
 
         !is_new_lcd_enable && self.lcd_enable()
     }
+
+    /// Returns true when a new write to the LCDC buffer (value)
+    /// would cause the LCD enable bit to be toggled on.
+    pub fn is_lcd_enable_toggled_on(&self, value: u8) -> bool {
+        let is_new_lcd_enable = value & 0b10000000 == 128;
+
+        is_new_lcd_enable && !self.lcd_enable()
+    }
 }
 
 #[cfg(test)]
@@ -175,6 +183,18 @@ mod tests {
         assert!(!lcdc.sprites_enabled());
     }
 
+    #[test]
+    fn lcd_enable_toggled() {
+        let mut lcdc = LCDControl::new();
+        assert!(lcdc.is_lcd_enable_toggled_on(0x80));
+        assert!(!lcdc.is_lcd_enable_toggled_off(0x00));
+
+        lcdc.write(0x80);
+        assert!(!lcdc.is_lcd_enable_toggled_on(0x80));
+        assert!(lcdc.is_lcd_enable_toggled_off(0x00));
+        assert!(!lcdc.is_lcd_enable_toggled_off(0x91));
+    }
+
     #[test]
     fn background_and_window_enabled() {
         let mut lcdc = LCDControl::new();
@@ -20,6 +20,7 @@ pub struct Oam([u8; 160]);
 /// only be stored in the first 255 tile data slots.
 /// Byte 3 - Sprite Flags. The last Byte represents bit-flags that alter the
 /// rendering of a sprite. See [SpriteFlags] struct for more details
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Sprite {
     x: u8,
     y: u8,
@@ -130,7 +131,7 @@ impl Oam {
     }
 
     pub fn sprite_at(&self, sprite_number: u8) -> Sprite {
-        if sprite_number > 40 {
+        if sprite_number >= 40 {
             panic!("There are only 40 sprites")
         }
 
@@ -0,0 +1,311 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use super::*;
+
+/// Output colours for the four shades, with separate sets for the
+/// background (and window) and each of the two sprite palettes, so that
+/// sprites can stand out from the background as on the Super Game Boy.
+/// Each set is indexed from [Color::White] to [Color::Black], and holds
+/// values in the 0x00RRGGBB format passed on to the [Renderer].
+///
+/// Palettes can be parsed from a small TOML file. Each set is a list of four
+/// colours, from lightest to darkest, written as `"#RRGGBB"` strings or
+/// `0xRRGGBB` integers. The sprite sets default to the background set.
+///
+/// ```toml
+/// name = "Autumn"
+/// background = ["#D58936", "#A44200", "#69140E", "#3C1518"]
+/// sprite_0 = ["#FFFFFF", "#A44200", "#69140E", "#000000"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub name: Cow<'static, str>,
+    pub background: [u32; 4],
+    pub sprite_0: [u32; 4],
+    pub sprite_1: [u32; 4],
+}
+
+impl Palette {
+    /// Plain shades of grey, the default.
+    pub const GREY: Palette = Palette::uniform("Grey", [0xFFFFFF, 0xA8A8A8, 0x454545, 0x000000]);
+
+    /// Green tint of the original DMG screen.
+    pub const DMG_GREEN: Palette =
+        Palette::uniform("DMG green", [0x9BBC0F, 0x8BAC0F, 0x306230, 0x0F380F]);
+
+    /// Olive greys of the Game Boy Pocket screen.
+    pub const POCKET: Palette =
+        Palette::uniform("Pocket", [0xC4CFA1, 0x8B956D, 0x4D533C, 0x1F1F1F]);
+
+    /// Washed out blue-green of the Game Boy Light backlight.
+    pub const LIGHT: Palette = Palette::uniform("Light", [0x00B581, 0x009A71, 0x00694A, 0x004F3B]);
+
+    /// Warm background with contrasting sprites, in the style of the Super
+    /// Game Boy's built in palettes.
+    pub const SGB: Palette = Palette {
+        name: Cow::Borrowed("SGB"),
+        background: [0xF8E8C8, 0xD89048, 0xA82820, 0x301850],
+        sprite_0: [0xF8F8F8, 0xF8A060, 0x905030, 0x000000],
+        sprite_1: [0xF8F8F8, 0x70C0F8, 0x3058A8, 0x000000],
+    };
+
+    /// Every built in palette, in the order they're cycled through.
+    pub const PRESETS: [Palette; 5] = [
+        Palette::GREY,
+        Palette::DMG_GREEN,
+        Palette::POCKET,
+        Palette::LIGHT,
+        Palette::SGB,
+    ];
+
+    /// A palette using the same colours for the background and sprites.
+    pub const fn uniform(name: &'static str, colors: [u32; 4]) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            background: colors,
+            sprite_0: colors,
+            sprite_1: colors,
+        }
+    }
+
+    /// Find a built in palette by name, ignoring case.
+    pub fn preset(name: &str) -> Option<Palette> {
+        Self::PRESETS
+            .into_iter()
+            .find(|palette| palette.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Output colour for a shade drawn by the given layer.
+    pub fn color(&self, source: PixelSource, color: Color) -> u32 {
+        let set = match source {
+            PixelSource::Background | PixelSource::Window => &self.background,
+            PixelSource::Sprite(PaletteNumber::OBP0) => &self.sprite_0,
+            PixelSource::Sprite(PaletteNumber::OBP1) => &self.sprite_1,
+        };
+
+        set[shade_index(color)]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::GREY
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut name = None;
+        let mut background = None;
+        let mut sprite_0 = None;
+        let mut sprite_1 = None;
+
+        for (number, line) in s.lines().enumerate() {
+            let line = strip_comment(line);
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = |message: &str| format!("Line {}: {message}", number + 1);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected key = value"))?;
+
+            match key.trim() {
+                "name" => name = Some(parse_string(value.trim()).map_err(|e| error(&e))?),
+                "background" => background = Some(parse_colors(value).map_err(|e| error(&e))?),
+                "sprite_0" => sprite_0 = Some(parse_colors(value).map_err(|e| error(&e))?),
+                "sprite_1" => sprite_1 = Some(parse_colors(value).map_err(|e| error(&e))?),
+                key => return Err(error(&format!("unknown key {key}"))),
+            }
+        }
+
+        let background = background.ok_or("Palette needs a background set")?;
+
+        Ok(Palette {
+            name: Cow::Owned(name.unwrap_or_else(|| "Custom".to_string())),
+            background,
+            sprite_0: sprite_0.unwrap_or(background),
+            sprite_1: sprite_1.unwrap_or(background),
+        })
+    }
+}
+
+/// Index of a shade in a palette's colour sets, from 0 for white to 3 for
+/// black.
+pub(super) fn shade_index(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::LightGray => 1,
+        Color::DarkGray => 2,
+        Color::Black => 3,
+    }
+}
+
+/// Convert a 15 bit BGR colour, as used by the CGB and SGB, to 0x00RRGGBB.
+pub(super) fn rgb555(color: u16) -> u32 {
+    let channel = |shift: u16| {
+        let value = ((color >> shift) & 0x1F) as u32;
+        (value * 255 + 15) / 31
+    };
+
+    channel(0) << 16 | channel(5) << 8 | channel(10)
+}
+
+/// Remove a trailing comment, ignoring any '#' inside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return line[..i].trim(),
+            _ => {}
+        }
+    }
+
+    line.trim()
+}
+
+fn parse_string(value: &str) -> Result<String, String> {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .map(str::to_string)
+        .ok_or_else(|| format!("expected a quoted string, found {value}"))
+}
+
+fn parse_colors(value: &str) -> Result<[u32; 4], String> {
+    let list = value
+        .trim()
+        .strip_prefix('[')
+        .and_then(|value| value.strip_suffix(']'))
+        .ok_or_else(|| format!("expected a list of colours, found {}", value.trim()))?;
+
+    let colors = list
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(parse_color)
+        .collect::<Result<Vec<u32>, String>>()?;
+
+    colors
+        .try_into()
+        .map_err(|colors: Vec<u32>| format!("expected 4 colours, found {}", colors.len()))
+}
+
+fn parse_color(value: &str) -> Result<u32, String> {
+    let hex = match value.strip_prefix("0x") {
+        Some(hex) => hex.to_string(),
+        None => parse_string(value)?
+            .strip_prefix('#')
+            .ok_or_else(|| format!("expected a colour like \"#RRGGBB\", found {value}"))?
+            .to_string(),
+    };
+
+    match u32::from_str_radix(&hex, 16) {
+        Ok(color) if hex.len() == 6 => Ok(color),
+        _ => Err(format!("invalid colour {value}")),
+    }
+}
+
+impl PPU {
+    /// Colours the frame is drawn with. Takes effect from the next pixel
+    /// drawn.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_by_layer() {
+        let palette = Palette::SGB;
+
+        assert_eq!(
+            palette.color(PixelSource::Background, Color::White),
+            0xF8E8C8
+        );
+        assert_eq!(palette.color(PixelSource::Window, Color::Black), 0x301850);
+        assert_eq!(
+            palette.color(PixelSource::Sprite(PaletteNumber::OBP0), Color::LightGray),
+            0xF8A060
+        );
+        assert_eq!(
+            palette.color(PixelSource::Sprite(PaletteNumber::OBP1), Color::DarkGray),
+            0x3058A8
+        );
+    }
+
+    #[test]
+    fn converts_rgb555() {
+        assert_eq!(rgb555(0x7FFF), 0xFFFFFF);
+        assert_eq!(rgb555(0x0000), 0x000000);
+        assert_eq!(rgb555(0x32BF), 0xFFAD63);
+        assert_eq!(rgb555(0x7C00), 0x0000FF);
+    }
+
+    #[test]
+    fn find_preset() {
+        assert_eq!(Palette::preset("dmg GREEN"), Some(Palette::DMG_GREEN));
+        assert_eq!(Palette::preset("sgb"), Some(Palette::SGB));
+        assert_eq!(Palette::preset("orange"), None);
+    }
+
+    #[test]
+    fn parse_palette_file() {
+        let palette: Palette = r##"
+            # Colours from lightest to darkest
+            name = "Autumn"
+            background = ["#D58936", "#a44200", "#69140E", "#3C1518"] # BG and window
+            sprite_1 = [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000]
+        "##
+        .parse()
+        .unwrap();
+
+        assert_eq!(palette.name, "Autumn");
+        assert_eq!(palette.background, [0xD58936, 0xA44200, 0x69140E, 0x3C1518]);
+        assert_eq!(palette.sprite_0, palette.background);
+        assert_eq!(palette.sprite_1, [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!("name = \"No colours\"".parse::<Palette>().is_err());
+        assert!("background = [\"#FFFFFF\"]".parse::<Palette>().is_err());
+        assert!("background = [\"#FFFFFG\", 0, 0, 0]"
+            .parse::<Palette>()
+            .is_err());
+        assert!("colours = []".parse::<Palette>().is_err());
+
+        let error = "\n\nbackground".parse::<Palette>().unwrap_err();
+        assert!(error.starts_with("Line 3"), "{error}");
+    }
+
+    #[test]
+    fn frame_drawn_with_palette() {
+        let frames = Arc::new(FrameBuffer::new());
+        let mut ppu = PPU::new(frames.clone());
+        ppu.set_palette(Palette::DMG_GREEN);
+        ppu.write_lcdc(0x91);
+        ppu.write_background_palette(0b11_10_01_00);
+
+        // The first frame after the LCD is switched on is blank.
+        for _ in 0..(2 * 17556) {
+            ppu.step(1);
+        }
+
+        assert_eq!(ppu.palette().name, "DMG green");
+        frames.with_latest(|frame| assert!(frame.pixels.iter().all(|&pixel| pixel == 0x9BBC0F)));
+    }
+}
@@ -1,23 +1,49 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{IndexedPixel, HEIGHT, WIDTH};
+
+/// A completed frame, borrowed from the PPU for the duration of a
+/// [Renderer::render] call. Implementations that need to keep the pixels
+/// around afterwards must copy them (see [super::FrameBuffer]).
+#[derive(Debug, Clone, Copy)]
+pub struct Frame<'a> {
+    /// Pixels in row-major order, as 0x00RRGGBB colours from the PPU's
+    /// [super::Palette].
+    pub pixels: &'a [u32],
+    /// Frames from the PPU are 160x144, or 256x224 with the Super Game Boy
+    /// border, but renderer adapters which scale the image pass larger
+    /// frames on to the renderer they wrap.
+    pub width: usize,
+    pub height: usize,
+    /// The frame before conversion to output colours, only present if
+    /// enabled with [super::PPU::set_indexed_output]. Always 160x144, even
+    /// if the frame has been scaled.
+    pub indexed: Option<&'a [IndexedPixel; WIDTH * HEIGHT]>,
+    /// Number of frames produced since the PPU was created, starting at 0.
+    /// Frames where the LCD is blank (switched off, or the first frame after
+    /// switching it back on) are counted too.
+    pub number: u64,
+    /// Emulated time since the PPU was created, at the point the frame was
+    /// completed.
+    pub timestamp: Duration,
+}
+
 /// Render is implemented to control drawing pixels to some output device.
 /// This [Renderers] render function will be called everytime the PPU enters the
 /// drawing mode. Note the gameboy typically runs at 60FPS it is up to implementation
 /// of the render to control the frame rate. But note the render function should not
 /// be blocked to do so (i.e. some form of threading is required).
 pub trait Renderer: Send + Sync {
-    /// Called with the updated buffer of pixels to be renderd to the device.
-    /// Is called everytime the PPU enters drawing mode.
-    fn render(&self, buffer: [u32; 160 * 144]);
+    /// Called with each completed frame to be rendered to the device. Is
+    /// called everytime the PPU enters VBlank, and when the LCD is switched
+    /// off.
+    fn render(&self, frame: &Frame);
+}
 
-    /// Function that converts a [Color] to a u32 value that can be used to draw
-    /// to the output device. This function has a default implementation that
-    /// returns a hexidecimal value for the color using a basic grey/white palette.
-    fn palette(&self, color: Color) -> u32 {
-        match color {
-            Color::White => 0xFFFFFF,
-            Color::LightGray => 0xA8A8A8,
-            Color::DarkGray => 0x454545,
-            Color::Black => 0,
-        }
+impl<R: Renderer + ?Sized> Renderer for Arc<R> {
+    fn render(&self, frame: &Frame) {
+        (**self).render(frame)
     }
 }
 
@@ -26,7 +52,7 @@ pub struct TestRenderer;
 
 #[cfg(test)]
 impl Renderer for TestRenderer {
-    fn render(&self, _: [u32; 160 * 144]) {}
+    fn render(&self, _: &Frame) {}
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -1,22 +1,65 @@
+use std::time::Duration;
+
 use lcdc_status::PPUMode;
 
 use super::*;
 
-// TODO: double check these
-const HBLANK_CYCLES: u32 = 204 / 4;
-const VBLANK_CYCLES: u32 = 456 / 4;
-const DRAWING_CYCLES: u32 = 172 / 4;
-const OAM_CYCLES: u32 = 80 / 4;
+/// Every scanline takes the same amount of time, regardless of how long
+/// the PPU spends drawing pixels (mode 3). Units are dots (T-cycles).
+const SCANLINE_DOTS: u32 = 456;
+const OAM_DOTS: u32 = 80;
+/// Minimum length of mode 3, without any scroll, window or sprite penalties.
+const DRAWING_DOTS: u32 = 172;
+/// How long a fetched sprite stalls the pixel pipeline (excluding any
+/// extra alignment penalty, see [PPU::sprite_penalty]).
+const SPRITE_FETCH_DOTS: u32 = 6;
+/// How long the pipeline stalls when the fetcher switches to the window.
+const WINDOW_FETCH_DOTS: u32 = 6;
+/// On line 153 LY only reads 153 for a single M-cycle before reading 0 for
+/// the rest of the line.
+const LINE_153_LY_DOTS: u32 = 4;
+/// The CPU sees the PPU a single M-cycle behind its internal state, so LY,
+/// the memory locks and the STAT interrupt sources all change an M-cycle
+/// before the mode in STAT does.
+const M_CYCLE_DOTS: u32 = 4;
+const MAX_SPRITES_PER_LINE: usize = 10;
+/// The window is offset by 7 pixels, so WX = 7 places it at the far left of
+/// the screen and WX = 166 shows a single column on the far right.
+pub(super) const WINDOW_X_OFFSET: u8 = 7;
+const WINDOW_MAX_WX: u8 = 166;
+
+/// The DMG runs at 4194304 T-cycles per second, or 1048576 M-cycles.
+const M_CYCLES_PER_SECOND: u128 = 1_048_576;
+
+/// Convert a count of M-cycles to the emulated time they take.
+fn emulated_time(cycles: u64) -> Duration {
+    let nanos = cycles as u128 * 1_000_000_000 / M_CYCLES_PER_SECOND;
+    Duration::from_nanos(nanos as u64)
+}
 
 impl PPU {
     /// Run a single rendering step of the PPU for the given
     /// amount of clock cycles.
     pub(crate) fn step(&mut self, cycles: u8) {
+        self.cycles += cycles as u64;
+
         if !self.lcdc.lcd_enable() {
             return;
         }
 
-        self.update_clock(cycles);
+        for _ in 0..(cycles as u32 * 4) {
+            self.dot_step();
+        }
+    }
+
+    pub(super) fn update_ly(&mut self, value: u8) {
+        self.ly = value;
+        self.ly_compare();
+    }
+
+    /// Advance the PPU by a single dot (T-cycle).
+    fn dot_step(&mut self) {
+        self.clock += 1;
 
         match self.lcd_stat.ppu_mode() {
             PPUMode::HBlank => self.hblank_step(),
@@ -24,158 +67,371 @@ impl PPU {
             PPUMode::Oam => self.oam_step(),
             PPUMode::Drawing => self.drawing_step(),
         }
-    }
 
-    pub(super) fn update_ly(&mut self, value: u8) {
-        self.ly = value;
-        self.ly_compare();
+        self.update_stat_line();
     }
 
     fn hblank_step(&mut self) {
-        if self.clock < HBLANK_CYCLES {
+        // The first line after the LCD is switched on doesn't perform an
+        // OAM scan, it stays in mode 0 until drawing starts.
+        if self.lcd_on_line && self.clock == OAM_DOTS {
+            self.lcd_on_line = false;
+            self.start_drawing();
+            self.switch_mode(PPUMode::Drawing);
             return;
         }
-        self.clock %= HBLANK_CYCLES;
 
-        if self.ly < 143 {
-            self.update_ly(self.ly + 1);
+        if self.clock == SCANLINE_DOTS - M_CYCLE_DOTS {
+            self.advance_ly();
+        }
+
+        if self.clock < SCANLINE_DOTS {
+            return;
+        }
+        self.clock = 0;
+        self.ly_compare();
+
+        if self.ly <= 143 {
             self.switch_mode(PPUMode::Oam)
         } else {
-            self.renderer.render(self.buffer);
-            self.reset_buffer();
+            self.finish_frame();
             self.switch_mode(PPUMode::VBlank)
         }
     }
 
-    fn vblank_step(&mut self) {
-        if self.clock < VBLANK_CYCLES {
-            return;
+    /// Send the completed frame to the renderer. The first frame after the
+    /// LCD is switched on is never displayed, the LCD stays blank instead.
+    fn finish_frame(&mut self) {
+        if self.blank_frame {
+            self.blank_frame = false;
+            self.blank_buffer();
+        } else {
+            self.draw_overlays();
+        }
+
+        self.present_frame();
+    }
+
+    /// Lend the frame buffer to the renderer, then clear it ready for the
+    /// next frame. With the Super Game Boy enabled, the renderer is sent its
+    /// coloured output with the border instead.
+    pub(super) fn present_frame(&mut self) {
+        if let Some(sgb) = self.sgb.as_mut() {
+            sgb.finish_frame();
         }
 
-        self.clock %= VBLANK_CYCLES;
-        self.update_ly(self.ly + 1);
+        let (pixels, width, height) = match self.sgb.as_deref() {
+            Some(sgb) => (sgb.output(), SGB_WIDTH, SGB_HEIGHT),
+            None => (&self.buffer[..], WIDTH, HEIGHT),
+        };
+        let frame = Frame {
+            pixels,
+            width,
+            height,
+            indexed: self.indexed.as_deref(),
+            number: self.frame_number,
+            timestamp: emulated_time(self.cycles),
+        };
+
+        self.renderer.render(&frame);
+        self.frame_number += 1;
+
+        self.last_frame.width = width;
+        self.last_frame.height = height;
+        self.last_frame.pixels.clear();
+        self.last_frame.pixels.extend_from_slice(pixels);
+        self.last_frame.number = frame.number;
+        self.last_frame.timestamp = frame.timestamp;
+        self.reset_buffer();
+    }
 
-        if self.ly >= 153 {
+    fn vblank_step(&mut self) {
+        // LY=153 quirk: LY is reset to 0 early, near the start of the last
+        // line of VBlank, rather than at the start of the next frame.
+        if self.ly == 153 && self.clock == LINE_153_LY_DOTS {
             self.update_ly(0);
+        }
+
+        // LY is only ever 0 during VBlank once the line 153 quirk has
+        // kicked in, so there's no next line to move on to.
+        if self.ly != 0 && self.clock == SCANLINE_DOTS - M_CYCLE_DOTS {
+            self.advance_ly();
+        }
+
+        if self.clock < SCANLINE_DOTS {
+            return;
+        }
+        self.clock = 0;
+
+        // The line 153 quirk has kicked in, so the frame is complete.
+        if self.ly == 0 {
+            self.start_frame();
             self.switch_mode(PPUMode::Oam)
+        } else {
+            self.ly_compare();
         }
     }
 
+    /// LY moves on to the next line one M-cycle before the current line
+    /// ends, in HBlank and VBlank alike, but the coincidence flag reads 0
+    /// until the new line has actually started.
+    fn advance_ly(&mut self) {
+        self.ly += 1;
+        self.lcd_stat.set_lyc_eq_ly(false);
+    }
+
     fn oam_step(&mut self) {
-        if self.clock < OAM_CYCLES {
+        if self.clock < OAM_DOTS {
             return;
         }
 
-        self.clock %= OAM_CYCLES;
+        self.start_drawing();
         self.switch_mode(PPUMode::Drawing);
     }
 
     fn drawing_step(&mut self) {
-        if self.clock < DRAWING_CYCLES {
+        if self.clock < OAM_DOTS + self.drawing_dots {
             return;
         }
 
-        self.clock %= DRAWING_CYCLES;
         self.render_scanline();
         self.switch_mode(PPUMode::HBlank);
     }
 
+    /// Work done by the PPU at the end of mode 2, before it starts pushing
+    /// pixels for the line.
+    fn start_drawing(&mut self) {
+        self.oam_scan();
+
+        // Once LY has matched WY the window may be drawn on every remaining
+        // line of the frame, even if WY is changed afterwards.
+        if self.ly == self.window_position.wy {
+            self.window_triggered = true;
+        }
+
+        self.drawing_dots = self.drawing_length();
+    }
+
+    /// Reset per frame state when the PPU returns to line 0.
+    pub(super) fn start_frame(&mut self) {
+        self.window_triggered = false;
+        self.window_line = 0;
+    }
+
     fn switch_mode(&mut self, mode: PPUMode) {
-        match mode {
-            PPUMode::HBlank => {
-                if self.lcd_stat.hblank_stat_ie() {
-                    self.request_stat_interrupt()
-                }
+        if mode == PPUMode::VBlank {
+            self.request_vblank_interrupt();
+        }
+
+        self.lcd_stat.set_ppu_mode(mode)
+    }
+
+    /// Is the PPU in the last M-cycle of a line before an OAM scan.
+    pub(super) fn entering_oam(&self) -> bool {
+        self.lcd_stat.ppu_mode() == PPUMode::HBlank
+            && self.ly <= 143
+            && self.clock >= SCANLINE_DOTS - M_CYCLE_DOTS
+    }
+
+    /// Is the PPU in the last M-cycle of an OAM scan before drawing.
+    pub(super) fn entering_drawing(&self) -> bool {
+        self.lcd_stat.ppu_mode() == PPUMode::Oam && self.clock >= OAM_DOTS - M_CYCLE_DOTS
+    }
+
+    /// Is the PPU in the last M-cycle of drawing before HBlank.
+    fn entering_hblank(&self) -> bool {
+        self.lcd_stat.ppu_mode() == PPUMode::Drawing
+            && self.clock >= OAM_DOTS + self.drawing_dots - M_CYCLE_DOTS
+    }
+
+    /// The STAT interrupt is requested on the rising edge of a single
+    /// internal line, which is the OR of all the enabled STAT sources. While
+    /// one source holds the line high, other sources becoming active will not
+    /// trigger another interrupt ("STAT blocking").
+    pub(super) fn update_stat_line(&mut self) {
+        let mode = self.lcd_stat.ppu_mode();
+
+        // On DMG the mode 2 source is also checked when entering VBlank
+        // on line 144.
+        let entering_vblank = mode == PPUMode::VBlank && self.ly == 144 && self.clock == 0;
+        let entering_oam = self.entering_oam();
+        let entering_hblank = self.entering_hblank();
+
+        let line = (self.lcd_stat.lyc_ly_stat_ie() && self.ly == self.lyc)
+            || (self.lcd_stat.hblank_stat_ie() && (mode == PPUMode::HBlank || entering_hblank))
+            || (self.lcd_stat.vblank_stat_ie() && mode == PPUMode::VBlank)
+            || (self.lcd_stat.oam_stat_ie()
+                && (mode == PPUMode::Oam || entering_oam || entering_vblank));
+
+        if line && !self.stat_line {
+            self.request_stat_interrupt();
+        }
+
+        self.stat_line = line;
+    }
+
+    /// Length of mode 3 (in dots) for the current scanline. The base length
+    /// is extended by the fine scroll (the pixels of the first tile that are
+    /// discarded), by fetching the window and by fetching sprites.
+    fn drawing_length(&self) -> u32 {
+        let mut dots = DRAWING_DOTS + (self.background_viewport.scx % 8) as u32;
+
+        if self.is_window_on_scanline() {
+            dots += WINDOW_FETCH_DOTS;
+        }
+
+        // Part of the sprite stall overlaps the background fetch, only
+        // whole M-cycles of it delay the end of mode 3.
+        if self.lcdc.sprites_enabled() {
+            dots += self.sprite_penalty() / M_CYCLE_DOTS * M_CYCLE_DOTS;
+        }
+
+        dots
+    }
+
+    /// Is any part of the window drawn on the current line. The window
+    /// needs to be enabled, WY needs to have matched LY at some point this
+    /// frame and WX must not push it entirely off the right of the screen.
+    fn is_window_on_scanline(&self) -> bool {
+        self.lcdc.window_enabled()
+            && self.lcdc.background_and_window_enabled()
+            && self.window_triggered
+            && self.window_position.wx <= WINDOW_MAX_WX
+    }
 
-                self.lcd_stat.set_ppu_mode(PPUMode::HBlank)
+    /// Each sprite on the scanline stalls the pixel pipeline for 6 dots,
+    /// plus up to 5 more when it is the first sprite to land on a given
+    /// background tile, depending on how far into the tile it starts.
+    fn sprite_penalty(&self) -> u32 {
+        let scx = self.background_viewport.scx as i16;
+
+        let mut penalty = 0;
+        let mut last_tile = None;
+
+        // Sprites are fetched as the pixel pipeline reaches them, left to
+        // right, which is the order the OAM scan leaves them in. Sprites on
+        // the same tile are next to each other.
+        for sprite in &self.line_sprites {
+            let x = sprite.x() + 8;
+            if x >= 168 {
+                continue;
+            }
+
+            let tile = (x + scx) / 8;
+            if last_tile != Some(tile) {
+                last_tile = Some(tile);
+                penalty += 5u32.saturating_sub(((x + scx) % 8) as u32);
             }
-            PPUMode::VBlank => {
-                if self.lcd_stat.vblank_stat_ie() {
-                    self.request_stat_interrupt()
-                }
 
-                self.request_vblank_interrupt();
+            penalty += SPRITE_FETCH_DOTS;
+        }
+
+        penalty
+    }
+
+    /// Mode 2 OAM scan. Walks OAM in index order and selects the first 10
+    /// sprites which overlap the current scanline, any further sprites are
+    /// ignored for this line. Note that sprites are selected even if they
+    /// are horizontally off screen (and still count towards the limit).
+    ///
+    /// The selected sprites are then ordered by drawing priority. On DMG
+    /// the sprite with the smaller X coordinate wins, with ties going to the
+    /// sprite that comes first in OAM. Since the scan preserves OAM order a
+    /// stable sort by X gives exactly that.
+    fn oam_scan(&mut self) {
+        let height = self.lcdc.sprite_size().height() as i16;
+        let ly = self.ly as i16;
+
+        self.line_sprites.clear();
+
+        for idx in 0..40 {
+            let sprite = self.oam.sprite_at(idx);
 
-                self.lcd_stat.set_ppu_mode(PPUMode::VBlank)
+            if ly >= sprite.y() && ly < sprite.y() + height {
+                self.line_sprites.push(sprite);
             }
-            PPUMode::Oam => {
-                if self.lcd_stat.oam_stat_ie() {
-                    self.request_stat_interrupt()
-                }
 
-                self.lcd_stat.set_ppu_mode(PPUMode::Oam)
+            if self.line_sprites.len() == MAX_SPRITES_PER_LINE {
+                break;
             }
-            PPUMode::Drawing => self.lcd_stat.set_ppu_mode(PPUMode::Drawing),
         }
+
+        self.line_sprites.sort_by_key(|sprite| sprite.x());
     }
 
     fn render_scanline(&mut self) {
         if self.lcdc.background_and_window_enabled() {
             self.render_background_scanline();
+        } else {
+            for x in 0..(WIDTH as u8) {
+                self.render_blank_pixel(x);
+            }
         }
 
-        if self.lcdc.sprites_enabled() {
+        if self.lcdc.sprites_enabled() && self.debug_options.layer_enabled(Layer::Sprites) {
             self.render_sprites_at_scanline();
         }
     }
 
+    /// Render the sprites selected during the OAM scan, in priority order.
+    /// Once a sprite has drawn a non-transparent pixel at a given X, lower
+    /// priority sprites never show through at that X, even if the winning
+    /// pixel ends up hidden behind the background.
     fn render_sprites_at_scanline(&mut self) {
-        for idx in 0..40 {
-            self.render_sprite_at_scanline(idx);
+        let mut claimed = [false; WIDTH];
+
+        for idx in 0..self.line_sprites.len() {
+            let sprite = self.line_sprites[idx];
+            self.render_sprite_at_scanline(&sprite, &mut claimed);
         }
     }
 
-    fn render_sprite_at_scanline(&mut self, sprite_number: u8) {
-        let sprite = self.oam.sprite_at(sprite_number);
+    fn render_sprite_at_scanline(&mut self, sprite: &oam::Sprite, claimed: &mut [bool; WIDTH]) {
         let sprite_size = self.lcdc.sprite_size();
 
-        let ly = self.ly as i16;
-        if ly < sprite.y() || ly >= sprite.y() + (sprite_size.height() as i16) {
-            return;
-        }
-
-        // Safety: line above
-        let y = (ly - sprite.y()) as u8;
+        // Safety: the OAM scan only selects sprites overlapping this line
+        let y = (self.ly as i16 - sprite.y()) as u8;
 
         for x in 0..8 {
+            let screen_x = x as i16 + sprite.x();
+
+            // sprite pixel outside of LCD range
+            if !(0..WIDTH as i16).contains(&screen_x) || claimed[screen_x as usize] {
+                continue;
+            }
+
             let tile = self
                 .tiledata
                 .sprite_tile_at(sprite.tile_number, sprite_size);
             let pixel = tile.pixel_at(x, y, sprite.flags);
 
-            self.render_sprite_pixel(pixel, &sprite, x);
+            // If the sprite pixel is Color0 (its transparent), lower priority
+            // sprites still get a chance to draw at this X.
+            if pixel == Pixel::Color0 {
+                continue;
+            }
+
+            claimed[screen_x as usize] = true;
+            self.render_sprite_pixel(pixel, sprite, screen_x as usize);
         }
     }
 
     /// Apply pixel mixing to a sprite_pixel, and insert it
     /// into the buffer over the background pixel if the following
     /// conditions are met:
-    /// 1) If the color number of the Sprite Pixel is 0,
-    ///    the Background Pixel is pushed to the LCD.
-    /// 2) If the BG-to-OBJ-Priority bit is 1 and the color
+    /// 1) If the BG-to-OBJ-Priority bit is 1 and the color
     ///    number of the Background Pixel is anything other than 0,
     ///    the Background Pixel is pushed to the LCD.
-    /// 3) If none of the above conditions apply, the Sprite
-    ///    Pixel is pushed to the LCD.
+    /// 2) Otherwise, the Sprite Pixel is pushed to the LCD.
+    ///
+    /// Transparent sprite pixels are filtered out before this is called.
     ///
     /// NOTE: this function has a few caveats for it to be valid:
     /// - The background scanline must always be rendered before the sprite
     /// - Background scanline rendering function must push BG pixel
     ///   to the bg_priority buffer
-    fn render_sprite_pixel(&mut self, sprite_pixel: Pixel, sprite: &oam::Sprite, x: u8) {
-        let x = x as i16 + sprite.x();
-
-        // sprite pixel outside of LCD range
-        if !(0..=160).contains(&x) {
-            return;
-        }
-
-        // If the sprite pixel is Color0 (its transparent), the background pixel is pushed to the LCD.
-        if sprite_pixel == Pixel::Color0 {
-            return;
-        }
-
-        let bg_priority = self.bg_priority[self.ly as usize * WIDTH + x as usize];
+    fn render_sprite_pixel(&mut self, sprite_pixel: Pixel, sprite: &oam::Sprite, x: usize) {
+        let bg_priority = self.bg_priority[self.ly as usize * WIDTH + x];
 
         // If the BG priority is set and the bg pixel is not a Color0. The
         // BG pixel is pushed to the LCD.
@@ -186,55 +442,71 @@ impl PPU {
         // Otherwise, the sprite pixel has priority and is rendered over the BG
         let palette = self.sprite_palette(sprite.flags);
         let color = palette.color_from_pixel(sprite_pixel);
+        let source = PixelSource::Sprite(sprite.flags.palette_number());
 
-        self.buffer[self.ly as usize * WIDTH + x as usize] = self.renderer.palette(color.into());
+        self.write_pixel(
+            self.ly as usize * WIDTH + x,
+            sprite_pixel,
+            color.into(),
+            source,
+        );
     }
 
     fn render_background_scanline(&mut self) {
+        let window = self.is_window_on_scanline();
+
         for x in 0..(WIDTH as u8) {
-            if self.is_window_pixel(x, self.ly) {
-                self.render_window_layer_pixel(x, self.ly);
-            } else {
-                self.render_background_layer_pixel(x, self.ly);
+            // WX < 7 starts the window off the left of the screen, those
+            // columns of the window are skipped rather than wrapping around.
+            let window_x = (x + WINDOW_X_OFFSET).checked_sub(self.window_position.wx);
+
+            let background = self.debug_options.layer_enabled(Layer::Background);
+
+            match window_x {
+                Some(window_x) if window && self.debug_options.layer_enabled(Layer::Window) => {
+                    self.render_window_layer_pixel(x, window_x)
+                }
+                _ if background => self.render_background_layer_pixel(x, self.ly),
+                _ => self.render_blank_pixel(x),
             }
         }
-    }
 
-    fn is_window_pixel(&self, x: u8, y: u8) -> bool {
-        if !self.lcdc.window_enabled() {
-            return false;
+        // The window keeps its own line counter, which only moves on lines
+        // where the window was actually drawn. So if the window is hidden
+        // for some lines mid-frame it picks up where it left off.
+        if window {
+            self.window_line += 1;
         }
-
-        // TODO: handle cases where wx is < 7
-        x >= self.window_position.wx() && y >= self.window_position.wy()
     }
 
-    fn render_window_layer_pixel(&mut self, x: u8, y: u8) {
+    fn render_window_layer_pixel(&mut self, x: u8, window_x: u8) {
         let window_map = self.current_window_map();
-
-        // Safety: this function shouldn't be called if wx < x. If this is the
-        // case, this pixel should be rendered using the bckground map instead
-        // because it doesn't overlap with the window.
-        let tile_x = x - self.window_position.wx();
-
-        // Same as above.
-        let tile_y = y - self.window_position.wy;
+        let tile_y = self.window_line;
 
         // TODO: everything below here is the same as background
-        let tile_number = window_map.tile_number_at(tile_x, tile_y);
+        let tile_number = window_map.tile_number_at(window_x, tile_y);
         let addressing_method = self.lcdc.addressing_method();
 
         let tile = self.tiledata.tile_at(tile_number, addressing_method);
 
-        let pixel_x = tile_x % 8;
+        let pixel_x = window_x % 8;
         let pixel_y = tile_y % 8;
 
         let pixel = tile.pixel_at(pixel_x, pixel_y);
 
         let color = self.background_palette.color_from_pixel(pixel);
 
-        self.bg_priority[y as usize * WIDTH + x as usize] = pixel;
-        self.buffer[y as usize * WIDTH + x as usize] = self.renderer.palette(color.into());
+        let idx = self.ly as usize * WIDTH + x as usize;
+        self.bg_priority[idx] = pixel;
+        self.write_pixel(idx, pixel, color.into(), PixelSource::Window);
+    }
+
+    /// Draw a pixel with no background or window behind it, as if BG colour
+    /// 0 was drawn with a white palette entry.
+    fn render_blank_pixel(&mut self, x: u8) {
+        let idx = self.ly as usize * WIDTH + x as usize;
+        self.bg_priority[idx] = Pixel::Color0;
+        self.write_pixel(idx, Pixel::Color0, Color::White, PixelSource::Background);
     }
 
     fn render_background_layer_pixel(&mut self, x: u8, y: u8) {
@@ -255,11 +527,12 @@ impl PPU {
 
         let color = self.background_palette.color_from_pixel(pixel);
 
-        self.bg_priority[y as usize * WIDTH + x as usize] = pixel;
-        self.buffer[y as usize * WIDTH + x as usize] = self.renderer.palette(color.into());
+        let idx = y as usize * WIDTH + x as usize;
+        self.bg_priority[idx] = pixel;
+        self.write_pixel(idx, pixel, color.into(), PixelSource::Background);
     }
 
-    fn sprite_palette(&self, flags: SpriteFlags) -> &SpritePalette {
+    pub(super) fn sprite_palette(&self, flags: SpriteFlags) -> &SpritePalette {
         match flags.palette_number() {
             oam::PaletteNumber::OBP0 => &self.sprite_palette_0,
             oam::PaletteNumber::OBP1 => &self.sprite_palette_1,
@@ -279,8 +552,557 @@ impl PPU {
             BGMapSelection::Map0 => &self.bg_map0,
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl PPU {
+        fn oam_scan_count(&mut self, ly: u8) -> usize {
+            self.ly = ly;
+            self.oam_scan();
+            self.line_sprites.len()
+        }
+    }
+
+    fn enabled_ppu() -> PPU {
+        let mut ppu = PPU::new(Arc::new(TestRenderer));
+        ppu.write_lcdc(0x80);
+        ppu.lcd_stat.set_ppu_mode(PPUMode::Oam);
+
+        ppu
+    }
+
+    /// Run the PPU until it leaves the current mode, returning the number
+    /// of dots spent in it.
+    fn dots_in_mode(ppu: &mut PPU) -> u32 {
+        let mode = ppu.lcd_stat.ppu_mode();
+        let mut dots = 0;
+
+        while ppu.lcd_stat.ppu_mode() == mode {
+            ppu.dot_step();
+            dots += 1;
+        }
+
+        dots
+    }
+
+    /// Render the given scanline and return its leftmost pixels as a
+    /// string, one character per shade: '.', '-', '+' and '#' for white
+    /// through to black.
+    fn render_line(ppu: &mut PPU, ly: u8, width: usize) -> String {
+        ppu.ly = ly;
+        ppu.start_drawing();
+        ppu.render_scanline();
+
+        let row = &ppu.buffer[ly as usize * WIDTH..ly as usize * WIDTH + width];
+
+        row.iter()
+            .map(|&pixel| match pixel {
+                0xFFFFFF => '.',
+                0xA8A8A8 => '-',
+                0x454545 => '+',
+                _ => '#',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn scanline_mode_lengths() {
+        let mut ppu = enabled_ppu();
 
-    fn update_clock(&mut self, cycles: u8) {
-        self.clock += cycles as u32;
+        assert_eq!(dots_in_mode(&mut ppu), 80);
+        assert_eq!(dots_in_mode(&mut ppu), 172);
+        assert_eq!(dots_in_mode(&mut ppu), 204);
+        assert_eq!(ppu.read_ly(), 1);
+    }
+
+    #[test]
+    fn fine_scroll_extends_drawing() {
+        let mut ppu = enabled_ppu();
+        ppu.write_background_viewport(ViewportRegister::Scx, 3);
+
+        dots_in_mode(&mut ppu);
+        assert_eq!(dots_in_mode(&mut ppu), 175);
+        assert_eq!(dots_in_mode(&mut ppu), 201);
+    }
+
+    #[test]
+    fn sprites_extend_drawing() {
+        let mut ppu = enabled_ppu();
+        ppu.write_lcdc(0x82);
+
+        // Sprite at X = 0 is aligned to a tile, so costs 11 dots.
+        ppu.write_oam(0, 16);
+        ppu.write_oam(1, 0);
+        // Sprite aligned to a tile costs 11 dots.
+        ppu.write_oam(4, 16);
+        ppu.write_oam(5, 16);
+        // Second sprite on the same tile only costs 6 dots.
+        ppu.write_oam(8, 16);
+        ppu.write_oam(9, 20);
+
+        dots_in_mode(&mut ppu);
+        assert_eq!(dots_in_mode(&mut ppu), 172 + 11 + 11 + 6);
+    }
+
+    #[test]
+    fn sprite_penalty_rounds_down_to_m_cycles() {
+        let mut ppu = enabled_ppu();
+        ppu.write_lcdc(0x82);
+
+        // Sprite 4 pixels into a tile costs 7 dots, only 4 of which count.
+        ppu.write_oam(0, 16);
+        ppu.write_oam(1, 4);
+
+        dots_in_mode(&mut ppu);
+        assert_eq!(dots_in_mode(&mut ppu), 172 + 4);
+    }
+
+    #[test]
+    fn ly_moves_on_an_m_cycle_before_the_line_ends() {
+        let mut ppu = enabled_ppu();
+        ppu.write_lyc(145);
+
+        for line in [10, 144] {
+            while ppu.read_ly() != line || ppu.clock != 0 {
+                ppu.dot_step();
+            }
+            while ppu.clock < SCANLINE_DOTS - M_CYCLE_DOTS - 1 {
+                ppu.dot_step();
+            }
+            assert_eq!(ppu.read_ly(), line);
+
+            ppu.dot_step();
+            assert_eq!(ppu.read_ly(), line + 1);
+        }
+
+        // LY=LYC isn't flagged until the line has actually started
+        assert_eq!(ppu.read_lcd_stat() & 0b100, 0);
+        for _ in 0..M_CYCLE_DOTS {
+            ppu.dot_step();
+        }
+        assert_ne!(ppu.read_lcd_stat() & 0b100, 0);
+    }
+
+    #[test]
+    fn line_153_quirk() {
+        let mut ppu = enabled_ppu();
+
+        while ppu.read_ly() != 153 || ppu.clock != 0 {
+            ppu.dot_step();
+        }
+
+        for _ in 0..LINE_153_LY_DOTS {
+            ppu.dot_step();
+        }
+        assert_eq!(ppu.read_ly(), 0);
+        assert_eq!(ppu.lcd_stat.ppu_mode(), PPUMode::VBlank);
+
+        dots_in_mode(&mut ppu);
+        assert_eq!(ppu.read_ly(), 0);
+        assert_eq!(ppu.lcd_stat.ppu_mode(), PPUMode::Oam);
+    }
+
+    #[test]
+    fn lcd_off_resets_ly_and_mode() {
+        let mut ppu = enabled_ppu();
+
+        while ppu.read_ly() != 10 || ppu.lcd_stat.ppu_mode() != PPUMode::Drawing {
+            ppu.dot_step();
+        }
+
+        ppu.write_lcdc(0x00);
+        assert_eq!(ppu.read_ly(), 0);
+        assert_eq!(ppu.lcd_stat.ppu_mode(), PPUMode::HBlank);
+
+        // PPU doesn't run while the LCD is off
+        ppu.step(100);
+        assert_eq!(ppu.read_ly(), 0);
+        assert_eq!(ppu.lcd_stat.ppu_mode(), PPUMode::HBlank);
+    }
+
+    #[test]
+    fn first_line_after_lcd_on_skips_oam_scan() {
+        let mut ppu = PPU::new(Arc::new(TestRenderer));
+        ppu.write_lcd_stat(0b0010_0000);
+        ppu.write_lcdc(0x80);
+
+        assert_eq!(ppu.lcd_stat.ppu_mode(), PPUMode::HBlank);
+        assert_eq!(dots_in_mode(&mut ppu), 80);
+        assert_eq!(ppu.lcd_stat.ppu_mode(), PPUMode::Drawing);
+        assert!(!ppu.interrupt_request.stat);
+
+        dots_in_mode(&mut ppu);
+        dots_in_mode(&mut ppu);
+        assert_eq!(ppu.read_ly(), 1);
+        assert_eq!(ppu.lcd_stat.ppu_mode(), PPUMode::Oam);
+    }
+
+    #[test]
+    fn first_frame_after_lcd_on_is_blank() {
+        let mut ppu = PPU::new(Arc::new(TestRenderer));
+        ppu.write_lcdc(0x80);
+        assert!(ppu.blank_frame);
+
+        while ppu.lcd_stat.ppu_mode() != PPUMode::VBlank {
+            ppu.dot_step();
+        }
+        assert!(!ppu.blank_frame);
+    }
+
+    #[test]
+    fn frames_are_numbered_and_timestamped() {
+        #[derive(Default)]
+        struct RecordingRenderer(std::sync::Mutex<Vec<(u64, Duration)>>);
+
+        impl Renderer for RecordingRenderer {
+            fn render(&self, frame: &Frame) {
+                let mut frames = self.0.lock().unwrap();
+                frames.push((frame.number, frame.timestamp));
+            }
+        }
+
+        let renderer = Arc::new(RecordingRenderer::default());
+        let mut ppu = PPU::new(renderer.clone());
+        ppu.write_lcdc(0x80);
+
+        // Two full frames of 17556 M-cycles each.
+        for _ in 0..(2 * 17556) {
+            ppu.step(1);
+        }
+
+        let frames = renderer.0.lock().unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].0, 0);
+        assert_eq!(frames[1].0, 1);
+        // One frame is just under 16.75ms of emulated time.
+        let frame_time = frames[1].1 - frames[0].1;
+        assert_eq!(frame_time.as_micros(), 16742);
+    }
+
+    #[test]
+    fn vram_and_oam_access_by_mode() {
+        let mut ppu = enabled_ppu();
+
+        assert_eq!(ppu.lcd_stat.ppu_mode(), PPUMode::Oam);
+        assert!(ppu.vram_readable() && ppu.vram_writable());
+        assert!(!ppu.oam_readable() && !ppu.oam_writable());
+
+        // VRAM reads are locked an M-cycle before drawing, OAM writes are
+        // let through for that M-cycle.
+        for _ in 0..(OAM_DOTS - M_CYCLE_DOTS) {
+            ppu.dot_step();
+        }
+        assert_eq!(ppu.lcd_stat.ppu_mode(), PPUMode::Oam);
+        assert!(!ppu.vram_readable() && ppu.vram_writable());
+        assert!(!ppu.oam_readable() && ppu.oam_writable());
+
+        dots_in_mode(&mut ppu);
+        assert_eq!(ppu.lcd_stat.ppu_mode(), PPUMode::Drawing);
+        assert!(!ppu.vram_readable() && !ppu.vram_writable());
+        assert!(!ppu.oam_readable() && !ppu.oam_writable());
+
+        dots_in_mode(&mut ppu);
+        assert_eq!(ppu.lcd_stat.ppu_mode(), PPUMode::HBlank);
+        assert!(ppu.vram_readable() && ppu.vram_writable());
+        assert!(ppu.oam_readable() && ppu.oam_writable());
+
+        // OAM reads are locked an M-cycle before the next line starts
+        while ppu.clock < SCANLINE_DOTS - M_CYCLE_DOTS {
+            ppu.dot_step();
+        }
+        assert!(!ppu.oam_readable() && ppu.oam_writable());
+
+        // Everything is accessible while the LCD is off
+        ppu.write_lcdc(0x00);
+        ppu.lcd_stat.set_ppu_mode(PPUMode::Drawing);
+        assert!(ppu.vram_readable() && ppu.vram_writable());
+        assert!(ppu.oam_readable() && ppu.oam_writable());
+    }
+
+    #[test]
+    fn stat_interrupt_on_rising_edge() {
+        let mut ppu = enabled_ppu();
+        ppu.write_lcd_stat(0b0000_1000);
+
+        dots_in_mode(&mut ppu);
+        dots_in_mode(&mut ppu);
+        ppu.dot_step();
+        assert!(ppu.interrupt_request.stat);
+    }
+
+    #[test]
+    fn stat_interrupt_blocked_while_line_high() {
+        let mut ppu = enabled_ppu();
+        // LYC=1 holds the line high across the HBlank -> OAM transition on
+        // line 0 -> 1, so entering mode 2 shouldn't trigger another interrupt.
+        ppu.write_lcd_stat(0b0110_1000);
+
+        dots_in_mode(&mut ppu);
+        dots_in_mode(&mut ppu);
+        ppu.dot_step();
+        assert!(ppu.interrupt_request.stat);
+        ppu.interrupt_request.stat = false;
+
+        ppu.write_lyc(1);
+        dots_in_mode(&mut ppu);
+        assert_eq!(ppu.read_ly(), 1);
+        assert!(!ppu.interrupt_request.stat);
+    }
+
+    mod sprite_selection {
+        use super::*;
+
+        const SOLID_BLACK: u8 = 1;
+        const SOLID_LIGHT: u8 = 2;
+        const HALF_DARK: u8 = 3;
+
+        /// Builds a PPU with an identity palette for the BG and OBP0, and a
+        /// few simple tiles for sprites to use.
+        fn scene() -> PPU {
+            let mut ppu = PPU::new(Arc::new(TestRenderer));
+            ppu.write_lcdc(0x83);
+            ppu.write_background_palette(0b11_10_01_00);
+            ppu.write_sprite_palette(SpritePaletteSelection::Palette0, 0b11_10_01_00);
+
+            for row in 0..8 {
+                let tile = |n: u16| n * 16 + row * 2;
+
+                ppu.write_tiledata(tile(SOLID_BLACK as u16), 0xFF);
+                ppu.write_tiledata(tile(SOLID_BLACK as u16) + 1, 0xFF);
+                ppu.write_tiledata(tile(SOLID_LIGHT as u16), 0xFF);
+                ppu.write_tiledata(tile(HALF_DARK as u16) + 1, 0xF0);
+            }
+
+            ppu
+        }
+
+        fn place_sprite(ppu: &mut PPU, idx: u16, screen_x: u8, tile: u8, flags: u8) {
+            ppu.write_oam(idx * 4, 16);
+            ppu.write_oam(idx * 4 + 1, screen_x + 8);
+            ppu.write_oam(idx * 4 + 2, tile);
+            ppu.write_oam(idx * 4 + 3, flags);
+        }
+
+        #[test]
+        fn x_priority() {
+            let mut ppu = scene();
+
+            // Smaller X wins even though it comes later in OAM.
+            place_sprite(&mut ppu, 0, 4, SOLID_BLACK, 0);
+            place_sprite(&mut ppu, 1, 0, SOLID_LIGHT, 0);
+            // Equal X, lower OAM index wins.
+            place_sprite(&mut ppu, 2, 16, SOLID_BLACK, 0);
+            place_sprite(&mut ppu, 3, 16, SOLID_LIGHT, 0);
+            // Transparent pixels let lower priority sprites through.
+            place_sprite(&mut ppu, 4, 24, HALF_DARK, 0);
+            place_sprite(&mut ppu, 5, 26, SOLID_BLACK, 0);
+
+            let expected = [
+                "--------####....########++++######......",
+                "--------####....########++++######......",
+                "--------####....########++++######......",
+                "--------####....########++++######......",
+                "--------####....########++++######......",
+                "--------####....########++++######......",
+                "--------####....########++++######......",
+                "--------####....########++++######......",
+                "........................................",
+            ];
+
+            for (ly, line) in expected.iter().enumerate() {
+                assert_eq!(render_line(&mut ppu, ly as u8, 40), *line, "line {}", ly);
+            }
+        }
+
+        #[test]
+        fn ten_sprites_per_line() {
+            let mut ppu = scene();
+
+            for idx in 0..11 {
+                place_sprite(&mut ppu, idx, idx as u8 * 8, SOLID_BLACK, 0);
+            }
+
+            assert_eq!(ppu.oam_scan_count(0), 10);
+            assert_eq!(
+                render_line(&mut ppu, 0, 96),
+                "################################################################################................"
+            );
+        }
+
+        #[test]
+        fn offscreen_sprites_count_towards_limit() {
+            let mut ppu = scene();
+
+            for idx in 0..10 {
+                place_sprite(&mut ppu, idx, 200, SOLID_BLACK, 0);
+            }
+            place_sprite(&mut ppu, 10, 0, SOLID_BLACK, 0);
+
+            assert_eq!(render_line(&mut ppu, 0, 8), "........");
+        }
+
+        #[test]
+        fn hidden_sprite_masks_lower_priority_sprite() {
+            let mut ppu = scene();
+
+            // Fill the background with a non-zero color, BG tile 0 is at
+            // $9000 using signed addressing.
+            for row in 0..8 {
+                ppu.write_tiledata(0x1000 + row * 2, 0xFF);
+            }
+
+            place_sprite(&mut ppu, 0, 0, SOLID_BLACK, 0b1000_0000);
+            place_sprite(&mut ppu, 1, 0, SOLID_BLACK, 0);
+
+            assert_eq!(render_line(&mut ppu, 0, 8), "--------");
+        }
+    }
+
+    mod window {
+        use super::*;
+
+        const LIGHT: u8 = 1;
+        const DARK: u8 = 2;
+
+        /// Builds a PPU with a white background and a window whose first
+        /// row of tiles is light grey and second row is dark grey.
+        fn scene() -> PPU {
+            let mut ppu = PPU::new(Arc::new(TestRenderer));
+            // LCD, window (map 1), unsigned addressing and BG enabled.
+            ppu.write_lcdc(0xF1);
+            ppu.write_background_palette(0b11_10_01_00);
+
+            for row in 0..8 {
+                ppu.write_tiledata(LIGHT as u16 * 16 + row * 2, 0xFF);
+                ppu.write_tiledata(DARK as u16 * 16 + row * 2 + 1, 0xFF);
+            }
+
+            for column in 0..32 {
+                ppu.write_bg_map(BGMapSelection::Map1, column, LIGHT);
+                ppu.write_bg_map(BGMapSelection::Map1, 32 + column, DARK);
+            }
+
+            // Mark the first column of the window so offsets are visible
+            for row in 0..8 {
+                ppu.write_tiledata(0x0300 + row * 2, 0xFF);
+                ppu.write_tiledata(0x0300 + row * 2 + 1, 0x80);
+            }
+            ppu.write_bg_map(BGMapSelection::Map1, 0, 0x30);
+
+            ppu
+        }
+
+        #[test]
+        fn window_at_left_edge() {
+            let mut ppu = scene();
+            ppu.write_window_position(WindowPositionRegister::WX, 7);
+
+            assert_eq!(render_line(&mut ppu, 0, 12), "#-----------");
+        }
+
+        #[test]
+        fn window_with_wx_below_7_is_clipped() {
+            let mut ppu = scene();
+            ppu.write_window_position(WindowPositionRegister::WX, 3);
+
+            // The first 4 columns of the window are off screen, so the
+            // marked column isn't visible.
+            assert_eq!(render_line(&mut ppu, 0, 12), "------------");
+        }
+
+        #[test]
+        fn window_at_wx_166() {
+            let mut ppu = scene();
+            ppu.write_window_position(WindowPositionRegister::WX, 166);
+
+            render_line(&mut ppu, 0, 0);
+            let last_column = ppu.buffer[WIDTH - 1];
+            assert_eq!(last_column, 0);
+            assert_eq!(ppu.window_line, 1);
+
+            ppu.write_window_position(WindowPositionRegister::WX, 167);
+            assert_eq!(render_line(&mut ppu, 1, 0), "");
+            assert_eq!(ppu.buffer[2 * WIDTH - 1], 0xFFFFFF);
+            assert_eq!(ppu.window_line, 1);
+        }
+
+        #[test]
+        fn window_line_counter_resumes_after_being_hidden() {
+            let mut ppu = scene();
+            ppu.write_window_position(WindowPositionRegister::WX, 8);
+
+            for ly in 0..4 {
+                assert_eq!(render_line(&mut ppu, ly, 4), ".#--");
+            }
+
+            ppu.write_lcdc(0xD1);
+            for ly in 4..10 {
+                assert_eq!(render_line(&mut ppu, ly, 4), "....");
+            }
+
+            // The window continues from its 5th line, not the 11th.
+            ppu.write_lcdc(0xF1);
+            for ly in 10..14 {
+                assert_eq!(render_line(&mut ppu, ly, 4), ".#--");
+            }
+            assert_eq!(render_line(&mut ppu, 14, 4), ".+++");
+        }
+
+        #[test]
+        fn wy_only_needs_to_match_once_per_frame() {
+            let mut ppu = scene();
+            ppu.write_window_position(WindowPositionRegister::WX, 8);
+            ppu.write_window_position(WindowPositionRegister::WY, 2);
+
+            assert_eq!(render_line(&mut ppu, 0, 4), "....");
+            assert_eq!(render_line(&mut ppu, 1, 4), "....");
+            assert_eq!(render_line(&mut ppu, 2, 4), ".#--");
+
+            // Moving WY below LY doesn't hide the window for the rest of the
+            // frame.
+            ppu.write_window_position(WindowPositionRegister::WY, 100);
+            assert_eq!(render_line(&mut ppu, 3, 4), ".#--");
+
+            // But it won't be triggered next frame until LY reaches WY.
+            ppu.start_frame();
+            assert_eq!(render_line(&mut ppu, 3, 4), "....");
+        }
+
+        #[test]
+        fn disabled_window_layer_shows_background_underneath() {
+            let mut ppu = scene();
+            ppu.write_window_position(WindowPositionRegister::WX, 8);
+            ppu.set_layer_enabled(Layer::Window, false);
+
+            assert_eq!(render_line(&mut ppu, 0, 4), "....");
+
+            // The window is still there as far as the hardware is concerned.
+            assert_eq!(ppu.window_line, 1);
+            ppu.set_layer_enabled(Layer::Window, true);
+            assert_eq!(render_line(&mut ppu, 1, 4), ".#--");
+        }
+
+        #[test]
+        fn disabled_background_layer_is_blank() {
+            let mut ppu = scene();
+            ppu.write_bg_map(BGMapSelection::Map0, 0, DARK);
+            ppu.write_window_position(WindowPositionRegister::WX, 10);
+            ppu.set_layer_enabled(Layer::Background, false);
+
+            assert_eq!(render_line(&mut ppu, 0, 5), "...#-");
+        }
+
+        #[test]
+        fn background_disabled_by_lcdc_is_white() {
+            let mut ppu = scene();
+            ppu.write_bg_map(BGMapSelection::Map0, 0, DARK);
+            ppu.write_window_position(WindowPositionRegister::WX, 7);
+            ppu.write_lcdc(0xF0);
+
+            assert_eq!(render_line(&mut ppu, 0, 4), "....");
+        }
     }
 }
@@ -0,0 +1,89 @@
+use std::io::{self, Write};
+use std::time::Duration;
+
+use super::*;
+
+/// A copy of a completed frame, as sent to the [Renderer].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screenshot {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+    pub number: u64,
+    pub timestamp: Duration,
+}
+
+impl Screenshot {
+    /// Nearest-neighbour scale the screenshot by an integer factor.
+    pub fn scaled(&self, factor: usize) -> Screenshot {
+        let factor = factor.max(1);
+        let width = self.width * factor;
+        let height = self.height * factor;
+
+        let pixels = (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width / factor, i / width / factor);
+                self.pixels[y * self.width + x]
+            })
+            .collect();
+
+        Screenshot {
+            width,
+            height,
+            pixels,
+            ..*self
+        }
+    }
+
+    /// Encode the screenshot as a PNG. Pixels are assumed to be 0x00RRGGBB,
+    /// as they are with every [Palette].
+    pub fn write_png(&self, writer: &mut impl Write) -> io::Result<()> {
+        crate::png::encode(writer, self.width, self.height, &self.pixels)
+    }
+}
+
+impl PPU {
+    /// The last frame sent to the renderer. Blank until the first frame is
+    /// completed.
+    pub fn screenshot(&self) -> Screenshot {
+        self.last_frame.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screenshot_of_last_frame() {
+        let mut ppu = PPU::new(Arc::new(TestRenderer));
+        ppu.write_lcdc(0x80);
+
+        // Two frames, the first is always blank.
+        for _ in 0..(2 * 17556) {
+            ppu.step(1);
+        }
+
+        let screenshot = ppu.screenshot();
+        assert_eq!(screenshot.number, 1);
+        assert_eq!((screenshot.width, screenshot.height), (WIDTH, HEIGHT));
+        // Background of tile 0 with the default palette
+        assert!(screenshot.pixels.iter().all(|&pixel| pixel == 0xFFFFFF));
+    }
+
+    #[test]
+    fn scaled_screenshot() {
+        let screenshot = Screenshot {
+            width: 2,
+            height: 1,
+            pixels: vec![1, 2],
+            number: 0,
+            timestamp: Duration::ZERO,
+        };
+
+        let scaled = screenshot.scaled(2);
+
+        assert_eq!((scaled.width, scaled.height), (4, 2));
+        assert_eq!(scaled.pixels, [1, 1, 2, 2, 1, 1, 2, 2]);
+    }
+}
@@ -0,0 +1,638 @@
+mod border;
+
+use border::Border;
+use palette::{rgb555, shade_index};
+
+use super::*;
+
+/// Width of the Super Game Boy's output, including the border.
+pub const SGB_WIDTH: usize = 256;
+/// Height of the Super Game Boy's output, including the border.
+pub const SGB_HEIGHT: usize = 224;
+
+/// Position of the Game Boy screen within the border.
+const SCREEN_X: usize = 48;
+const SCREEN_Y: usize = 40;
+
+/// The screen is coloured in 8x8 cells, each using one of four palettes.
+const CELLS_X: usize = WIDTH / 8;
+const CELLS_Y: usize = HEIGHT / 8;
+
+/// Number of bytes copied from the screen by the *_TRN commands.
+const TRANSFER_SIZE: usize = 0x1000;
+
+/// Colours of the SGB's first built in palette, 1-A.
+const DEFAULT_PALETTE: [u16; 4] = [0x67BF, 0x265B, 0x10B5, 0x2866];
+
+/// What's shown in place of the game screen, set by MASK_EN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mask {
+    None,
+    /// Keep showing the last frame.
+    Freeze,
+    Black,
+    /// Fill the screen with colour 0.
+    Color0,
+}
+
+/// Data to copy out of the next complete frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transfer {
+    Palettes,
+    Attributes,
+    /// Border tiles, either the lower or the upper 128.
+    Tiles {
+        upper: bool,
+    },
+    /// Border tile map and palettes.
+    Border,
+}
+
+/// State of the Super Game Boy, which colours the screen and surrounds it
+/// with a border, as told by command packets sent through the joypad
+/// register. Packets are received by the [crate::Joypad], and the game sends
+/// larger blocks of data by drawing them on screen.
+pub(super) struct Sgb {
+    /// Shade of each pixel of the game screen, 0 for white to 3 for black.
+    screen: Box<[u8; WIDTH * HEIGHT]>,
+    /// Packets of a command which spans more than one.
+    command: Vec<u8>,
+    palettes: [[u16; 4]; 4],
+    system_palettes: Box<[[u16; 4]; 512]>,
+    /// Palette of each 8x8 cell of the screen.
+    attributes: [u8; CELLS_X * CELLS_Y],
+    attribute_files: Box<[[u8; CELLS_X * CELLS_Y]; 45]>,
+    mask: Mask,
+    /// Pending transfer, and whether a complete frame has started since it
+    /// was requested.
+    transfer: Option<(Transfer, bool)>,
+    border: Border,
+    /// The coloured game screen, kept while the screen is frozen.
+    colored: Vec<u32>,
+    output: Vec<u32>,
+}
+
+impl Sgb {
+    pub(super) fn new() -> Self {
+        let mut sgb = Self {
+            screen: Box::new([0; WIDTH * HEIGHT]),
+            command: Vec::with_capacity(16 * 7),
+            palettes: [DEFAULT_PALETTE; 4],
+            system_palettes: Box::new([DEFAULT_PALETTE; 512]),
+            attributes: [0; CELLS_X * CELLS_Y],
+            attribute_files: Box::new([[0; CELLS_X * CELLS_Y]; 45]),
+            mask: Mask::None,
+            transfer: None,
+            border: Border::new(),
+            colored: vec![0; WIDTH * HEIGHT],
+            output: vec![0; SGB_WIDTH * SGB_HEIGHT],
+        };
+        sgb.compose();
+        sgb
+    }
+
+    pub(super) fn write_shade(&mut self, idx: usize, color: Color) {
+        self.screen[idx] = shade_index(color) as u8;
+    }
+
+    pub(super) fn fill_shade(&mut self, color: Color) {
+        self.screen.fill(shade_index(color) as u8);
+    }
+
+    /// The last composed frame, [SGB_WIDTH] by [SGB_HEIGHT].
+    pub(super) fn output(&self) -> &[u32] {
+        &self.output
+    }
+
+    /// Handle a packet from the joypad. Commands which span several packets
+    /// are run once the last one arrives.
+    pub(super) fn receive_packet(&mut self, packet: [u8; 16]) {
+        if self.command.is_empty() && packet[0] & 0x7 == 0 {
+            return;
+        }
+
+        self.command.extend_from_slice(&packet);
+        if self.command.len() / 16 < (self.command[0] & 0x7) as usize {
+            return;
+        }
+
+        let command = std::mem::take(&mut self.command);
+        self.run_command(&command);
+        self.command = command;
+        self.command.clear();
+    }
+
+    fn run_command(&mut self, data: &[u8]) {
+        match data[0] >> 3 {
+            0x00 => self.set_palettes(0, 1, &data[1..]),
+            0x01 => self.set_palettes(2, 3, &data[1..]),
+            0x02 => self.set_palettes(0, 3, &data[1..]),
+            0x03 => self.set_palettes(1, 2, &data[1..]),
+            0x04 => self.attribute_blocks(&data[1..]),
+            0x05 => self.attribute_lines(&data[1..]),
+            0x06 => self.attribute_divide(&data[1..]),
+            0x07 => self.attribute_characters(&data[1..]),
+            0x0A => self.set_system_palettes(&data[1..]),
+            0x0B => self.request_transfer(Transfer::Palettes),
+            0x13 => self.request_transfer(Transfer::Tiles {
+                upper: data[1] & 0x1 != 0,
+            }),
+            0x14 => self.request_transfer(Transfer::Border),
+            0x15 => self.request_transfer(Transfer::Attributes),
+            0x16 => self.set_attribute_file(data[1]),
+            0x17 => {
+                self.mask = match data[1] & 0x3 {
+                    0 => Mask::None,
+                    1 => Mask::Freeze,
+                    2 => Mask::Black,
+                    _ => Mask::Color0,
+                }
+            }
+            // MLT_REQ is handled by the joypad, sound, SNES and the
+            // remaining system commands are ignored.
+            _ => {}
+        }
+    }
+
+    /// PAL01, PAL23, PAL03 and PAL12. Colour 0 is shared by all palettes.
+    fn set_palettes(&mut self, first: usize, second: usize, data: &[u8]) {
+        let color = |i: usize| u16::from_le_bytes([data[2 * i], data[2 * i + 1]]);
+
+        for palette in self.palettes.iter_mut() {
+            palette[0] = color(0);
+        }
+        for shade in 1..4 {
+            self.palettes[first][shade] = color(shade);
+            self.palettes[second][shade] = color(shade + 3);
+        }
+    }
+
+    /// PAL_SET, copy four of the palettes sent by PAL_TRN.
+    fn set_system_palettes(&mut self, data: &[u8]) {
+        for (i, palette) in self.palettes.iter_mut().enumerate() {
+            let number = u16::from_le_bytes([data[2 * i], data[2 * i + 1]]) & 0x1FF;
+            *palette = self.system_palettes[number as usize];
+        }
+
+        let shared = self.palettes[0][0];
+        for palette in self.palettes.iter_mut() {
+            palette[0] = shared;
+        }
+
+        let flags = data[8];
+        if flags & 0x80 != 0 {
+            self.set_attribute_file(flags & 0x3F);
+        }
+        if flags & 0x40 != 0 {
+            self.mask = Mask::None;
+        }
+    }
+
+    /// ATTR_SET, apply one of the attribute files sent by ATTR_TRN.
+    fn set_attribute_file(&mut self, value: u8) {
+        if let Some(file) = self.attribute_files.get((value & 0x3F) as usize) {
+            self.attributes = *file;
+        }
+        if value & 0x40 != 0 {
+            self.mask = Mask::None;
+        }
+    }
+
+    /// ATTR_BLK, colour the inside, outside and outline of rectangles.
+    fn attribute_blocks(&mut self, data: &[u8]) {
+        let count = (data[0] as usize).min(0x12);
+
+        for block in data[1..].chunks_exact(6).take(count) {
+            let mut control = block[0] & 0x7;
+            let mut line = (block[1] >> 2) & 0x3;
+            let inside = block[1] & 0x3;
+            let outside = (block[1] >> 4) & 0x3;
+            let (x1, y1, x2, y2) = (block[2], block[3], block[4], block[5]);
+
+            // Changing only the inside or outside also changes the outline.
+            if control == 0b001 {
+                control = 0b011;
+                line = inside;
+            } else if control == 0b100 {
+                control = 0b110;
+                line = outside;
+            }
+
+            for y in 0..CELLS_Y as u8 {
+                for x in 0..CELLS_X as u8 {
+                    let within = (x1..=x2).contains(&x) && (y1..=y2).contains(&y);
+                    let on_line = within && (x == x1 || x == x2 || y == y1 || y == y2);
+
+                    let palette = if on_line {
+                        (control & 0b010 != 0).then_some(line)
+                    } else if within {
+                        (control & 0b001 != 0).then_some(inside)
+                    } else {
+                        (control & 0b100 != 0).then_some(outside)
+                    };
+
+                    if let Some(palette) = palette {
+                        self.attributes[y as usize * CELLS_X + x as usize] = palette;
+                    }
+                }
+            }
+        }
+    }
+
+    /// ATTR_LIN, colour whole rows or columns.
+    fn attribute_lines(&mut self, data: &[u8]) {
+        let count = (data[0] as usize).min(0x6E);
+
+        for &line in data[1..].iter().take(count) {
+            let number = (line & 0x1F) as usize;
+            let palette = (line >> 5) & 0x3;
+
+            if line & 0x80 != 0 {
+                if number < CELLS_Y {
+                    self.attributes[number * CELLS_X..(number + 1) * CELLS_X].fill(palette);
+                }
+            } else if number < CELLS_X {
+                for y in 0..CELLS_Y {
+                    self.attributes[y * CELLS_X + number] = palette;
+                }
+            }
+        }
+    }
+
+    /// ATTR_DIV, split the screen in two along a row or column.
+    fn attribute_divide(&mut self, data: &[u8]) {
+        let after = data[0] & 0x3;
+        let before = (data[0] >> 2) & 0x3;
+        let on_line = (data[0] >> 4) & 0x3;
+        let horizontal = data[0] & 0x40 != 0;
+        let line = data[1] as usize;
+
+        for y in 0..CELLS_Y {
+            for x in 0..CELLS_X {
+                let position = if horizontal { y } else { x };
+                self.attributes[y * CELLS_X + x] = match position.cmp(&line) {
+                    std::cmp::Ordering::Less => before,
+                    std::cmp::Ordering::Equal => on_line,
+                    std::cmp::Ordering::Greater => after,
+                };
+            }
+        }
+    }
+
+    /// ATTR_CHR, colour individual cells from a starting cell onwards.
+    fn attribute_characters(&mut self, data: &[u8]) {
+        let (mut x, mut y) = (data[0] as usize, data[1] as usize);
+        let count = (u16::from_le_bytes([data[2], data[3]]) as usize).min(CELLS_X * CELLS_Y);
+        let vertical = data[4] & 0x1 != 0;
+
+        for i in 0..count {
+            let Some(&byte) = data.get(5 + i / 4) else {
+                break;
+            };
+            if x >= CELLS_X || y >= CELLS_Y {
+                break;
+            }
+
+            self.attributes[y * CELLS_X + x] = (byte >> (6 - 2 * (i % 4))) & 0x3;
+
+            if vertical {
+                y += 1;
+                if y == CELLS_Y {
+                    y = 0;
+                    x += 1;
+                }
+            } else {
+                x += 1;
+                if x == CELLS_X {
+                    x = 0;
+                    y += 1;
+                }
+            }
+        }
+    }
+
+    fn request_transfer(&mut self, transfer: Transfer) {
+        self.transfer = Some((transfer, false));
+    }
+
+    /// Run any pending transfer, and compose the output from the completed
+    /// frame.
+    pub(super) fn finish_frame(&mut self) {
+        match self.transfer {
+            Some((transfer, true)) => {
+                self.transfer = None;
+                let data = self.transfer_data();
+                self.run_transfer(transfer, &data);
+            }
+            Some((transfer, false)) => self.transfer = Some((transfer, true)),
+            None => {}
+        }
+
+        self.compose();
+    }
+
+    /// Read the screen back as tile data, as the SNES does. Tiles are read
+    /// left to right, top to bottom, each as 16 bytes of 2bpp data.
+    fn transfer_data(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(TRANSFER_SIZE);
+
+        for tile in 0..TRANSFER_SIZE / 16 {
+            let (column, row) = (tile % CELLS_X, tile / CELLS_X);
+
+            for y in 0..8 {
+                let line = (row * 8 + y) * WIDTH + column * 8;
+                let (mut low, mut high) = (0, 0);
+
+                for (x, &shade) in self.screen[line..line + 8].iter().enumerate() {
+                    low |= (shade & 0x1) << (7 - x);
+                    high |= (shade >> 1) << (7 - x);
+                }
+
+                data.push(low);
+                data.push(high);
+            }
+        }
+
+        data
+    }
+
+    fn run_transfer(&mut self, transfer: Transfer, data: &[u8]) {
+        match transfer {
+            Transfer::Palettes => {
+                for (palette, colors) in self.system_palettes.iter_mut().zip(data.chunks(8)) {
+                    for (shade, color) in palette.iter_mut().zip(colors.chunks(2)) {
+                        *shade = u16::from_le_bytes([color[0], color[1]]);
+                    }
+                }
+            }
+            Transfer::Attributes => {
+                for (file, bytes) in self.attribute_files.iter_mut().zip(data.chunks(90)) {
+                    for (i, attribute) in file.iter_mut().enumerate() {
+                        *attribute = (bytes[i / 4] >> (6 - 2 * (i % 4))) & 0x3;
+                    }
+                }
+            }
+            Transfer::Tiles { upper } => self.border.load_tiles(upper, data),
+            Transfer::Border => self.border.load_map(data),
+        }
+    }
+
+    /// Draw the border, then the coloured game screen over it.
+    fn compose(&mut self) {
+        if self.mask == Mask::None {
+            for (i, color) in self.colored.iter_mut().enumerate() {
+                let (x, y) = (i % WIDTH, i / WIDTH);
+                let palette = self.attributes[y / 8 * CELLS_X + x / 8] as usize;
+                *color = rgb555(self.palettes[palette][self.screen[i] as usize]);
+            }
+        }
+
+        let backdrop = rgb555(self.palettes[0][0]);
+        self.border.draw(&mut self.output, backdrop);
+
+        for (y, row) in self.colored.chunks_exact(WIDTH).enumerate() {
+            let start = (SCREEN_Y + y) * SGB_WIDTH + SCREEN_X;
+            let line = &mut self.output[start..start + WIDTH];
+
+            match self.mask {
+                Mask::None | Mask::Freeze => line.copy_from_slice(row),
+                Mask::Black => line.fill(0),
+                Mask::Color0 => line.fill(backdrop),
+            }
+        }
+    }
+}
+
+impl PPU {
+    /// Emulate a Super Game Boy, colouring the screen and adding a border
+    /// as the game asks. Frames sent to the renderer become [SGB_WIDTH] by
+    /// [SGB_HEIGHT], with the game screen in the middle.
+    pub fn set_sgb_enabled(&mut self, enabled: bool) {
+        self.sgb = enabled.then(|| Box::new(Sgb::new()));
+    }
+
+    pub fn sgb_enabled(&self) -> bool {
+        self.sgb.is_some()
+    }
+
+    /// Handle a Super Game Boy command packet sent through the joypad.
+    pub(crate) fn receive_sgb_packet(&mut self, packet: [u8; 16]) {
+        if let Some(sgb) = self.sgb.as_mut() {
+            sgb.receive_packet(packet);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(command: u8, data: &[u8]) -> [u8; 16] {
+        let mut packet = [0; 16];
+        packet[0] = command << 3 | 1;
+        packet[1..=data.len()].copy_from_slice(data);
+        packet
+    }
+
+    fn pixel(sgb: &Sgb, x: usize, y: usize) -> u32 {
+        sgb.output()[(SCREEN_Y + y) * SGB_WIDTH + SCREEN_X + x]
+    }
+
+    /// Draw transfer data on the screen the way a game would, as tiles.
+    fn draw_transfer(sgb: &mut Sgb, data: &[u8]) {
+        for (tile, bytes) in data.chunks(16).enumerate() {
+            let (column, row) = (tile % CELLS_X, tile / CELLS_X);
+            for y in 0..8 {
+                for x in 0..8 {
+                    let low = (bytes[y * 2] >> (7 - x)) & 0x1;
+                    let high = (bytes[y * 2 + 1] >> (7 - x)) & 0x1;
+                    sgb.screen[(row * 8 + y) * WIDTH + column * 8 + x] = high << 1 | low;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn colours_screen_with_palettes() {
+        let mut sgb = Sgb::new();
+
+        // PAL01: shared colour 0, then colours 1-3 of palettes 0 and 1.
+        let colors: Vec<u8> = [0x7FFF, 0x001F, 0, 0, 0x03E0, 0, 0x7C00]
+            .iter()
+            .flat_map(|color: &u16| color.to_le_bytes())
+            .collect();
+        sgb.receive_packet(packet(0x00, &colors));
+
+        // ATTR_DIV: palette 1 from column 10 onwards.
+        sgb.receive_packet(packet(0x06, &[0b00_01_00_01, 10]));
+
+        sgb.screen.fill(1);
+        sgb.screen[WIDTH * HEIGHT - 1] = 3;
+        sgb.screen[0] = 0;
+        sgb.finish_frame();
+
+        assert_eq!(pixel(&sgb, 0, 0), 0xFFFFFF);
+        assert_eq!(pixel(&sgb, 79, 0), 0xFF0000);
+        assert_eq!(pixel(&sgb, 80, 0), 0x00FF00);
+        assert_eq!(pixel(&sgb, WIDTH - 1, HEIGHT - 1), 0x0000FF);
+        // The border is transparent until one is sent, showing colour 0.
+        assert_eq!(sgb.output()[0], 0xFFFFFF);
+    }
+
+    #[test]
+    fn attribute_blocks() {
+        let mut sgb = Sgb::new();
+
+        // Only the inside set: the outline takes the inside palette.
+        sgb.receive_packet(packet(0x04, &[1, 0b001, 0b11_10_01, 2, 2, 5, 5]));
+        assert_eq!(sgb.attributes[2 * CELLS_X + 2], 1);
+        assert_eq!(sgb.attributes[3 * CELLS_X + 3], 1);
+        assert_eq!(sgb.attributes[0], 0);
+
+        // Outline and outside.
+        sgb.receive_packet(packet(0x04, &[1, 0b110, 0b11_10_01, 2, 2, 5, 5]));
+        assert_eq!(sgb.attributes[2 * CELLS_X + 2], 2);
+        assert_eq!(sgb.attributes[3 * CELLS_X + 3], 1);
+        assert_eq!(sgb.attributes[0], 3);
+    }
+
+    #[test]
+    fn attribute_lines_and_characters() {
+        let mut sgb = Sgb::new();
+
+        // Row 3 palette 2, column 4 palette 1.
+        sgb.receive_packet(packet(0x05, &[2, 0x80 | 2 << 5 | 3, 1 << 5 | 4]));
+        assert_eq!(sgb.attributes[3 * CELLS_X], 2);
+        assert_eq!(sgb.attributes[4], 1);
+        assert_eq!(sgb.attributes[3 * CELLS_X + 4], 1);
+
+        // Five cells from the end of row 0, wrapping to the next row.
+        sgb.receive_packet(packet(0x07, &[18, 0, 5, 0, 0, 0b11_10_01_11, 0b10_000000]));
+        assert_eq!(sgb.attributes[18], 3);
+        assert_eq!(sgb.attributes[19], 2);
+        assert_eq!(sgb.attributes[CELLS_X], 1);
+        assert_eq!(sgb.attributes[CELLS_X + 1], 3);
+        assert_eq!(sgb.attributes[CELLS_X + 2], 2);
+    }
+
+    #[test]
+    fn commands_spanning_several_packets() {
+        let mut sgb = Sgb::new();
+
+        // ATTR_BLK with three blocks, the last split across two packets.
+        let mut first = packet(0x04, &[3, 0b001, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0b001, 2]);
+        first[0] = 0x04 << 3 | 2;
+        sgb.receive_packet(first);
+        assert_eq!(sgb.attributes[0], 0);
+
+        let mut second = [0; 16];
+        second[..4].copy_from_slice(&[1, 1, 1, 1]);
+        sgb.receive_packet(second);
+        assert_eq!(sgb.attributes[0], 1);
+        assert_eq!(sgb.attributes[CELLS_X + 1], 2);
+    }
+
+    #[test]
+    fn system_palettes_and_attribute_files() {
+        let mut sgb = Sgb::new();
+
+        let mut palettes = vec![0; TRANSFER_SIZE];
+        // Palette 5: colour 0 red, colour 3 blue.
+        palettes[40..42].copy_from_slice(&0x001Fu16.to_le_bytes());
+        palettes[46..48].copy_from_slice(&0x7C00u16.to_le_bytes());
+        draw_transfer(&mut sgb, &palettes);
+        sgb.receive_packet(packet(0x0B, &[]));
+        sgb.finish_frame();
+        sgb.finish_frame();
+        assert_eq!(sgb.transfer, None);
+
+        let mut files = vec![0; TRANSFER_SIZE];
+        // File 1: the first cell uses palette 3.
+        files[90] = 0b11_000000;
+        draw_transfer(&mut sgb, &files);
+        sgb.receive_packet(packet(0x15, &[]));
+        sgb.finish_frame();
+        sgb.finish_frame();
+
+        // PAL_SET: palette 5 as palette 3, applying attribute file 1.
+        sgb.receive_packet(packet(0x0A, &[0, 0, 0, 0, 0, 0, 5, 0, 0x80 | 1]));
+        sgb.screen.fill(3);
+        sgb.finish_frame();
+
+        assert_eq!(pixel(&sgb, 0, 0), 0x0000FF);
+        assert_eq!(pixel(&sgb, 8, 0), rgb555(0x0000));
+    }
+
+    #[test]
+    fn border_transfer() {
+        let mut sgb = Sgb::new();
+
+        // Upper tile 128: every pixel colour 1.
+        let mut tiles = vec![0; TRANSFER_SIZE];
+        for row in 0..8 {
+            tiles[row * 2] = 0xFF;
+        }
+        draw_transfer(&mut sgb, &tiles);
+        sgb.receive_packet(packet(0x13, &[1]));
+        // The frame in progress when the command arrives isn't used.
+        sgb.finish_frame();
+        assert!(sgb.transfer.is_some());
+        sgb.finish_frame();
+        assert_eq!(sgb.transfer, None);
+
+        let mut map = vec![0; TRANSFER_SIZE];
+        map[0..2].copy_from_slice(&(128u16 | 4 << 10).to_le_bytes());
+        map[0x802..0x804].copy_from_slice(&0x03E0u16.to_le_bytes());
+        draw_transfer(&mut sgb, &map);
+        sgb.receive_packet(packet(0x14, &[]));
+        sgb.finish_frame();
+        sgb.finish_frame();
+
+        assert_eq!(sgb.output()[0], 0x00FF00);
+        assert_eq!(sgb.output()[7 * SGB_WIDTH + 7], 0x00FF00);
+        assert_eq!(sgb.output()[8], rgb555(DEFAULT_PALETTE[0]));
+    }
+
+    #[test]
+    fn mask() {
+        let mut sgb = Sgb::new();
+        sgb.screen.fill(3);
+        sgb.finish_frame();
+        let black = pixel(&sgb, 0, 0);
+
+        sgb.receive_packet(packet(0x17, &[1]));
+        sgb.screen.fill(0);
+        sgb.finish_frame();
+        assert_eq!(pixel(&sgb, 0, 0), black);
+
+        sgb.receive_packet(packet(0x17, &[2]));
+        sgb.finish_frame();
+        assert_eq!(pixel(&sgb, 0, 0), 0);
+
+        sgb.receive_packet(packet(0x17, &[3]));
+        sgb.finish_frame();
+        assert_eq!(pixel(&sgb, 0, 0), rgb555(DEFAULT_PALETTE[0]));
+
+        sgb.receive_packet(packet(0x17, &[0]));
+        sgb.finish_frame();
+        assert_eq!(pixel(&sgb, 0, 0), rgb555(DEFAULT_PALETTE[0]));
+    }
+
+    #[test]
+    fn renderer_gets_frames_with_border() {
+        let frames = Arc::new(FrameBuffer::new());
+        let mut ppu = PPU::new(frames.clone());
+        ppu.set_sgb_enabled(true);
+        ppu.write_lcdc(0x91);
+
+        for _ in 0..(2 * 17556) {
+            ppu.step(1);
+        }
+
+        frames.with_latest(|frame| {
+            assert_eq!((frame.width, frame.height), (SGB_WIDTH, SGB_HEIGHT));
+            assert_eq!(frame.pixels.len(), SGB_WIDTH * SGB_HEIGHT);
+        });
+        assert_eq!(ppu.screenshot().width, SGB_WIDTH);
+    }
+}
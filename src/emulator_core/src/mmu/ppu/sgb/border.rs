@@ -0,0 +1,114 @@
+use super::*;
+
+/// Number of tiles across and down the border.
+const MAP_WIDTH: usize = SGB_WIDTH / 8;
+const MAP_HEIGHT: usize = SGB_HEIGHT / 8;
+
+/// The border around the game screen, made of SNES tiles with 4 bits per
+/// pixel. Border palettes are numbered 4-7 by the SNES, and colour 0 of every
+/// palette is transparent, showing the backdrop behind.
+pub(super) struct Border {
+    /// 256 tiles of 32 bytes, loaded 128 at a time by CHR_TRN.
+    tiles: Box<[u8; 256 * 32]>,
+    /// Tile number, palette and flips of each tile on the border, loaded by
+    /// PCT_TRN.
+    map: Box<[u16; MAP_WIDTH * MAP_HEIGHT]>,
+    palettes: [[u16; 16]; 4],
+}
+
+impl Border {
+    pub(super) fn new() -> Self {
+        Self {
+            tiles: Box::new([0; 256 * 32]),
+            map: Box::new([0; MAP_WIDTH * MAP_HEIGHT]),
+            palettes: [[0; 16]; 4],
+        }
+    }
+
+    /// CHR_TRN, load either the lower or upper half of the tiles.
+    pub(super) fn load_tiles(&mut self, upper: bool, data: &[u8]) {
+        let start = if upper { 128 * 32 } else { 0 };
+        self.tiles[start..start + 128 * 32].copy_from_slice(&data[..128 * 32]);
+    }
+
+    /// PCT_TRN, load the tile map followed by the palettes.
+    pub(super) fn load_map(&mut self, data: &[u8]) {
+        for (entry, bytes) in self.map.iter_mut().zip(data.chunks_exact(2)) {
+            *entry = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+
+        let colors = data[0x800..0x880].chunks_exact(2);
+        for (color, bytes) in self.palettes.iter_mut().flatten().zip(colors) {
+            *color = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+    }
+
+    /// Draw the whole border, filling transparent pixels with the backdrop.
+    pub(super) fn draw(&self, output: &mut [u32], backdrop: u32) {
+        for (i, &entry) in self.map.iter().enumerate() {
+            let (column, row) = (i % MAP_WIDTH, i / MAP_WIDTH);
+            let tile = &self.tiles[(entry & 0xFF) as usize * 32..][..32];
+            let palette = &self.palettes[((entry >> 10) & 0x7).saturating_sub(4) as usize];
+            let flip_x = entry & 0x4000 != 0;
+            let flip_y = entry & 0x8000 != 0;
+
+            for y in 0..8 {
+                let line = if flip_y { 7 - y } else { y };
+                let planes = [
+                    tile[line * 2],
+                    tile[line * 2 + 1],
+                    tile[16 + line * 2],
+                    tile[16 + line * 2 + 1],
+                ];
+
+                for x in 0..8 {
+                    let bit = if flip_x { x } else { 7 - x };
+                    let index = planes.iter().enumerate().fold(0, |index, (plane, byte)| {
+                        index | ((byte >> bit) & 0x1) << plane
+                    });
+
+                    output[(row * 8 + y) * SGB_WIDTH + column * 8 + x] = match index {
+                        0 => backdrop,
+                        index => rgb555(palette[index as usize]),
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_tiles_with_flips() {
+        let mut border = Border::new();
+
+        // Tile 1: the top left pixel uses colour 1, the rest colour 15.
+        let mut tiles = vec![0; 4096];
+        tiles[32..64].fill(0xFF);
+        tiles[33] = 0x7F;
+        tiles[48] = 0x7F;
+        tiles[49] = 0x7F;
+        border.load_tiles(false, &tiles);
+
+        let mut map = vec![0; 4096];
+        // Top left tile: tile 1, palette 4. Next tile: flipped both ways.
+        map[0..2].copy_from_slice(&(1u16 | 4 << 10).to_le_bytes());
+        map[2..4].copy_from_slice(&(1u16 | 4 << 10 | 0xC000).to_le_bytes());
+        map[0x802..0x804].copy_from_slice(&0x001Fu16.to_le_bytes());
+        map[0x81E..0x820].copy_from_slice(&0x7C00u16.to_le_bytes());
+        border.load_map(&map);
+
+        let mut output = vec![0; SGB_WIDTH * SGB_HEIGHT];
+        border.draw(&mut output, 0x123456);
+
+        assert_eq!(output[0], 0xFF0000);
+        assert_eq!(output[1], 0x0000FF);
+        assert_eq!(output[7 * SGB_WIDTH + 15], 0xFF0000);
+        assert_eq!(output[8], 0x0000FF);
+        assert_eq!(output[16], 0x123456);
+        assert_eq!(output[SGB_WIDTH * SGB_HEIGHT - 1], 0x123456);
+    }
+}
@@ -3,7 +3,6 @@ use super::{Color, Pixel};
 /// This struct is used for Object palette registers
 /// which there are two of. It assigns gray shades to color ids
 /// of pixels for sprites.
-
 pub struct SpritePalette(u8);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -34,6 +33,15 @@ impl SpritePalette {
         }
     }
 
+    /// Shade the register assigns to the pixel, ignoring the fact that
+    /// Color0 is always transparent when rendering sprites.
+    pub(super) fn color_at(&self, pixel: Pixel) -> Color {
+        match pixel {
+            Pixel::Color0 => SpriteColor::from(self.0 & 0b00000011).into(),
+            _ => self.color_from_pixel(pixel).into(),
+        }
+    }
+
     fn color_1(&self) -> SpriteColor {
         (self.0 >> 2 & 0b00000011).into()
     }
@@ -112,4 +120,15 @@ mod tests {
             SpriteColor::DarkGray
         );
     }
+
+    #[test]
+    fn color_at() {
+        let mut palette = SpritePalette::new();
+        palette.write(0b10110011);
+
+        assert_eq!(palette.color_at(Pixel::Color0), Color::Black);
+        assert_eq!(palette.color_at(Pixel::Color1), Color::White);
+        assert_eq!(palette.color_at(Pixel::Color2), Color::Black);
+        assert_eq!(palette.color_at(Pixel::Color3), Color::DarkGray);
+    }
 }
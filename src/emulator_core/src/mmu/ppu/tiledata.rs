@@ -56,6 +56,14 @@ impl<'a> TileData {
         BackgroundTile::new(&self.data[start..start + 16])
     }
 
+    /// Tile by its position in tile data (0-383), regardless of any
+    /// addressing method.
+    pub(super) fn tile_by_index(&'a self, index: usize) -> BackgroundTile<'a> {
+        let start = index * 16;
+
+        BackgroundTile::new(&self.data[start..start + 16])
+    }
+
     pub(super) fn sprite_tile_at(
         &'a self,
         tile_number: u8,
@@ -173,6 +181,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn tile_by_index() {
+        let smiley_face = smiley_face();
+        let mut tiledata = TileData::new();
+
+        for i in 0..16 {
+            tiledata.write(383 * 16 + i, smiley_face[i as usize]);
+        }
+
+        assert_smiley_face_bg_tile(tiledata.tile_by_index(383));
+    }
+
     mod sprite_tile_at {
         use super::*;
 
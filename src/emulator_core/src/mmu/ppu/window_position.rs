@@ -12,20 +12,3 @@ pub enum WindowPositionRegister {
     WX,
     WY,
 }
-
-
-impl WindowPosition {
-    // Accessor for the PPU to get the 'processed' value
-    // of the register for rendering
-    pub(super) fn wy(&self) -> u8 {
-        self.wy
-    }
-
-    // Accessor for the PPU to get the 'processed' value
-    // of the register for rendering. For WX this means
-    // subtracting 7 from stored value (clamped to 0).
-    // Since the window can only render pixels where X >= 7.
-    pub(super) fn wx(&self) -> u8 {
-        self.wx.wrapping_sub(7)
-    }
-}
@@ -0,0 +1,149 @@
+//! Minimal PNG encoder, enough to save frames without pulling in an image
+//! library. Images are written as 8-bit RGB with the pixel data stored in
+//! uncompressed deflate blocks, so files are larger than they need to be
+//! but any PNG decoder can read them.
+use std::io::{self, Write};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+/// Largest amount of data that fits in a single stored deflate block.
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+/// Write an image of 0x00RRGGBB pixels as a PNG.
+pub(crate) fn encode(
+    writer: &mut impl Write,
+    width: usize,
+    height: usize,
+    pixels: &[u32],
+) -> io::Result<()> {
+    assert_eq!(
+        pixels.len(),
+        width * height,
+        "Pixel count should match size"
+    );
+
+    writer.write_all(&SIGNATURE)?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, RGB, default compression, filtering and no
+    // interlacing.
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(writer, b"IHDR", &header)?;
+
+    // Each row starts with a filter type byte, 0 for no filtering.
+    let mut raw = Vec::with_capacity(height * (1 + width * 3));
+    for row in pixels.chunks(width.max(1)) {
+        raw.push(0);
+        for pixel in row {
+            let [_, r, g, b] = pixel.to_be_bytes();
+            raw.extend_from_slice(&[r, g, b]);
+        }
+    }
+    write_chunk(writer, b"IDAT", &zlib_stored(&raw))?;
+
+    write_chunk(writer, b"IEND", &[])
+}
+
+fn write_chunk(writer: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+
+    let crc = crc32(kind.iter().chain(data));
+    writer.write_all(&crc.to_be_bytes())
+}
+
+/// Wrap data in a zlib stream made of stored (uncompressed) deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / MAX_STORED_BLOCK * 5 + 16);
+    // Deflate with a 32K window, no preset dictionary.
+    out.extend_from_slice(&[0x78, 0x01]);
+
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"IEND".iter()), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn encodes_header_and_chunks() {
+        let mut out = Vec::new();
+        encode(&mut out, 2, 1, &[0xFF0000, 0x0000FF]).unwrap();
+
+        assert_eq!(out[..8], SIGNATURE);
+        assert_eq!(&out[12..16], b"IHDR");
+        assert_eq!(&out[16..20], &2u32.to_be_bytes());
+        assert_eq!(&out[20..24], &1u32.to_be_bytes());
+        // IEND is always the same 12 bytes.
+        assert_eq!(
+            out[out.len() - 12..],
+            [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]
+        );
+
+        // The single row, after the zlib header and stored block header.
+        let idat = 8 + 25;
+        assert_eq!(&out[idat + 4..idat + 8], b"IDAT");
+        assert_eq!(out[idat + 8..idat + 15], [0x78, 0x01, 1, 7, 0, !7, 0xFF]);
+        assert_eq!(out[idat + 15..idat + 22], [0, 0xFF, 0, 0, 0, 0, 0xFF]);
+    }
+
+    #[test]
+    fn splits_large_images_into_blocks() {
+        let data = vec![7; MAX_STORED_BLOCK + 10];
+        let stream = zlib_stored(&data);
+
+        // Header, two block headers, data and checksum
+        assert_eq!(stream.len(), 2 + 5 + 5 + data.len() + 4);
+        assert_eq!(stream[2], 0);
+        assert_eq!(stream[2 + 5 + MAX_STORED_BLOCK], 1);
+    }
+}
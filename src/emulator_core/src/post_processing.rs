@@ -0,0 +1,43 @@
+//! [Renderer] adapters which post-process frames before passing them on to
+//! another renderer, to get closer to the look of the original LCD. Each
+//! adapter wraps an inner renderer, so they can be stacked:
+//!
+//! ```
+//! use std::sync::Arc;
+//! use emulator_core::{FrameBuffer, Ghosting, PixelGrid, ToneCurve};
+//!
+//! let frames = Arc::new(FrameBuffer::new());
+//! let renderer = Ghosting::new(ToneCurve::dmg_green(PixelGrid::new(frames.clone(), 4)), 0.5);
+//! ```
+//!
+//! Each adapter borrows the [Frame] it's given and passes on a new one
+//! borrowing its own processed pixels, keeping the rest of the frame's
+//! metadata (number, timestamp and palette indices) as it was.
+mod ghosting;
+mod pixel_grid;
+mod tone_curve;
+
+pub use ghosting::Ghosting;
+pub use pixel_grid::PixelGrid;
+pub use tone_curve::ToneCurve;
+
+/// Split a 0x00RRGGBB pixel into its channels.
+fn channels(pixel: u32) -> [u8; 3] {
+    [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]
+}
+
+/// Join channels back into a 0x00RRGGBB pixel.
+fn from_channels([r, g, b]: [u8; 3]) -> u32 {
+    (r as u32) << 16 | (g as u32) << 8 | b as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_and_join_channels() {
+        assert_eq!(channels(0x123456), [0x12, 0x34, 0x56]);
+        assert_eq!(from_channels([0x12, 0x34, 0x56]), 0x123456);
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::Mutex;
+
+use super::*;
+use crate::mmu::{Frame, Renderer};
+
+/// Blends each frame with the ones before it, like the slow response time
+/// of the original LCD. Some games rely on this, flickering sprites on
+/// alternate frames to make them look transparent.
+pub struct Ghosting<R: Renderer> {
+    inner: R,
+    persistence: f32,
+    previous: Mutex<Vec<u32>>,
+}
+
+impl<R: Renderer> Ghosting<R> {
+    /// Persistence is how much of the previous output remains in each new
+    /// frame, clamped to 0.0 (no ghosting) to 1.0 (the image never changes).
+    pub fn new(inner: R, persistence: f32) -> Self {
+        Self {
+            inner,
+            persistence: persistence.clamp(0.0, 1.0),
+            previous: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl<R: Renderer> Renderer for Ghosting<R> {
+    fn render(&self, frame: &Frame) {
+        let mut previous = self.previous.lock().expect("Should lock previous frame");
+
+        // Nothing to blend with for the first frame, or if the size changes.
+        if previous.len() != frame.pixels.len() {
+            *previous = frame.pixels.to_vec();
+        } else {
+            for (old, &new) in previous.iter_mut().zip(frame.pixels) {
+                *old = blend(*old, new, self.persistence);
+            }
+        }
+
+        self.inner.render(&Frame {
+            pixels: &previous,
+            ..*frame
+        });
+    }
+}
+
+fn blend(old: u32, new: u32, persistence: f32) -> u32 {
+    let (old, new) = (channels(old), channels(new));
+    let mix = |i: usize| {
+        (old[i] as f32 * persistence + new[i] as f32 * (1.0 - persistence)).round() as u8
+    };
+
+    from_channels([mix(0), mix(1), mix(2)])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::mmu::FrameBuffer;
+
+    fn render(renderer: &impl Renderer, value: u32) {
+        let pixels = [value; 4];
+        renderer.render(&Frame {
+            pixels: &pixels,
+            width: 2,
+            height: 2,
+            indexed: None,
+            number: 0,
+            timestamp: Duration::ZERO,
+        });
+    }
+
+    #[test]
+    fn first_frame_is_unchanged() {
+        let frames = Arc::new(FrameBuffer::new());
+        let ghosting = Ghosting::new(frames.clone(), 0.5);
+
+        render(&ghosting, 0xFFFFFF);
+
+        frames.with_latest(|frame| assert_eq!(frame.pixels, &[0xFFFFFF; 4]));
+    }
+
+    #[test]
+    fn blends_with_previous_output() {
+        let frames = Arc::new(FrameBuffer::new());
+        let ghosting = Ghosting::new(frames.clone(), 0.5);
+
+        render(&ghosting, 0xFFFFFF);
+        render(&ghosting, 0x000000);
+        frames.with_latest(|frame| assert_eq!(frame.pixels[0], 0x808080));
+
+        // The ghost fades out over following frames
+        render(&ghosting, 0x000000);
+        frames.with_latest(|frame| assert_eq!(frame.pixels[0], 0x404040));
+    }
+
+    #[test]
+    fn no_persistence_passes_frames_through() {
+        let frames = Arc::new(FrameBuffer::new());
+        let ghosting = Ghosting::new(frames.clone(), -1.0);
+
+        render(&ghosting, 0xFFFFFF);
+        render(&ghosting, 0x123456);
+
+        frames.with_latest(|frame| assert_eq!(frame.pixels[0], 0x123456));
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::Mutex;
+
+use super::*;
+use crate::mmu::{Frame, Renderer};
+
+/// How bright the gaps between pixels are, relative to the pixels.
+const DEFAULT_GAP_BRIGHTNESS: f32 = 0.75;
+
+/// Scales frames up by an integer factor, darkening the last row and column
+/// of each scaled pixel to mimic the visible gaps between the dots of the
+/// LCD.
+pub struct PixelGrid<R: Renderer> {
+    inner: R,
+    scale: usize,
+    gap_brightness: f32,
+    output: Mutex<Vec<u32>>,
+}
+
+impl<R: Renderer> PixelGrid<R> {
+    /// Scale must be at least 2 for the gaps to be visible. A scale of 0 is
+    /// treated as 1.
+    pub fn new(inner: R, scale: usize) -> Self {
+        Self {
+            inner,
+            scale: scale.max(1),
+            gap_brightness: DEFAULT_GAP_BRIGHTNESS,
+            output: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Change how bright the gaps are, from 0.0 (black) to 1.0 (no gaps).
+    pub fn with_gap_brightness(mut self, brightness: f32) -> Self {
+        self.gap_brightness = brightness.clamp(0.0, 1.0);
+        self
+    }
+}
+
+impl<R: Renderer> Renderer for PixelGrid<R> {
+    fn render(&self, frame: &Frame) {
+        let scale = self.scale;
+        let width = frame.width * scale;
+        let height = frame.height * scale;
+
+        let mut output = self.output.lock().expect("Should lock output");
+        output.resize(width * height, 0);
+
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = frame.pixels[(y / scale) * frame.width + x / scale];
+                let gap = scale > 1 && (x % scale == scale - 1 || y % scale == scale - 1);
+
+                output[y * width + x] = if gap {
+                    darken(pixel, self.gap_brightness)
+                } else {
+                    pixel
+                };
+            }
+        }
+
+        self.inner.render(&Frame {
+            pixels: &output,
+            width,
+            height,
+            ..*frame
+        });
+    }
+}
+
+fn darken(pixel: u32, brightness: f32) -> u32 {
+    let [r, g, b] = channels(pixel);
+    let scale = |channel: u8| (channel as f32 * brightness).round() as u8;
+
+    from_channels([scale(r), scale(g), scale(b)])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::mmu::FrameBuffer;
+
+    fn render(renderer: &impl Renderer, pixels: &[u32], width: usize, height: usize) {
+        renderer.render(&Frame {
+            pixels,
+            width,
+            height,
+            indexed: None,
+            number: 3,
+            timestamp: Duration::ZERO,
+        });
+    }
+
+    #[test]
+    fn scales_with_gaps() {
+        let frames = Arc::new(FrameBuffer::new());
+        let grid = PixelGrid::new(frames.clone(), 3).with_gap_brightness(0.5);
+
+        render(&grid, &[0xFFFFFF, 0x808080], 2, 1);
+
+        frames.with_latest(|frame| {
+            assert_eq!((frame.width, frame.height), (6, 3));
+            assert_eq!(frame.number, 3);
+            #[rustfmt::skip]
+            assert_eq!(
+                frame.pixels,
+                &[
+                    0xFFFFFF, 0xFFFFFF, 0x808080, 0x808080, 0x808080, 0x404040,
+                    0xFFFFFF, 0xFFFFFF, 0x808080, 0x808080, 0x808080, 0x404040,
+                    0x808080, 0x808080, 0x808080, 0x404040, 0x404040, 0x404040,
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn scale_of_one_passes_frames_through() {
+        let frames = Arc::new(FrameBuffer::new());
+        let grid = PixelGrid::new(frames.clone(), 1);
+
+        render(&grid, &[0xFFFFFF, 0x808080], 2, 1);
+
+        frames.with_latest(|frame| assert_eq!(frame.pixels, &[0xFFFFFF, 0x808080]));
+    }
+}
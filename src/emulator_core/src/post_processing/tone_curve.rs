@@ -0,0 +1,102 @@
+use std::sync::Mutex;
+
+use super::*;
+use crate::mmu::{Frame, Renderer};
+
+/// Shades of the original DMG screen, from darkest to lightest.
+const DMG_GREEN: [u32; 4] = [0x0F380F, 0x306230, 0x8BAC0F, 0x9BBC0F];
+
+/// Maps the brightness of each pixel onto a curve between four colours, from
+/// darkest to lightest. Brightness between the control points is blended
+/// smoothly, so intermediate shades (e.g. from [super::Ghosting]) are kept.
+pub struct ToneCurve<R: Renderer> {
+    inner: R,
+    curve: [[u8; 3]; 4],
+    output: Mutex<Vec<u32>>,
+}
+
+impl<R: Renderer> ToneCurve<R> {
+    pub fn new(inner: R, curve: [u32; 4]) -> Self {
+        Self {
+            inner,
+            curve: curve.map(channels),
+            output: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The green tint of the original DMG screen.
+    pub fn dmg_green(inner: R) -> Self {
+        Self::new(inner, DMG_GREEN)
+    }
+
+    fn map(&self, pixel: u32) -> u32 {
+        let [r, g, b] = channels(pixel);
+        // Rec. 601 luma, from 0.0 to 3.0 across the three segments of the curve
+        let luma = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) / 255.0 * 3.0;
+
+        let segment = (luma.floor() as usize).min(2);
+        let t = luma - segment as f32;
+        let (from, to) = (self.curve[segment], self.curve[segment + 1]);
+        let mix = |i: usize| (from[i] as f32 + (to[i] as f32 - from[i] as f32) * t).round() as u8;
+
+        from_channels([mix(0), mix(1), mix(2)])
+    }
+}
+
+impl<R: Renderer> Renderer for ToneCurve<R> {
+    fn render(&self, frame: &Frame) {
+        let mut output = self.output.lock().expect("Should lock output");
+        output.clear();
+        output.extend(frame.pixels.iter().map(|&pixel| self.map(pixel)));
+
+        self.inner.render(&Frame {
+            pixels: &output,
+            ..*frame
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::mmu::FrameBuffer;
+
+    #[test]
+    fn maps_default_palette_to_dmg_green() {
+        let curve = ToneCurve::dmg_green(FrameBuffer::new());
+
+        assert_eq!(curve.map(0xFFFFFF), 0x9BBC0F);
+        assert_eq!(curve.map(0x000000), 0x0F380F);
+        assert_eq!(curve.map(0x555555), 0x306230);
+        assert_eq!(curve.map(0xAAAAAA), 0x8BAC0F);
+    }
+
+    #[test]
+    fn blends_between_control_points() {
+        let curve = ToneCurve::new(FrameBuffer::new(), [0x000000, 0x000000, 0x000000, 0x0000FF]);
+
+        // Halfway through the last segment
+        let pixel = curve.map(0xD4D4D4);
+        assert!((0x7E..=0x81).contains(&pixel), "{pixel:06X}");
+    }
+
+    #[test]
+    fn renders_mapped_frame() {
+        let frames = Arc::new(FrameBuffer::new());
+        let curve = ToneCurve::dmg_green(frames.clone());
+        let pixels = [0xFFFFFF, 0x000000];
+
+        curve.render(&Frame {
+            pixels: &pixels,
+            width: 2,
+            height: 1,
+            indexed: None,
+            number: 0,
+            timestamp: std::time::Duration::ZERO,
+        });
+
+        frames.with_latest(|frame| assert_eq!(frame.pixels, &[0x9BBC0F, 0x0F380F]));
+    }
+}
@@ -0,0 +1,233 @@
+//! Records frames passed through a [Renderer] to a video file. Recording
+//! can be started and stopped at any time while the emulator is running.
+//!
+//! Output follows emulated time rather than wall clock time, so recordings
+//! play back at the DMG's real refresh rate of ~59.73 Hz regardless of how
+//! fast the emulator was running.
+mod gif;
+mod y4m;
+
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::mmu::{Frame, Renderer};
+
+/// The DMG refreshes the screen every 70224 T-cycles, at 4194304 T-cycles
+/// per second.
+const FRAME_RATE_NUMERATOR: u64 = 4_194_304;
+const FRAME_RATE_DENOMINATOR: u64 = 70_224;
+
+/// Video formats which can be recorded.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecordingFormat {
+    /// Animated GIF. Frames are quantised to at most 256 colours, and some
+    /// frames are dropped as GIF frame delays can't be shorter than 1/50s
+    /// in most viewers.
+    Gif,
+    /// YUV4MPEG2 stream (4:4:4), at the exact DMG frame rate.
+    Y4m,
+    /// Headerless RGB24 frames at the exact DMG frame rate. Can be read by
+    /// ffmpeg with `-f rawvideo -pix_fmt rgb24 -s <w>x<h> -r 4194304/70224`.
+    Raw,
+}
+
+impl RecordingFormat {
+    /// Usual file extension for the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordingFormat::Gif => "gif",
+            RecordingFormat::Y4m => "y4m",
+            RecordingFormat::Raw => "rgb",
+        }
+    }
+}
+
+impl FromStr for RecordingFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "gif" => Ok(RecordingFormat::Gif),
+            "y4m" => Ok(RecordingFormat::Y4m),
+            "raw" | "rgb" => Ok(RecordingFormat::Raw),
+            _ => Err(format!("Unknown recording format: {s}")),
+        }
+    }
+}
+
+/// Encodes frames in one of the [RecordingFormat]s.
+trait FrameEncoder: Send {
+    fn encode(&mut self, frame: &Frame) -> io::Result<()>;
+
+    /// Write anything left over and flush the output.
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// A [Renderer] adapter which passes every frame on to the inner renderer,
+/// and also encodes it to a file while recording.
+pub struct Recorder<R: Renderer> {
+    inner: R,
+    state: Mutex<RecorderState>,
+}
+
+#[derive(Default)]
+struct RecorderState {
+    encoder: Option<Box<dyn FrameEncoder>>,
+    /// First error hit while encoding, returned from [Recorder::stop].
+    error: Option<io::Error>,
+}
+
+impl<R: Renderer> Recorder<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            state: Mutex::new(RecorderState::default()),
+        }
+    }
+
+    /// Start recording to the given writer, from the next frame onwards.
+    /// Any recording already in progress is stopped first.
+    pub fn start(
+        &self,
+        format: RecordingFormat,
+        writer: impl Write + Send + 'static,
+    ) -> io::Result<()> {
+        let result = self.stop();
+
+        let encoder: Box<dyn FrameEncoder> = match format {
+            RecordingFormat::Gif => Box::new(gif::GifEncoder::new(writer)),
+            RecordingFormat::Y4m => Box::new(y4m::FixedRateEncoder::y4m(writer)),
+            RecordingFormat::Raw => Box::new(y4m::FixedRateEncoder::raw(writer)),
+        };
+        self.lock().encoder = Some(encoder);
+
+        result
+    }
+
+    /// Stop recording, finishing the file. Returns the first error hit
+    /// while recording, if there was one.
+    pub fn stop(&self) -> io::Result<()> {
+        let mut state = self.lock();
+
+        let finished = match state.encoder.take() {
+            Some(mut encoder) => encoder.finish(),
+            None => Ok(()),
+        };
+
+        match state.error.take() {
+            Some(err) => Err(err),
+            None => finished,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.lock().encoder.is_some()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RecorderState> {
+        self.state.lock().expect("Should lock recorder")
+    }
+}
+
+impl<R: Renderer> Renderer for Recorder<R> {
+    fn render(&self, frame: &Frame) {
+        {
+            let mut state = self.lock();
+            if let Some(encoder) = state.encoder.as_mut() {
+                // Stop recording on errors, rather than failing every frame.
+                if let Err(err) = encoder.encode(frame) {
+                    state.encoder = None;
+                    state.error = Some(err);
+                }
+            }
+        }
+
+        self.inner.render(frame);
+    }
+}
+
+/// Index of the frame slot at the given time since the start of a
+/// recording, with slots at the DMG frame rate.
+fn frame_slot(elapsed: Duration) -> u64 {
+    let nanos = elapsed.as_nanos() * FRAME_RATE_NUMERATOR as u128;
+    let period = FRAME_RATE_DENOMINATOR as u128 * 1_000_000_000;
+    ((nanos + period / 2) / period) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::mmu::FrameBuffer;
+    use crate::test_writers::{FailingWriter, SharedBuffer};
+
+    pub(super) fn frame_at(pixels: &[u32], width: usize, number: u64) -> Frame<'_> {
+        Frame {
+            pixels,
+            width,
+            height: pixels.len() / width,
+            indexed: None,
+            number,
+            timestamp: Duration::from_nanos(
+                number * FRAME_RATE_DENOMINATOR * 1_000_000_000 / FRAME_RATE_NUMERATOR,
+            ),
+        }
+    }
+
+    #[test]
+    fn parse_format() {
+        assert_eq!("gif".parse(), Ok(RecordingFormat::Gif));
+        assert_eq!("Y4M".parse(), Ok(RecordingFormat::Y4m));
+        assert_eq!("raw".parse(), Ok(RecordingFormat::Raw));
+        assert!("mp4".parse::<RecordingFormat>().is_err());
+    }
+
+    #[test]
+    fn frame_slots() {
+        assert_eq!(frame_slot(Duration::ZERO), 0);
+        assert_eq!(frame_slot(Duration::from_millis(16)), 1);
+        assert_eq!(frame_slot(Duration::from_secs(1)), 60);
+        assert_eq!(frame_slot(Duration::from_secs(100)), 5973);
+    }
+
+    #[test]
+    fn records_only_while_started() {
+        let frames = Arc::new(FrameBuffer::new());
+        let recorder = Recorder::new(frames.clone());
+        let output = SharedBuffer::default();
+        let pixels = [0xFFFFFF; 4];
+
+        recorder.render(&frame_at(&pixels, 2, 0));
+        assert!(!recorder.is_recording());
+
+        recorder
+            .start(RecordingFormat::Raw, output.clone())
+            .unwrap();
+        recorder.render(&frame_at(&pixels, 2, 1));
+        recorder.render(&frame_at(&pixels, 2, 2));
+        recorder.stop().unwrap();
+
+        recorder.render(&frame_at(&pixels, 2, 3));
+
+        assert!(!recorder.is_recording());
+        assert_eq!(output.bytes().len(), 2 * 4 * 3);
+        // Frames are still passed on to the inner renderer.
+        assert_eq!(frames.with_latest(|frame| frame.number), 3);
+    }
+
+    #[test]
+    fn encoding_errors_stop_recording() {
+        let recorder = Recorder::new(FrameBuffer::new());
+        let pixels = [0; 4];
+
+        recorder.start(RecordingFormat::Raw, FailingWriter).unwrap();
+        recorder.render(&frame_at(&pixels, 2, 0));
+
+        assert!(!recorder.is_recording());
+        assert!(recorder.stop().is_err());
+        assert!(recorder.stop().is_ok());
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::*;
+
+/// Shortest frame delay most GIF viewers will honour, in hundredths of a
+/// second. Shorter delays are often slowed right down to 1/10s.
+const MIN_DELAY: u64 = 2;
+const MAX_COLORS: usize = 256;
+/// LZW codes are at most 12 bits, so the code table holds 4096 entries.
+const MAX_CODES: u16 = 4096;
+
+/// Writes frames as a looping animated GIF. GIF frame delays are in
+/// hundredths of a second, so frames are merged until at least
+/// [MIN_DELAY] has passed. Delays are rounded from the emulated timestamps,
+/// so the animation never drifts from emulated time by more than 1/100s.
+pub(super) struct GifEncoder<W: Write + Send> {
+    writer: W,
+    /// Timestamp and size of the first frame, once the header is written.
+    start: Option<(Duration, usize, usize)>,
+    /// Frame waiting to be written, as its delay depends on when the next
+    /// frame arrives.
+    pending: Option<PendingFrame>,
+}
+
+struct PendingFrame {
+    width: usize,
+    height: usize,
+    pixels: Vec<u32>,
+    /// Time the frame is shown, in hundredths of a second since the start.
+    shown_at: u64,
+}
+
+impl<W: Write + Send> GifEncoder<W> {
+    pub(super) fn new(writer: W) -> Self {
+        Self {
+            writer,
+            start: None,
+            pending: None,
+        }
+    }
+
+    fn write_header(&mut self, width: usize, height: usize) -> io::Result<()> {
+        self.writer.write_all(b"GIF89a")?;
+        self.writer.write_all(&(width as u16).to_le_bytes())?;
+        self.writer.write_all(&(height as u16).to_le_bytes())?;
+        // No global colour table, background colour 0, square pixels.
+        self.writer.write_all(&[0, 0, 0])?;
+
+        // Loop forever
+        self.writer.write_all(&[0x21, 0xFF, 0x0B])?;
+        self.writer.write_all(b"NETSCAPE2.0")?;
+        self.writer.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])
+    }
+
+    fn write_frame(&mut self, frame: &PendingFrame, delay: u64) -> io::Result<()> {
+        let (palette, indices) = quantise(&frame.pixels);
+        let bits = palette_bits(palette.len());
+
+        // Graphic control extension: leave the frame in place, no
+        // transparency.
+        self.writer.write_all(&[0x21, 0xF9, 0x04, 0x04])?;
+        self.writer
+            .write_all(&(delay.min(u16::MAX as u64) as u16).to_le_bytes())?;
+        self.writer.write_all(&[0x00, 0x00])?;
+
+        // Image descriptor, covering the whole screen with a local colour
+        // table.
+        self.writer.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.writer.write_all(&(frame.width as u16).to_le_bytes())?;
+        self.writer
+            .write_all(&(frame.height as u16).to_le_bytes())?;
+        self.writer.write_all(&[0x80 | (bits - 1)])?;
+
+        for i in 0..(1 << bits) {
+            let [_, r, g, b] = palette.get(i).copied().unwrap_or(0).to_be_bytes();
+            self.writer.write_all(&[r, g, b])?;
+        }
+
+        let min_code_size = bits.max(2);
+        self.writer.write_all(&[min_code_size])?;
+        for block in lzw_encode(&indices, min_code_size).chunks(255) {
+            self.writer.write_all(&[block.len() as u8])?;
+            self.writer.write_all(block)?;
+        }
+        self.writer.write_all(&[0])
+    }
+}
+
+impl<W: Write + Send> FrameEncoder for GifEncoder<W> {
+    fn encode(&mut self, frame: &Frame) -> io::Result<()> {
+        let (start, width, height) = match self.start {
+            Some(start) => start,
+            None => {
+                self.write_header(frame.width, frame.height)?;
+                *self
+                    .start
+                    .insert((frame.timestamp, frame.width, frame.height))
+            }
+        };
+
+        if (frame.width, frame.height) != (width, height) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Frame size changed while recording",
+            ));
+        }
+
+        let shown_at = (frame.timestamp.saturating_sub(start).as_millis() as u64 + 5) / 10;
+
+        if let Some(pending) = self.pending.take() {
+            let delay = shown_at.saturating_sub(pending.shown_at);
+
+            // Too soon after the pending frame, replace it with this one.
+            if delay < MIN_DELAY {
+                self.pending = Some(PendingFrame {
+                    pixels: frame.pixels.to_vec(),
+                    ..pending
+                });
+                return Ok(());
+            }
+
+            self.write_frame(&pending, delay)?;
+        }
+
+        self.pending = Some(PendingFrame {
+            width: frame.width,
+            height: frame.height,
+            pixels: frame.pixels.to_vec(),
+            shown_at,
+        });
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.start.is_none() {
+            return self.writer.flush();
+        }
+
+        if let Some(pending) = self.pending.take() {
+            // Show the last frame for one frame period.
+            let delay = (100 * FRAME_RATE_DENOMINATOR / FRAME_RATE_NUMERATOR).max(MIN_DELAY);
+            self.write_frame(&pending, delay)?;
+        }
+
+        self.writer.write_all(&[0x3B])?;
+        self.writer.flush()
+    }
+}
+
+/// Reduce a frame to at most 256 colours, returning the palette and the
+/// palette index of each pixel. Frames from the PPU only use 4 colours, so
+/// are kept exact. Post-processed frames with more colours than fit are
+/// mapped onto a fixed 3-3-2 bit RGB palette.
+fn quantise(pixels: &[u32]) -> (Vec<u32>, Vec<u8>) {
+    let mut palette: Vec<u32> = Vec::new();
+    let mut lookup: HashMap<u32, u8> = HashMap::new();
+    let mut indices = Vec::with_capacity(pixels.len());
+
+    for &pixel in pixels {
+        let index = match lookup.get(&pixel) {
+            Some(&index) => index,
+            None if palette.len() < MAX_COLORS => {
+                let index = palette.len() as u8;
+                palette.push(pixel);
+                lookup.insert(pixel, index);
+                index
+            }
+            None => return quantise_rgb332(pixels),
+        };
+        indices.push(index);
+    }
+
+    (palette, indices)
+}
+
+fn quantise_rgb332(pixels: &[u32]) -> (Vec<u32>, Vec<u8>) {
+    let palette = (0..=255u32)
+        .map(|i| {
+            let r = (i >> 5) * 255 / 7;
+            let g = (i >> 2 & 0x7) * 255 / 7;
+            let b = (i & 0x3) * 255 / 3;
+            r << 16 | g << 8 | b
+        })
+        .collect();
+
+    let indices = pixels
+        .iter()
+        .map(|pixel| {
+            let [_, r, g, b] = pixel.to_be_bytes();
+            (r & 0xE0) | (g & 0xE0) >> 3 | b >> 6
+        })
+        .collect();
+
+    (palette, indices)
+}
+
+/// Number of bits needed for a colour table of the given size, between 1
+/// and 8.
+fn palette_bits(colors: usize) -> u8 {
+    let mut bits = 1;
+    while (1 << bits) < colors {
+        bits += 1;
+    }
+    bits
+}
+
+/// Compress palette indices with the variable code size LZW used by GIF.
+fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+
+    let mut output = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end + 1;
+    let mut code_size = min_code_size + 1;
+
+    output.write(clear, code_size);
+
+    let Some((&first, rest)) = indices.split_first() else {
+        output.write(end, code_size);
+        return output.finish();
+    };
+
+    let mut prefix = first as u16;
+    for &index in rest {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+
+        output.write(prefix, code_size);
+        table.insert((prefix, index), next_code);
+        next_code += 1;
+
+        // The decoder adds its table entries one code behind, so the code
+        // size goes up once the entry after the next power of two exists.
+        if next_code > (1 << code_size) && code_size < 12 {
+            code_size += 1;
+        }
+
+        if next_code == MAX_CODES {
+            output.write(clear, code_size);
+            table.clear();
+            next_code = end + 1;
+            code_size = min_code_size + 1;
+        }
+
+        prefix = index as u16;
+    }
+
+    output.write(prefix, code_size);
+    output.write(end, code_size);
+    output.finish()
+}
+
+/// Packs codes into bytes, least significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::frame_at;
+    use super::*;
+    use crate::test_writers::SharedBuffer;
+
+    /// Reference LZW decoder, to check the encoder round trips.
+    fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1u16 << min_code_size;
+        let end = clear + 1;
+        let reset = || -> Vec<Vec<u8>> {
+            let mut table: Vec<Vec<u8>> = (0..clear).map(|i| vec![i as u8]).collect();
+            table.push(Vec::new());
+            table.push(Vec::new());
+            table
+        };
+
+        let mut table = reset();
+        let mut code_size = min_code_size + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut output = Vec::new();
+        let (mut buffer, mut bits, mut bytes) = (0u32, 0u8, data.iter());
+
+        loop {
+            while bits < code_size {
+                buffer |= (*bytes.next().expect("Should end with end code") as u32) << bits;
+                bits += 8;
+            }
+            let code = (buffer & ((1 << code_size) - 1)) as u16;
+            buffer >>= code_size;
+            bits -= code_size;
+
+            if code == clear {
+                table = reset();
+                code_size = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return output;
+            }
+
+            let entry = match (table.get(code as usize), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => {
+                    let mut entry = previous.clone();
+                    entry.push(previous[0]);
+                    entry
+                }
+                (None, None) => panic!("Invalid code {code}"),
+            };
+
+            if let Some(mut previous) = previous.take() {
+                if table.len() < MAX_CODES as usize {
+                    previous.push(entry[0]);
+                    table.push(previous);
+                }
+                if table.len() == (1 << code_size) && code_size < 12 {
+                    code_size += 1;
+                }
+            }
+
+            output.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        let simple = vec![0, 0, 1, 1, 0, 0, 1, 1, 2, 3, 3, 3];
+        assert_eq!(lzw_decode(&lzw_encode(&simple, 2), 2), simple);
+
+        // Enough varied data to fill the code table several times.
+        let mut state = 12345u32;
+        let noisy: Vec<u8> = (0..50_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect();
+        assert_eq!(lzw_decode(&lzw_encode(&noisy, 8), 8), noisy);
+
+        assert_eq!(lzw_decode(&lzw_encode(&[], 2), 2), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn quantise_keeps_few_colours_exact() {
+        let (palette, indices) = quantise(&[0xFFFFFF, 0x000000, 0xFFFFFF, 0x123456]);
+
+        assert_eq!(palette, [0xFFFFFF, 0x000000, 0x123456]);
+        assert_eq!(indices, [0, 1, 0, 2]);
+        assert_eq!(palette_bits(palette.len()), 2);
+    }
+
+    #[test]
+    fn quantise_many_colours() {
+        let pixels: Vec<u32> = (0..1000).map(|i| i * 0x1234).collect();
+        let (palette, indices) = quantise(&pixels);
+
+        assert_eq!(palette.len(), 256);
+        assert_eq!(palette[indices[999] as usize] >> 21, (999 * 0x1234) >> 21);
+        assert_eq!(palette_bits(palette.len()), 8);
+    }
+
+    #[test]
+    fn delays_follow_emulated_time() {
+        let output = SharedBuffer::default();
+        let mut encoder = GifEncoder::new(output.clone());
+        let pixels = [0xFFFFFF, 0];
+
+        // 60 emulated frames, just over a second.
+        for number in 0..60 {
+            encoder.encode(&frame_at(&pixels, 2, number)).unwrap();
+        }
+        encoder.finish().unwrap();
+
+        let output = output.bytes();
+        assert_eq!(&output[..6], b"GIF89a");
+        assert_eq!(*output.last().unwrap(), 0x3B);
+
+        let delays: Vec<u64> = (0..output.len() - 6)
+            .filter(|&at| output[at..at + 4] == [0x21, 0xF9, 0x04, 0x04])
+            .map(|at| u16::from_le_bytes([output[at + 4], output[at + 5]]) as u64)
+            .collect();
+
+        // Frames 1/60s apart are merged so each is shown for at least
+        // 2/100s, alternating between 2/100s and 3/100s.
+        assert_eq!(delays.len(), 41);
+        assert!(delays.iter().all(|&delay| delay == 2 || delay == 3));
+        // The last frame, shown 0.99s in, lasts 2/100s.
+        assert_eq!(delays.iter().sum::<u64>(), 99 + 2);
+    }
+
+    #[test]
+    fn frame_size_must_not_change() {
+        let mut encoder = GifEncoder::new(SharedBuffer::default());
+
+        encoder.encode(&frame_at(&[0; 4], 2, 0)).unwrap();
+        assert!(encoder.encode(&frame_at(&[0; 6], 3, 1)).is_err());
+    }
+}
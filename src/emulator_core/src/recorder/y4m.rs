@@ -0,0 +1,190 @@
+use std::time::Duration;
+
+use super::*;
+
+/// Writes frames at a constant frame rate, as Y4M or raw RGB24. Frames are
+/// placed by their emulated timestamp: if frames are missing (e.g. while the
+/// LCD is off) the previous frame is repeated, and a frame arriving in a
+/// slot which has already been written is dropped.
+pub(super) struct FixedRateEncoder<W: Write + Send> {
+    writer: W,
+    y4m: bool,
+    start: Option<Start>,
+    /// Number of frame slots written so far.
+    written: u64,
+    /// Encoded bytes of the last frame written, to repeat if needed.
+    last: Vec<u8>,
+}
+
+struct Start {
+    timestamp: Duration,
+    width: usize,
+    height: usize,
+}
+
+impl<W: Write + Send> FixedRateEncoder<W> {
+    pub(super) fn y4m(writer: W) -> Self {
+        Self::new(writer, true)
+    }
+
+    pub(super) fn raw(writer: W) -> Self {
+        Self::new(writer, false)
+    }
+
+    fn new(writer: W, y4m: bool) -> Self {
+        Self {
+            writer,
+            y4m,
+            start: None,
+            written: 0,
+            last: Vec::new(),
+        }
+    }
+
+    fn write_last(&mut self) -> io::Result<()> {
+        if self.y4m {
+            self.writer.write_all(b"FRAME\n")?;
+        }
+        self.writer.write_all(&self.last)?;
+        self.written += 1;
+
+        Ok(())
+    }
+}
+
+impl<W: Write + Send> FrameEncoder for FixedRateEncoder<W> {
+    fn encode(&mut self, frame: &Frame) -> io::Result<()> {
+        let start = self.start.get_or_insert(Start {
+            timestamp: frame.timestamp,
+            width: frame.width,
+            height: frame.height,
+        });
+
+        if (start.width, start.height) != (frame.width, frame.height) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Frame size changed while recording",
+            ));
+        }
+
+        let slot = frame_slot(frame.timestamp.saturating_sub(start.timestamp));
+        if slot < self.written {
+            return Ok(());
+        }
+
+        if self.written == 0 && self.y4m {
+            writeln!(
+                self.writer,
+                "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C444",
+                frame.width, frame.height, FRAME_RATE_NUMERATOR, FRAME_RATE_DENOMINATOR
+            )?;
+        }
+
+        while self.written < slot {
+            self.write_last()?;
+        }
+
+        self.last = if self.y4m {
+            yuv444_planes(frame.pixels)
+        } else {
+            frame
+                .pixels
+                .iter()
+                .flat_map(|pixel| {
+                    let [_, r, g, b] = pixel.to_be_bytes();
+                    [r, g, b]
+                })
+                .collect()
+        };
+
+        self.write_last()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Convert 0x00RRGGBB pixels to planar Y, U and V (BT.601, limited range).
+fn yuv444_planes(pixels: &[u32]) -> Vec<u8> {
+    let mut planes = vec![0; pixels.len() * 3];
+    let (y_plane, chroma) = planes.split_at_mut(pixels.len());
+    let (u_plane, v_plane) = chroma.split_at_mut(pixels.len());
+
+    for (i, pixel) in pixels.iter().enumerate() {
+        let [_, r, g, b] = pixel.to_be_bytes();
+        let (r, g, b) = (r as i32, g as i32, b as i32);
+
+        y_plane[i] = (16 + ((66 * r + 129 * g + 25 * b + 128) >> 8)) as u8;
+        u_plane[i] = (128 + ((-38 * r - 74 * g + 112 * b + 128) >> 8)) as u8;
+        v_plane[i] = (128 + ((112 * r - 94 * g - 18 * b + 128) >> 8)) as u8;
+    }
+
+    planes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::frame_at;
+    use super::*;
+    use crate::test_writers::SharedBuffer;
+
+    #[test]
+    fn y4m_header_and_frames() {
+        let output = SharedBuffer::default();
+        let mut encoder = FixedRateEncoder::y4m(output.clone());
+
+        encoder.encode(&frame_at(&[0xFFFFFF, 0], 2, 0)).unwrap();
+        encoder.encode(&frame_at(&[0, 0xFFFFFF], 2, 1)).unwrap();
+        encoder.finish().unwrap();
+
+        let output = output.bytes();
+        let header = b"YUV4MPEG2 W2 H1 F4194304:70224 Ip A1:1 C444\n";
+        assert_eq!(&output[..header.len()], header);
+
+        let frames = &output[header.len()..];
+        assert_eq!(frames.len(), 2 * (6 + 6));
+        assert_eq!(&frames[..12], b"FRAME\n\xEB\x10\x80\x80\x80\x80");
+        assert_eq!(&frames[12..18], b"FRAME\n");
+    }
+
+    #[test]
+    fn repeats_frames_to_fill_gaps() {
+        let output = SharedBuffer::default();
+        let mut encoder = FixedRateEncoder::raw(output.clone());
+
+        encoder.encode(&frame_at(&[0x010203], 1, 10)).unwrap();
+        // Three frames missing, e.g. the LCD was off.
+        encoder.encode(&frame_at(&[0x040506], 1, 14)).unwrap();
+
+        assert_eq!(
+            *output.bytes(),
+            [1, 2, 3, 1, 2, 3, 1, 2, 3, 1, 2, 3, 4, 5, 6]
+        );
+    }
+
+    #[test]
+    fn drops_frames_in_the_same_slot() {
+        let output = SharedBuffer::default();
+        let mut encoder = FixedRateEncoder::raw(output.clone());
+
+        let frame = frame_at(&[0x010203], 1, 1);
+        encoder.encode(&frame).unwrap();
+        encoder
+            .encode(&Frame {
+                pixels: &[0x040506],
+                ..frame
+            })
+            .unwrap();
+
+        assert_eq!(*output.bytes(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn frame_size_must_not_change() {
+        let mut encoder = FixedRateEncoder::raw(SharedBuffer::default());
+
+        encoder.encode(&frame_at(&[0; 4], 2, 0)).unwrap();
+        assert!(encoder.encode(&frame_at(&[0; 4], 4, 1)).is_err());
+    }
+}
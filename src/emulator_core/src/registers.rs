@@ -42,6 +42,18 @@ impl Registers {
         }
     }
 
+    /// Values left behind by the Super Game Boy's boot ROM.
+    pub fn sgb() -> Registers {
+        Registers {
+            af: 0x0100,
+            bc: 0x0014,
+            de: 0x0000,
+            hl: 0xc060,
+            sp: 0xfffe,
+            pc: 0x0100,
+        }
+    }
+
     pub fn read_eight(&self, register: EightBitRegister) -> u8 {
         match register {
             EightBitRegister::A => (self.af >> 8) as u8,
@@ -0,0 +1,469 @@
+//! Pixel-art upscaling filters. These smooth the diagonal edges of
+//! sprites and text, rather than just making the pixels bigger as
+//! nearest-neighbour scaling does.
+//!
+//! Filters can be applied to a buffer directly with [Filter::apply], or
+//! to every frame by wrapping a renderer in a [Scaler].
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use crate::mmu::{Frame, Renderer};
+
+/// An upscaling filter.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Filter {
+    /// Leave the frame as it is.
+    None,
+    /// AdvMAME2x/Scale2x, doubles the size.
+    Scale2x,
+    /// AdvMAME3x/Scale3x, triples the size.
+    Scale3x,
+    /// Scale2x applied twice.
+    Scale4x,
+    /// Eagle, doubles the size. Smooths more aggressively than Scale2x.
+    Eagle,
+    /// A single pass of xBR at 2x, which finds edges by comparing colour
+    /// distances across a 5x5 neighbourhood and blends along them.
+    XbrLite,
+}
+
+impl Filter {
+    /// How many times larger the output is in each dimension.
+    pub fn factor(&self) -> usize {
+        match self {
+            Filter::None => 1,
+            Filter::Scale2x | Filter::Eagle | Filter::XbrLite => 2,
+            Filter::Scale3x => 3,
+            Filter::Scale4x => 4,
+        }
+    }
+
+    /// Scale the given image into output, which is resized to fit. Returns
+    /// the width and height of the output.
+    pub fn apply(
+        &self,
+        pixels: &[u32],
+        width: usize,
+        height: usize,
+        output: &mut Vec<u32>,
+    ) -> (usize, usize) {
+        let image = Image {
+            pixels,
+            width,
+            height,
+        };
+
+        match self {
+            Filter::None => {
+                output.clear();
+                output.extend_from_slice(pixels);
+            }
+            Filter::Scale2x => scale2x(&image, output),
+            Filter::Scale3x => scale3x(&image, output),
+            Filter::Scale4x => {
+                let mut doubled = Vec::new();
+                scale2x(&image, &mut doubled);
+                let doubled = Image {
+                    pixels: &doubled,
+                    width: width * 2,
+                    height: height * 2,
+                };
+                scale2x(&doubled, output);
+            }
+            Filter::Eagle => eagle(&image, output),
+            Filter::XbrLite => xbr_lite(&image, output),
+        }
+
+        (width * self.factor(), height * self.factor())
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Filter::None),
+            "scale2x" => Ok(Filter::Scale2x),
+            "scale3x" => Ok(Filter::Scale3x),
+            "scale4x" => Ok(Filter::Scale4x),
+            "eagle" => Ok(Filter::Eagle),
+            "xbr" | "xbr-lite" => Ok(Filter::XbrLite),
+            _ => Err(format!("Unknown filter: {s}")),
+        }
+    }
+}
+
+/// Applies a [Filter] to every frame before passing it on to the inner
+/// renderer.
+pub struct Scaler<R: Renderer> {
+    inner: R,
+    filter: Filter,
+    output: Mutex<Vec<u32>>,
+}
+
+impl<R: Renderer> Scaler<R> {
+    pub fn new(inner: R, filter: Filter) -> Self {
+        Self {
+            inner,
+            filter,
+            output: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl<R: Renderer> Renderer for Scaler<R> {
+    fn render(&self, frame: &Frame) {
+        let mut output = self.output.lock().expect("Should lock output");
+        let (width, height) =
+            self.filter
+                .apply(frame.pixels, frame.width, frame.height, &mut output);
+
+        self.inner.render(&Frame {
+            pixels: &output,
+            width,
+            height,
+            ..*frame
+        });
+    }
+}
+
+struct Image<'a> {
+    pixels: &'a [u32],
+    width: usize,
+    height: usize,
+}
+
+impl Image<'_> {
+    /// Pixel at the given offset from (x, y). Offsets past the edge of the
+    /// image are clamped, repeating the edge pixels.
+    fn at(&self, x: usize, y: usize, dx: isize, dy: isize) -> u32 {
+        let x = x.saturating_add_signed(dx).min(self.width - 1);
+        let y = y.saturating_add_signed(dy).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Write a block of scaled pixels for the source pixel at (x, y).
+fn write_block<const N: usize>(
+    output: &mut [u32],
+    width: usize,
+    x: usize,
+    y: usize,
+    block: [[u32; N]; N],
+) {
+    let out_width = width * N;
+    for (row, pixels) in block.iter().enumerate() {
+        let start = (y * N + row) * out_width + x * N;
+        output[start..start + N].copy_from_slice(pixels);
+    }
+}
+
+// Neighbourhood of E, the pixel being scaled:
+//   A B C
+//   D E F
+//   G H I
+fn scale2x(image: &Image, output: &mut Vec<u32>) {
+    output.resize(image.pixels.len() * 4, 0);
+
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let b = image.at(x, y, 0, -1);
+            let d = image.at(x, y, -1, 0);
+            let e = image.at(x, y, 0, 0);
+            let f = image.at(x, y, 1, 0);
+            let h = image.at(x, y, 0, 1);
+
+            let block = if b != h && d != f {
+                [
+                    [if d == b { d } else { e }, if b == f { f } else { e }],
+                    [if d == h { d } else { e }, if h == f { f } else { e }],
+                ]
+            } else {
+                [[e; 2]; 2]
+            };
+
+            write_block(output, image.width, x, y, block);
+        }
+    }
+}
+
+fn scale3x(image: &Image, output: &mut Vec<u32>) {
+    output.resize(image.pixels.len() * 9, 0);
+
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let a = image.at(x, y, -1, -1);
+            let b = image.at(x, y, 0, -1);
+            let c = image.at(x, y, 1, -1);
+            let d = image.at(x, y, -1, 0);
+            let e = image.at(x, y, 0, 0);
+            let f = image.at(x, y, 1, 0);
+            let g = image.at(x, y, -1, 1);
+            let h = image.at(x, y, 0, 1);
+            let i = image.at(x, y, 1, 1);
+
+            let block = if b != h && d != f {
+                let pick = |condition: bool, pixel: u32| if condition { pixel } else { e };
+
+                [
+                    [
+                        pick(d == b, d),
+                        pick((d == b && e != c) || (b == f && e != a), b),
+                        pick(b == f, f),
+                    ],
+                    [
+                        pick((d == b && e != g) || (d == h && e != a), d),
+                        e,
+                        pick((b == f && e != i) || (h == f && e != c), f),
+                    ],
+                    [
+                        pick(d == h, d),
+                        pick((d == h && e != i) || (h == f && e != g), h),
+                        pick(h == f, f),
+                    ],
+                ]
+            } else {
+                [[e; 3]; 3]
+            };
+
+            write_block(output, image.width, x, y, block);
+        }
+    }
+}
+
+fn eagle(image: &Image, output: &mut Vec<u32>) {
+    output.resize(image.pixels.len() * 4, 0);
+
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let a = image.at(x, y, -1, -1);
+            let b = image.at(x, y, 0, -1);
+            let c = image.at(x, y, 1, -1);
+            let d = image.at(x, y, -1, 0);
+            let e = image.at(x, y, 0, 0);
+            let f = image.at(x, y, 1, 0);
+            let g = image.at(x, y, -1, 1);
+            let h = image.at(x, y, 0, 1);
+            let i = image.at(x, y, 1, 1);
+
+            let corner = |p: u32, q: u32, r: u32| if p == q && q == r { q } else { e };
+
+            let block = [
+                [corner(d, a, b), corner(b, c, f)],
+                [corner(d, g, h), corner(h, i, f)],
+            ];
+
+            write_block(output, image.width, x, y, block);
+        }
+    }
+}
+
+fn xbr_lite(image: &Image, output: &mut Vec<u32>) {
+    output.resize(image.pixels.len() * 4, 0);
+
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let corner = |sx: isize, sy: isize| xbr_corner(image, x, y, sx, sy);
+
+            let block = [
+                [corner(-1, -1), corner(1, -1)],
+                [corner(-1, 1), corner(1, 1)],
+            ];
+
+            write_block(output, image.width, x, y, block);
+        }
+    }
+}
+
+/// Colour of the corner of the scaled pixel at (x, y) in the direction
+/// (sx, sy). Written for the bottom right corner, the others are found by
+/// mirroring the neighbourhood:
+///      A1 B1 C1
+///   A0  A  B  C C4
+///   D0  D  E  F F4
+///   G0  G  H  I I4
+///      G5 H5 I5
+fn xbr_corner(image: &Image, x: usize, y: usize, sx: isize, sy: isize) -> u32 {
+    let p = |dx: isize, dy: isize| image.at(x, y, dx * sx, dy * sy);
+
+    let (b, c, d, e, f) = (p(0, -1), p(1, -1), p(-1, 0), p(0, 0), p(1, 0));
+    let (g, h, i) = (p(-1, 1), p(0, 1), p(1, 1));
+    let (f4, i4, h5, i5) = (p(2, 0), p(2, 1), p(0, 2), p(1, 2));
+
+    if e == f || e == h {
+        return e;
+    }
+
+    // Edges running along H-F, against edges running along E-I.
+    let along =
+        distance(e, c) + distance(e, g) + distance(i, f4) + distance(i, h5) + 4 * distance(h, f);
+    let across =
+        distance(h, d) + distance(h, i5) + distance(f, i4) + distance(f, b) + 4 * distance(e, i);
+
+    if along < across {
+        let nearest = if distance(e, f) <= distance(e, h) {
+            f
+        } else {
+            h
+        };
+        blend(e, nearest)
+    } else {
+        e
+    }
+}
+
+/// Perceptual distance between two colours, weighting differences in
+/// brightness over differences in hue.
+fn distance(p: u32, q: u32) -> u32 {
+    let (py, pu, pv) = yuv(p);
+    let (qy, qu, qv) = yuv(q);
+
+    (48 * (py - qy).abs() + 7 * (pu - qu).abs() + 6 * (pv - qv).abs()) as u32
+}
+
+fn yuv(pixel: u32) -> (i32, i32, i32) {
+    let r = (pixel >> 16 & 0xFF) as i32;
+    let g = (pixel >> 8 & 0xFF) as i32;
+    let b = (pixel & 0xFF) as i32;
+
+    let y = (299 * r + 587 * g + 114 * b) / 1000;
+    (y, (b - y) / 2, (r - y) / 2)
+}
+
+/// Even mix of two colours.
+fn blend(p: u32, q: u32) -> u32 {
+    ((p & 0xFEFEFE) >> 1) + ((q & 0xFEFEFE) >> 1) + (p & q & 0x010101)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::mmu::FrameBuffer;
+
+    const W: u32 = 0xFFFFFF;
+    const K: u32 = 0x000000;
+
+    /// A 3x3 image with a diagonal line from top right to bottom left.
+    const DIAGONAL: [u32; 9] = [W, W, K, W, K, W, K, W, W];
+
+    fn apply(filter: Filter, pixels: &[u32], width: usize) -> Vec<u32> {
+        let mut output = Vec::new();
+        let height = pixels.len() / width;
+        let (out_width, out_height) = filter.apply(pixels, width, height, &mut output);
+
+        assert_eq!(out_width * out_height, output.len());
+        output
+    }
+
+    #[test]
+    fn parse_filter() {
+        assert_eq!("scale2x".parse(), Ok(Filter::Scale2x));
+        assert_eq!("XBR".parse(), Ok(Filter::XbrLite));
+        assert_eq!("none".parse(), Ok(Filter::None));
+        assert!("bilinear".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn flat_image_stays_flat() {
+        let pixels = [0x123456; 16];
+
+        for filter in [
+            Filter::None,
+            Filter::Scale2x,
+            Filter::Scale3x,
+            Filter::Scale4x,
+            Filter::Eagle,
+            Filter::XbrLite,
+        ] {
+            let output = apply(filter, &pixels, 4);
+            let factor = filter.factor();
+            assert_eq!(output.len(), 16 * factor * factor);
+            assert!(output.iter().all(|&pixel| pixel == 0x123456), "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn scale2x_smooths_diagonal() {
+        let output = apply(Filter::Scale2x, &DIAGONAL, 3);
+
+        #[rustfmt::skip]
+        assert_eq!(output, [
+            W, W, W, W, K, K,
+            W, W, W, K, W, K,
+            W, W, K, K, K, W,
+            W, K, K, K, W, W,
+            K, W, K, W, W, W,
+            K, K, W, W, W, W,
+        ]);
+    }
+
+    #[test]
+    fn scale3x_keeps_centre_pixel() {
+        let output = apply(Filter::Scale3x, &DIAGONAL, 3);
+
+        // The centre of each 3x3 block is always the source pixel.
+        for y in 0..3 {
+            for x in 0..3 {
+                assert_eq!(output[(y * 3 + 1) * 9 + x * 3 + 1], DIAGONAL[y * 3 + x]);
+            }
+        }
+        // The white pixel above the centre fills in the step towards the
+        // black pixels below and to the right of it.
+        assert_eq!(output[9 + 5], K);
+        assert_eq!(output[2 * 9 + 5], K);
+        assert_eq!(output[2 * 9 + 4], W);
+    }
+
+    #[test]
+    fn scale4x_is_scale2x_twice() {
+        let twice = apply(Filter::Scale2x, &apply(Filter::Scale2x, &DIAGONAL, 3), 6);
+
+        assert_eq!(apply(Filter::Scale4x, &DIAGONAL, 3), twice);
+    }
+
+    #[test]
+    fn eagle_fills_corners_matching_all_three_neighbours() {
+        let pixels = [K, K, W, K, W, W, W, W, W];
+        let output = apply(Filter::Eagle, &pixels, 3);
+
+        // Centre pixel, top left corner: D, A and B are all black.
+        assert_eq!(output[2 * 6 + 2], K);
+        assert_eq!(output[2 * 6 + 3], W);
+        assert_eq!(output[3 * 6 + 3], W);
+    }
+
+    #[test]
+    fn xbr_blends_along_diagonal() {
+        let output = apply(Filter::XbrLite, &DIAGONAL, 3);
+
+        // The white pixel above the centre gets a blended bottom right
+        // corner, filling in the step in the line.
+        assert_eq!(output[6 + 3], blend(W, K));
+        assert_eq!(output[6 + 2], W);
+        // The centre keeps its corners along the line.
+        assert_eq!(output[2 * 6 + 3], K);
+    }
+
+    #[test]
+    fn scaler_renders_scaled_frame() {
+        let frames = Arc::new(FrameBuffer::new());
+        let scaler = Scaler::new(frames.clone(), Filter::Scale3x);
+
+        scaler.render(&Frame {
+            pixels: &DIAGONAL,
+            width: 3,
+            height: 3,
+            indexed: None,
+            number: 1,
+            timestamp: std::time::Duration::ZERO,
+        });
+
+        frames.with_latest(|frame| {
+            assert_eq!((frame.width, frame.height), (9, 9));
+            assert_eq!(frame.number, 1);
+        });
+    }
+}
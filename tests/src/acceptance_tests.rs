@@ -1,5 +1,6 @@
 use crate::support::BlarggTestCase;
 use crate::support::MooneyeTestCase;
+use crate::support::ReferenceImageTestCase;
 
 struct CpuInstrs;
 struct InstrTiming;
@@ -11,6 +12,51 @@ struct Tim00DivTrigger;
 struct Tim01DivTrigger;
 struct Tim10DivTrigger;
 struct Tim11DivTrigger;
+struct StatIrqBlocking;
+struct LcdOnTiming;
+struct LcdOnWriteTiming;
+struct BootRegsSgb;
+struct HaltIme0Ei;
+struct HaltIme0NointrTiming;
+struct HaltIme1Timing;
+struct HaltIme1Timing2;
+struct EiSequence;
+struct EiTiming;
+struct DiTiming;
+struct IntrTiming;
+struct RapidDiEi;
+struct IePush;
+struct MemTiming;
+struct AddSpETiming;
+struct CallTiming;
+struct CallTiming2;
+struct CallCcTiming;
+struct CallCcTiming2;
+struct JpTiming;
+struct JpCcTiming;
+struct LdHlSpETiming;
+struct PopTiming;
+struct PushTiming;
+struct RetTiming;
+struct RetCcTiming;
+struct RetiTiming;
+struct RetiIntrTiming;
+struct RstTiming;
+struct DivTiming;
+struct OamDmaBasic;
+struct OamDmaRegRead;
+struct OamDmaRestart;
+struct OamDmaStart;
+struct OamDmaTiming;
+struct HblankLyScxTiming;
+struct Intr12Timing;
+struct Intr20Timing;
+struct Intr2Mode0Timing;
+struct Intr2Mode0TimingSprites;
+struct Intr2Mode3Timing;
+struct Intr2OamOkTiming;
+struct VblankStatIntr;
+struct DmgAcid2;
 
 impl BlarggTestCase for CpuInstrs {
     fn filepath() -> String {
@@ -120,6 +166,468 @@ impl MooneyeTestCase for Tim11DivTrigger {
     }
 }
 
+impl MooneyeTestCase for StatIrqBlocking {
+    fn filepath() -> String {
+        "../roms/acceptance/ppu/stat_irq_blocking.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        200000
+    }
+}
+
+impl MooneyeTestCase for LcdOnTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/ppu/lcdon_timing-GS.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        800000
+    }
+}
+
+impl MooneyeTestCase for LcdOnWriteTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/ppu/lcdon_write_timing-GS.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        1600000
+    }
+}
+
+impl MooneyeTestCase for BootRegsSgb {
+    fn filepath() -> String {
+        "../roms/acceptance/boot_regs-sgb.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        200000
+    }
+
+    fn sgb() -> bool {
+        true
+    }
+}
+
+impl MooneyeTestCase for HaltIme0Ei {
+    fn filepath() -> String {
+        "../roms/acceptance/halt_ime0_ei.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        200000
+    }
+}
+
+impl MooneyeTestCase for HaltIme0NointrTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/halt_ime0_nointr_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for HaltIme1Timing {
+    fn filepath() -> String {
+        "../roms/acceptance/halt_ime1_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        200000
+    }
+}
+
+impl MooneyeTestCase for HaltIme1Timing2 {
+    fn filepath() -> String {
+        "../roms/acceptance/halt_ime1_timing2-GS.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for EiSequence {
+    fn filepath() -> String {
+        "../roms/acceptance/ei_sequence.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        200000
+    }
+}
+
+impl MooneyeTestCase for EiTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/ei_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        200000
+    }
+}
+
+impl MooneyeTestCase for DiTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/di_timing-GS.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for IntrTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/intr_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        200000
+    }
+}
+
+impl MooneyeTestCase for RapidDiEi {
+    fn filepath() -> String {
+        "../roms/acceptance/rapid_di_ei.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        200000
+    }
+}
+
+impl MooneyeTestCase for IePush {
+    fn filepath() -> String {
+        "../roms/acceptance/interrupts/ie_push.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        200000
+    }
+}
+
+impl BlarggTestCase for MemTiming {
+    fn filepath() -> String {
+        "../roms/mem_timing/mem_timing.gb".to_string()
+    }
+
+    fn expected_output() -> String {
+        "mem_timing\n\n01:ok  02:ok  03:ok  \n\nPassed all tests\n".to_string()
+    }
+
+    fn steps() -> u32 {
+        4000000
+    }
+}
+
+impl MooneyeTestCase for AddSpETiming {
+    fn filepath() -> String {
+        "../roms/acceptance/add_sp_e_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for CallTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/call_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for CallTiming2 {
+    fn filepath() -> String {
+        "../roms/acceptance/call_timing2.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for CallCcTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/call_cc_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for CallCcTiming2 {
+    fn filepath() -> String {
+        "../roms/acceptance/call_cc_timing2.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for JpTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/jp_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for JpCcTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/jp_cc_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for LdHlSpETiming {
+    fn filepath() -> String {
+        "../roms/acceptance/ld_hl_sp_e_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for PopTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/pop_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for PushTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/push_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for RetTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/ret_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for RetCcTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/ret_cc_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for RetiTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/reti_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for RetiIntrTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/reti_intr_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for RstTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/rst_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for DivTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/div_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for OamDmaBasic {
+    fn filepath() -> String {
+        "../roms/acceptance/oam_dma/basic.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for OamDmaRegRead {
+    fn filepath() -> String {
+        "../roms/acceptance/oam_dma/reg_read.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for OamDmaRestart {
+    fn filepath() -> String {
+        "../roms/acceptance/oam_dma_restart.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for OamDmaStart {
+    fn filepath() -> String {
+        "../roms/acceptance/oam_dma_start.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for OamDmaTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/oam_dma_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for HblankLyScxTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/ppu/hblank_ly_scx_timing-GS.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        1600000
+    }
+}
+
+impl MooneyeTestCase for Intr12Timing {
+    fn filepath() -> String {
+        "../roms/acceptance/ppu/intr_1_2_timing-GS.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for Intr20Timing {
+    fn filepath() -> String {
+        "../roms/acceptance/ppu/intr_2_0_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for Intr2Mode0Timing {
+    fn filepath() -> String {
+        "../roms/acceptance/ppu/intr_2_mode0_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for Intr2Mode0TimingSprites {
+    fn filepath() -> String {
+        "../roms/acceptance/ppu/intr_2_mode0_timing_sprites.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        6400000
+    }
+}
+
+impl MooneyeTestCase for Intr2Mode3Timing {
+    fn filepath() -> String {
+        "../roms/acceptance/ppu/intr_2_mode3_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for Intr2OamOkTiming {
+    fn filepath() -> String {
+        "../roms/acceptance/ppu/intr_2_oam_ok_timing.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl MooneyeTestCase for VblankStatIntr {
+    fn filepath() -> String {
+        "../roms/acceptance/ppu/vblank_stat_intr-GS.gb".to_string()
+    }
+
+    fn steps() -> u32 {
+        400000
+    }
+}
+
+impl ReferenceImageTestCase for DmgAcid2 {
+    fn filepath() -> String {
+        "../roms/dmg-acid2.gb".to_string()
+    }
+
+    fn reference_image() -> String {
+        "reference/dmg-acid2.png".to_string()
+    }
+
+    fn steps() -> u32 {
+        2000000
+    }
+}
+
 #[test]
 fn cpu_instrs() {
     CpuInstrs::run();
@@ -130,6 +638,40 @@ fn instr_timing() {
     InstrTiming::run();
 }
 
+#[test]
+fn mem_timing() {
+    MemTiming::run();
+}
+
+#[test]
+fn memory_access_timing() {
+    AddSpETiming::run();
+    CallTiming::run();
+    CallTiming2::run();
+    CallCcTiming::run();
+    CallCcTiming2::run();
+    JpTiming::run();
+    JpCcTiming::run();
+    LdHlSpETiming::run();
+    PopTiming::run();
+    PushTiming::run();
+    RetTiming::run();
+    RetCcTiming::run();
+    RetiTiming::run();
+    RetiIntrTiming::run();
+    RstTiming::run();
+    DivTiming::run();
+}
+
+#[test]
+fn oam_dma() {
+    OamDmaBasic::run();
+    OamDmaRegRead::run();
+    OamDmaRestart::run();
+    OamDmaStart::run();
+    OamDmaTiming::run();
+}
+
 #[test]
 fn timer() {
     Tim00::run();
@@ -141,3 +683,52 @@ fn timer() {
     Tim10DivTrigger::run();
     Tim11DivTrigger::run();
 }
+
+#[test]
+fn sgb_boot_regs() {
+    BootRegsSgb::run();
+}
+
+#[test]
+fn halt() {
+    HaltIme0Ei::run();
+    HaltIme0NointrTiming::run();
+    HaltIme1Timing::run();
+    HaltIme1Timing2::run();
+}
+
+#[test]
+fn interrupts() {
+    EiSequence::run();
+    EiTiming::run();
+    IntrTiming::run();
+    RapidDiEi::run();
+    IePush::run();
+    DiTiming::run();
+}
+
+#[test]
+fn ppu() {
+    StatIrqBlocking::run();
+    HblankLyScxTiming::run();
+    Intr12Timing::run();
+    Intr20Timing::run();
+    Intr2Mode0Timing::run();
+    Intr2Mode0TimingSprites::run();
+    Intr2Mode3Timing::run();
+    Intr2OamOkTiming::run();
+    VblankStatIntr::run();
+    LcdOnTiming::run();
+    LcdOnWriteTiming::run();
+}
+
+// dmg-acid2 checks sprite selection, X-priority and the 10 sprite limit
+// against the screen it produces on a DMG. Neither the ROM nor its
+// reference-dmg.png are bundled yet, copy them from a dmg-acid2 release to
+// roms/dmg-acid2.gb and tests/reference/dmg-acid2.png and run with
+// --ignored.
+#[test]
+#[ignore = "needs roms/dmg-acid2.gb and tests/reference/dmg-acid2.png"]
+fn dmg_acid2() {
+    DmgAcid2::run();
+}
@@ -1,4 +1,8 @@
-use std::{fs::File, io::Read, sync::Arc};
+use std::{
+    fs::File,
+    io::Read,
+    sync::{Arc, Mutex},
+};
 
 use emulator_core::*;
 
@@ -17,7 +21,7 @@ pub trait BlarggTestCase {
         let mut clock = 0;
 
         while clock < Self::steps() {
-            let cycles = cpu.step();
+            let cycles = cpu.step().expect("Should step");
             clock += cycles as u32;
         }
 
@@ -34,44 +38,150 @@ pub trait MooneyeTestCase {
     /// Number of CPU cycles to execute before completing test case
     fn steps() -> u32;
 
+    /// Run the test ROM on a Super Game Boy
+    fn sgb() -> bool {
+        false
+    }
+
     fn run() {
-        let mut cpu = setup_emulator(&Self::filepath());
+        let mut cpu = if Self::sgb() {
+            setup_sgb_emulator(&Self::filepath())
+        } else {
+            setup_emulator(&Self::filepath())
+        };
         let mut clock = 0;
 
         while clock < Self::steps() {
-            let cycles = cpu.step();
+            let cycles = cpu.step().expect("Should step");
             clock += cycles as u32;
         }
 
-        let registers = cpu.registers();
+        let state = cpu.state();
+
+        assert_eq!(state.b(), 3);
+        assert_eq!(state.c(), 5);
+        assert_eq!(state.d(), 8);
+        assert_eq!(state.e(), 13);
+        assert_eq!(state.h(), 21);
+        assert_eq!(state.l(), 34);
+    }
+}
+
+pub trait ReferenceImageTestCase {
+    /// Path to the test ROM
+    fn filepath() -> String;
+
+    /// Path to a PNG of the screen the test ROM produces on real hardware
+    fn reference_image() -> String;
+
+    /// Number of CPU cycles to execute before comparing the screen
+    fn steps() -> u32;
+
+    fn run() {
+        let renderer = Arc::new(ShadeRenderer::default());
+        let mut cpu = CPU::new(setup_mmu_with_renderer(&Self::filepath(), renderer.clone()));
+        cpu.mmu.ppu.set_indexed_output(true);
+        let mut clock = 0;
+
+        while clock < Self::steps() {
+            let cycles = cpu.step().expect("Should step");
+            clock += cycles as u32;
+        }
 
-        assert_eq!(registers.b, 3);
-        assert_eq!(registers.c, 5);
-        assert_eq!(registers.d, 8);
-        assert_eq!(registers.e, 13);
-        assert_eq!(registers.h, 21);
-        assert_eq!(registers.l, 34);
+        let expected = load_reference_image(&Self::reference_image());
+        let actual = renderer.shades.lock().expect("Should lock");
+        assert_eq!(actual.len(), expected.len(), "No frame was rendered");
+
+        let mismatches: Vec<usize> = (0..expected.len())
+            .filter(|&i| actual[i] != expected[i])
+            .collect();
+        if let Some(&first) = mismatches.first() {
+            panic!(
+                "{} pixels differ from the reference image, the first at ({}, {}): expected {:?}, got {:?}",
+                mismatches.len(),
+                first % 160,
+                first / 160,
+                expected[first],
+                actual[first],
+            );
+        }
     }
 }
 
 pub fn setup_emulator(rom_path: &str) -> CPU {
+    CPU::new(setup_mmu(rom_path))
+}
+
+pub fn setup_sgb_emulator(rom_path: &str) -> CPU {
+    CPU::new_sgb(setup_mmu(rom_path))
+}
+
+fn setup_mmu(rom_path: &str) -> MMU {
+    setup_mmu_with_renderer(rom_path, Arc::new(TestRenderer))
+}
+
+fn setup_mmu_with_renderer(rom_path: &str, renderer: Arc<dyn Renderer>) -> MMU {
     let mut fp = File::open(rom_path).expect("Should exist");
     let mut data = Vec::new();
     fp.read_to_end(&mut data).expect("Should read");
 
     let cartridge = create_cartridge(data, Box::new(TestPersister));
-    let ppu = PPU::new(Arc::new(TestRenderer));
+    let ppu = PPU::new(renderer);
     let joypad = Arc::new(Joypad::new());
-    let mmu = MMU::new(ppu, cartridge, joypad);
+    MMU::new(ppu, cartridge, joypad)
+}
 
-    CPU::new(mmu)
+/// Decode a PNG into the shade of each pixel. Reference images are
+/// greyscale, or close enough that the brightness picks out the shade.
+fn load_reference_image(path: &str) -> Vec<Color> {
+    let mut decoder = png::Decoder::new(File::open(path).expect("Should exist"));
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().expect("Should be a PNG");
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).expect("Should decode");
+    assert_eq!(
+        (info.width, info.height),
+        (160, 144),
+        "Should be a DMG screen"
+    );
+
+    let channels = info.color_type.samples();
+    let colour_channels = if channels > 2 { 3 } else { 1 };
+
+    data[..info.buffer_size()]
+        .chunks(channels)
+        .map(|pixel| {
+            let sum: usize = pixel[..colour_channels].iter().map(|&c| c as usize).sum();
+            match sum / colour_channels {
+                0xC0.. => Color::White,
+                0x80.. => Color::LightGray,
+                0x40.. => Color::DarkGray,
+                _ => Color::Black,
+            }
+        })
+        .collect()
 }
 
 pub struct TestRenderer;
 pub struct TestPersister;
 
+/// Keeps the shades of the last frame, for comparing against a reference
+/// image regardless of the palette colours.
+#[derive(Default)]
+pub struct ShadeRenderer {
+    shades: Mutex<Vec<Color>>,
+}
+
 impl Renderer for TestRenderer {
-    fn render(&self, _: [u32; 160 * 144]) {}
+    fn render(&self, _: &Frame) {}
+}
+
+impl Renderer for ShadeRenderer {
+    fn render(&self, frame: &Frame) {
+        let indexed = frame.indexed.expect("Should have indexed output enabled");
+        let mut shades = self.shades.lock().expect("Should lock");
+        *shades = indexed.iter().map(|pixel| pixel.color).collect();
+    }
 }
 
 impl CartridgePersistence for TestPersister {